@@ -0,0 +1,106 @@
+//! Short tool names standing for repositories, e.g. `grd install rg`
+
+use std::collections::BTreeMap;
+
+use anyhow::{Result, anyhow};
+
+use crate::{config::Config, table::print_table};
+
+/// Aliases known out of the box; definition files and the `[aliases]` config table add to and
+/// override them
+const BUILTIN: &[(&str, &str)] = &[
+    ("bat", "sharkdp/bat"),
+    ("btm", "ClementTsang/bottom"),
+    ("delta", "dandavison/delta"),
+    ("dust", "bootandy/dust"),
+    ("eza", "eza-community/eza"),
+    ("fd", "sharkdp/fd"),
+    ("fzf", "junegunn/fzf"),
+    ("gh", "cli/cli"),
+    ("grd", "lucidfrontier45/grd"),
+    ("hyperfine", "sharkdp/hyperfine"),
+    ("jq", "jqlang/jq"),
+    ("just", "casey/just"),
+    ("k9s", "derailed/k9s"),
+    ("lazygit", "jesseduffield/lazygit"),
+    ("rg", "BurntSushi/ripgrep"),
+    ("ruff", "astral-sh/ruff"),
+    ("sd", "chmln/sd"),
+    ("starship", "starship/starship"),
+    ("tokei", "XAMPPRocky/tokei"),
+    ("uv", "astral-sh/uv"),
+    ("yq", "mikefarah/yq"),
+    ("zoxide", "ajeetdsouza/zoxide"),
+];
+
+/// Every alias with its repository and whether it comes from the config file rather than being
+/// built in or defined
+pub fn table(config: &Config) -> BTreeMap<String, (String, bool)> {
+    let mut table: BTreeMap<String, (String, bool)> = BUILTIN
+        .iter()
+        .map(|(name, repo)| (name.to_string(), (repo.to_string(), false)))
+        .collect();
+    for (name, repo) in &config.definitions.aliases {
+        table.insert(name.clone(), (repo.clone(), false));
+    }
+    for (name, repo) in &config.aliases {
+        table.insert(name.clone(), (repo.clone(), true));
+    }
+    table
+}
+
+/// The repository `name` stands for; names containing a `/` already are one
+pub fn resolve(name: &str) -> Result<String> {
+    if name.contains('/') {
+        return Ok(name.to_string());
+    }
+    let config = Config::load()?;
+    table(&config)
+        .remove(name)
+        .map(|(repo, _)| repo)
+        .ok_or_else(|| {
+            anyhow!(
+                "Unknown tool '{}': give its repository as owner/repo, or add an alias with `grd config set aliases.{} <owner/repo>`",
+                name,
+                name
+            )
+        })
+}
+
+/// Fail unless `name` can be typed in place of a repository and `repo` is `owner/repo`
+pub fn check(name: &str, repo: &str) -> Result<()> {
+    if name.is_empty() || name.contains('/') {
+        return Err(anyhow!("Invalid alias name '{}'", name));
+    }
+    let valid = repo
+        .split_once('/')
+        .is_some_and(|(owner, name)| !owner.is_empty() && !name.is_empty() && !name.contains('/'));
+    if !valid {
+        return Err(anyhow!(
+            "Invalid repository '{}' for alias '{}', expected owner/repo",
+            repo,
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Print every alias with its repository and where it comes from
+pub fn list() -> Result<()> {
+    let config = Config::load()?;
+    let rows: Vec<Vec<String>> = table(&config)
+        .into_iter()
+        .map(|(name, (repo, configured))| {
+            let source = if configured {
+                "config"
+            } else if config.definitions.aliases.contains_key(&name) {
+                "definitions"
+            } else {
+                "built-in"
+            };
+            vec![name, repo, source.to_string()]
+        })
+        .collect();
+    print_table(&["ALIAS", "REPO", "SOURCE"], &rows);
+    Ok(())
+}
@@ -0,0 +1,265 @@
+//! Downloaded release assets kept under `assets/` in the cache directory, so that installing the
+//! same version again (elsewhere, in another scope, or by `grd run`) needs no download
+//!
+//! Each entry is a directory named after the asset's URL and the digest GitHub computed for it,
+//! holding the asset as `data` and its `meta.json`. An entry whose digest GitHub reports is used as
+//! is; other entries are revalidated with the server by their `ETag` first.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+
+use crate::{download::DownloadSource, exec, install::file_sha256, local, release::Asset, state};
+
+const DATA: &str = "data";
+const META: &str = "meta.json";
+
+#[derive(Serialize, Deserialize)]
+struct Meta {
+    url: String,
+    name: String,
+    size: u64,
+    sha256: String,
+    etag: Option<String>,
+    /// Unix time the asset was downloaded at
+    cached_at: u64,
+}
+
+/// The cache entry of one asset
+pub struct Entry {
+    dir: PathBuf,
+    meta: Option<Meta>,
+}
+
+impl Entry {
+    /// The entry of `asset`, cached or not
+    pub fn of(asset: &Asset) -> Result<Entry> {
+        Ok(Entry::under(&dir()?, asset))
+    }
+
+    /// The entry of `asset` in the asset cache at `root`
+    fn under(root: &Path, asset: &Asset) -> Entry {
+        let mut hasher = Sha256::new();
+        hasher.update(asset.browser_download_url.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(asset.digest.as_deref().unwrap_or_default().as_bytes());
+        let dir = root.join(format!("{:x}", hasher.finalize()));
+        let meta = fs::read_to_string(dir.join(META))
+            .ok()
+            .and_then(|text| serde_json::from_str::<Meta>(&text).ok())
+            .filter(|meta| meta.url == asset.browser_download_url && meta.size == asset.size);
+        Entry { dir, meta }
+    }
+
+    /// The cached asset, when the digest GitHub reports for it vouches that it is unchanged
+    pub fn fresh(&self, asset: &Asset, memory_limit: u64) -> Option<DownloadSource> {
+        asset.digest.as_ref()?;
+        self.load(memory_limit).ok()
+    }
+
+    /// Entity tag to ask the server whether the cached asset is still current
+    pub fn etag(&self) -> Option<&str> {
+        self.meta.as_ref()?.etag.as_deref()
+    }
+
+    /// The cached asset, checked against the digest it was stored with
+    pub fn load(&self, memory_limit: u64) -> Result<DownloadSource> {
+        let meta = self
+            .meta
+            .as_ref()
+            .ok_or_else(|| anyhow!("{:?} is not cached", self.dir))?;
+        let path = self.dir.join(DATA);
+        if file_sha256(&path)? != meta.sha256 {
+            let _ = fs::remove_dir_all(&self.dir);
+            return Err(anyhow!("The cached copy of {} is corrupt", meta.name));
+        }
+        // Touched, so that pruning by age spares the assets still in use
+        let _ = fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(SystemTime::now()));
+        local::read(&path, memory_limit)
+    }
+
+    /// Cache `source`, downloaded from `asset` with the entity tag `etag`
+    pub fn store(&self, asset: &Asset, source: &DownloadSource, etag: Option<&str>) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let mut temp_file = NamedTempFile::new_in(&self.dir)?;
+        std::io::copy(&mut source.reader()?, &mut temp_file)?;
+        temp_file.persist(self.dir.join(DATA))?;
+        let meta = Meta {
+            url: asset.browser_download_url.clone(),
+            name: asset.name.clone(),
+            size: asset.size,
+            sha256: source.sha256()?,
+            etag: etag.map(str::to_string),
+            cached_at: state::now(),
+        };
+        fs::write(self.dir.join(META), serde_json::to_string_pretty(&meta)?)?;
+        Ok(())
+    }
+}
+
+/// Directory of the asset cache
+pub fn dir() -> Result<PathBuf> {
+    Ok(state::cache_dir()?.join("assets"))
+}
+
+/// The cached assets, with the time they were last used and their size
+pub fn entries() -> Result<Vec<(SystemTime, u64, PathBuf)>> {
+    entries_in(&dir()?)
+}
+
+fn entries_in(root: &Path) -> Result<Vec<(SystemTime, u64, PathBuf)>> {
+    let mut entries = Vec::new();
+    let Ok(dir) = fs::read_dir(root) else {
+        return Ok(entries);
+    };
+    for entry in dir {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let last_used = fs::metadata(path.join(DATA))
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        entries.push((last_used, exec::dir_size(&path), path));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+    use crate::{http, progress::ProgressBar};
+
+    const DATA_BYTES: &[u8] = b"asset bytes";
+
+    fn asset(url: &str, digest: Option<&str>) -> Asset {
+        Asset {
+            name: "rg.tar.gz".to_string(),
+            browser_download_url: url.to_string(),
+            size: DATA_BYTES.len() as u64,
+            digest: digest.map(str::to_string),
+            content_type: None,
+            download_count: 0,
+        }
+    }
+
+    #[test]
+    fn entries_are_keyed_by_url_and_digest() {
+        let root = Path::new("/cache/assets");
+        let key = |asset: &Asset| Entry::under(root, asset).dir;
+        let url = "https://example.com/rg.tar.gz";
+        let base = key(&asset(url, Some("sha256:00")));
+        assert_eq!(base, key(&asset(url, Some("sha256:00"))));
+        assert_ne!(base, key(&asset(url, Some("sha256:11"))));
+        assert_ne!(base, key(&asset(url, None)));
+        assert_ne!(
+            base,
+            key(&asset("https://example.com/fd.tar.gz", Some("sha256:00")))
+        );
+        assert_eq!(base.parent(), Some(root));
+    }
+
+    #[test]
+    fn stored_assets_are_loaded_with_their_etag() {
+        let root = tempfile::tempdir().unwrap();
+        let verified = asset("https://example.com/rg.tar.gz", Some("sha256:00"));
+        let unverified = asset("https://example.com/rg.tar.gz", None);
+        for asset in [&verified, &unverified] {
+            let source = DownloadSource::Memory(DATA_BYTES.to_vec());
+            Entry::under(root.path(), asset)
+                .store(asset, &source, Some("\"v1\""))
+                .unwrap();
+        }
+
+        let entry = Entry::under(root.path(), &verified);
+        assert_eq!(entry.etag(), Some("\"v1\""));
+        let fresh = entry.fresh(&verified, u64::MAX).unwrap();
+        assert_eq!(fresh.sha256().unwrap(), entry.meta.as_ref().unwrap().sha256);
+        // Without a digest the server has to confirm the copy first
+        let entry = Entry::under(root.path(), &unverified);
+        assert!(entry.fresh(&unverified, u64::MAX).is_none());
+        assert!(entry.load(u64::MAX).is_ok());
+
+        // An entry recorded with another size is not this asset
+        let resized = Asset {
+            size: 1,
+            ..verified.clone()
+        };
+        assert_eq!(Entry::under(root.path(), &resized).etag(), None);
+        assert_eq!(entries_in(root.path()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn corrupt_entries_are_dropped() {
+        let root = tempfile::tempdir().unwrap();
+        let asset = asset("https://example.com/rg.tar.gz", Some("sha256:00"));
+        let source = DownloadSource::Memory(DATA_BYTES.to_vec());
+        Entry::under(root.path(), &asset)
+            .store(&asset, &source, None)
+            .unwrap();
+        let entry = Entry::under(root.path(), &asset);
+        fs::write(entry.dir.join(DATA), b"asset bytez").unwrap();
+
+        assert!(entry.fresh(&asset, u64::MAX).is_none());
+        assert!(!entry.dir.exists());
+        assert!(Entry::under(root.path(), &asset).meta.is_none());
+    }
+
+    /// Serve `count` requests, answering 304 to those still having entity tag `"v1"`
+    fn serve(count: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/rg.tar.gz", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming().take(count) {
+                let mut stream = stream.unwrap();
+                let mut current = false;
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap().to_lowercase();
+                    if line.is_empty() {
+                        break;
+                    }
+                    current |= line == "if-none-match: \"v1\"";
+                }
+                let response = if current {
+                    "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n".to_string()
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nETag: \"v2\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\nasset bytes",
+                        DATA_BYTES.len()
+                    )
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn cached_assets_are_revalidated_by_etag() {
+        let url = serve(3);
+        let agent = http::build_agent();
+        let pb = ProgressBar::hidden();
+        let get = |etag| http::get_if_none_match(&agent, &url, etag, &pb).unwrap();
+        assert!(get(Some("\"v1\"")).is_none());
+        let mut response = get(Some("\"v0\"")).unwrap();
+        assert_eq!(response.headers()["etag"], "\"v2\"");
+        assert_eq!(response.body_mut().read_to_vec().unwrap(), DATA_BYTES);
+        assert!(get(None).is_some());
+    }
+}
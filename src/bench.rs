@@ -0,0 +1,134 @@
+use std::{
+    io::{self, Read, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use tempfile::NamedTempFile;
+use ureq::Agent;
+
+use crate::{
+    http, install::InstallOptions, progress::ProgressBar, release::Asset, resolve::resolve_release,
+    select::select_asset, units::format_size,
+};
+
+/// Time downloading an asset with each strategy grd could use, to tune `--memory-limit`
+/// and segmented downloads for the local network and disk
+pub fn bench(agent: &Agent, repo: &str, connections: usize, opts: &InstallOptions) -> Result<()> {
+    let release = resolve_release(agent, repo, opts)?;
+    let asset = select_asset(&release.assets, opts)?;
+    eprintln!(
+        "Benchmarking {} ({}) from {}",
+        asset.name,
+        format_size(asset.size, opts.si),
+        release.tag_name
+    );
+
+    let mut results = Vec::new();
+    eprintln!("Single stream into memory...");
+    results.push((
+        "single stream, memory".to_string(),
+        timed(|| single_stream(agent, &asset, false))?,
+        format_size(asset.size, opts.si),
+    ));
+    eprintln!("Single stream into a temp file...");
+    results.push((
+        "single stream, temp file".to_string(),
+        timed(|| single_stream(agent, &asset, true))?,
+        format_size(8 * 1024, opts.si),
+    ));
+    eprintln!("{} segments into memory...", connections);
+    let segmented = format!("{} segments, memory", connections);
+    match timed(|| segmented_download(agent, &asset, connections)) {
+        Ok(elapsed) => results.push((segmented, elapsed, format_size(asset.size, opts.si))),
+        Err(e) => eprintln!("Skipped segmented download: {:#}", e),
+    }
+
+    println!(
+        "{:<28} {:>10} {:>14} {:>12}",
+        "STRATEGY", "TIME", "THROUGHPUT", "BUFFER"
+    );
+    for (strategy, elapsed, buffer) in &results {
+        let per_second = (asset.size as f64 / elapsed.as_secs_f64()) as u64;
+        println!(
+            "{:<28} {:>9.2}s {:>12}/s {:>12}",
+            strategy,
+            elapsed.as_secs_f64(),
+            format_size(per_second, opts.si),
+            buffer
+        );
+    }
+    Ok(())
+}
+
+fn timed(run: impl FnOnce() -> Result<()>) -> Result<Duration> {
+    let start = Instant::now();
+    run()?;
+    Ok(start.elapsed())
+}
+
+/// Download the asset in one request, into memory or through a temp file
+fn single_stream(agent: &Agent, asset: &Asset, to_disk: bool) -> Result<()> {
+    let mut response =
+        http::get_with_retry(agent, &asset.browser_download_url, &ProgressBar::hidden())?;
+    let mut reader = response.body_mut().as_reader();
+    let received = if to_disk {
+        let mut temp_file = NamedTempFile::new()?;
+        let received = io::copy(&mut reader, &mut temp_file)?;
+        temp_file.flush()?;
+        received
+    } else {
+        let mut bytes = Vec::with_capacity(asset.size as usize);
+        reader.read_to_end(&mut bytes)? as u64
+    };
+    check_size(asset, received)
+}
+
+/// Download the asset as `connections` byte ranges fetched in parallel
+fn segmented_download(agent: &Agent, asset: &Asset, connections: usize) -> Result<()> {
+    let connections = connections.max(1) as u64;
+    let segment = asset.size.div_ceil(connections).max(1);
+    let received = thread::scope(|scope| {
+        let handles: Vec<_> = (0..asset.size)
+            .step_by(segment as usize)
+            .map(|start| {
+                let end = (start + segment).min(asset.size) - 1;
+                scope.spawn(move || fetch_range(agent, &asset.browser_download_url, start, end))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .map_err(|_| anyhow!("Download thread panicked"))?
+            })
+            .sum::<Result<u64>>()
+    })?;
+    check_size(asset, received)
+}
+
+fn fetch_range(agent: &Agent, url: &str, start: u64, end: u64) -> Result<u64> {
+    let mut response = agent
+        .get(url)
+        .header("Range", format!("bytes={}-{}", start, end))
+        .call()?;
+    if response.status().as_u16() != 206 {
+        return Err(anyhow!("the server does not support range requests"));
+    }
+    let mut bytes = Vec::with_capacity((end - start + 1) as usize);
+    Ok(response.body_mut().as_reader().read_to_end(&mut bytes)? as u64)
+}
+
+fn check_size(asset: &Asset, received: u64) -> Result<()> {
+    if received != asset.size {
+        return Err(anyhow!(
+            "Received {} bytes of {}, expected {}",
+            received,
+            asset.name,
+            asset.size
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use std::{fs::File, io::Read, path::Path};
+
+use anyhow::{Result, anyhow};
+
+use crate::gha;
+
+/// Platform an executable is built for, read from its header
+#[derive(Debug, PartialEq, Eq)]
+pub struct Format {
+    pub os: &'static str,
+    /// Architectures it contains (several for macOS universal binaries)
+    pub archs: Vec<&'static str>,
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.os, self.archs.join("+"))
+    }
+}
+
+/// Detect ELF, PE and Mach-O (thin or universal) executables; `None` for anything else
+pub fn detect(header: &[u8]) -> Option<Format> {
+    let u16_le = |at: usize| Some(u16::from_le_bytes(header.get(at..at + 2)?.try_into().ok()?));
+    let u32_le = |at: usize| Some(u32::from_le_bytes(header.get(at..at + 4)?.try_into().ok()?));
+    let u32_be = |at: usize| Some(u32::from_be_bytes(header.get(at..at + 4)?.try_into().ok()?));
+
+    match header.get(..4)? {
+        [0x7f, b'E', b'L', b'F'] => {
+            // Other ELF systems (FreeBSD, NetBSD, Solaris) mark themselves in EI_OSABI
+            if !matches!(header.get(7)?, 0 | 3) {
+                return None;
+            }
+            let machine = match header.get(5)? {
+                2 => u16::from_be_bytes(header.get(18..20)?.try_into().ok()?),
+                _ => u16_le(18)?,
+            };
+            let arch = match machine {
+                0x3e => "x86_64",
+                0xb7 => "aarch64",
+                0x03 => "x86",
+                0x28 => "arm",
+                0xf3 => "riscv64",
+                _ => "unknown",
+            };
+            Some(Format {
+                os: "linux",
+                archs: vec![arch],
+            })
+        }
+        [b'M', b'Z', ..] => {
+            let pe = u32_le(0x3c)? as usize;
+            if header.get(pe..pe + 4)? != b"PE\0\0" {
+                return None;
+            }
+            let arch = match u16_le(pe + 4)? {
+                0x8664 => "x86_64",
+                0xaa64 => "aarch64",
+                0x014c => "x86",
+                0x01c4 => "arm",
+                _ => "unknown",
+            };
+            Some(Format {
+                os: "windows",
+                archs: vec![arch],
+            })
+        }
+        [0xcf, 0xfa, 0xed, 0xfe] | [0xce, 0xfa, 0xed, 0xfe] => Some(Format {
+            os: "macos",
+            archs: vec![macho_arch(u32_le(4)?)],
+        }),
+        [0xca, 0xfe, 0xba, 0xbe] => {
+            // Java class files share the magic; their version number is far above any arch count
+            let count = u32_be(4)? as usize;
+            if count == 0 || count > 16 {
+                return None;
+            }
+            let archs = (0..count)
+                .map(|i| u32_be(8 + i * 20).map(macho_arch))
+                .collect::<Option<Vec<_>>>()?;
+            Some(Format { os: "macos", archs })
+        }
+        _ => None,
+    }
+}
+
+fn macho_arch(cputype: u32) -> &'static str {
+    match cputype {
+        0x0100_0007 => "x86_64",
+        0x0100_000c => "aarch64",
+        0x0000_0007 => "x86",
+        0x0000_000c => "arm",
+        _ => "unknown",
+    }
+}
+
+/// Warn (or fail when `strict`) if an installed executable is built for another platform than
+/// the selected `os`/`arch`
+pub fn check_format(path: &Path, os: &str, arch: &str, strict: bool) -> Result<()> {
+    let mut header = Vec::with_capacity(4096);
+    File::open(path)?.take(4096).read_to_end(&mut header)?;
+    let Some(format) = detect(&header) else {
+        return Ok(());
+    };
+    if format.os == os && format.archs.contains(&arch) {
+        return Ok(());
+    }
+    let message = format!(
+        "{:?} is a {} executable, not {}-{} as selected; the asset was probably misidentified",
+        path, format, os, arch
+    );
+    if strict {
+        return Err(anyhow!("{} (--strict-format)", message));
+    }
+    gha::warning(&message);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 64-bit ELF header of byte order `data` (1 little, 2 big) with `osabi` and `machine`
+    fn elf(data: u8, osabi: u8, machine: u16) -> Vec<u8> {
+        let mut header = vec![0x7f, b'E', b'L', b'F', 2, data, 1, osabi];
+        header.resize(18, 0);
+        header.extend(match data {
+            2 => machine.to_be_bytes(),
+            _ => machine.to_le_bytes(),
+        });
+        header
+    }
+
+    /// DOS stub pointing at a PE header for `machine`
+    fn pe(machine: u16) -> Vec<u8> {
+        let mut header = vec![b'M', b'Z'];
+        header.resize(0x3c, 0);
+        header.extend(0x80u32.to_le_bytes());
+        header.resize(0x80, 0);
+        header.extend(b"PE\0\0");
+        header.extend(machine.to_le_bytes());
+        header
+    }
+
+    fn macho(cputype: u32) -> Vec<u8> {
+        let mut header = vec![0xcf, 0xfa, 0xed, 0xfe];
+        header.extend(cputype.to_le_bytes());
+        header
+    }
+
+    /// Universal binary of `cputypes`, or a class file of version `cputypes.len()`
+    fn fat(cputypes: &[u32]) -> Vec<u8> {
+        let mut header = vec![0xca, 0xfe, 0xba, 0xbe];
+        header.extend((cputypes.len() as u32).to_be_bytes());
+        for cputype in cputypes {
+            header.extend(cputype.to_be_bytes());
+            header.extend([0; 16]);
+        }
+        header
+    }
+
+    #[test]
+    fn detects_executable_formats() {
+        let format = |os, archs: &[&'static str]| {
+            Some(Format {
+                os,
+                archs: archs.to_vec(),
+            })
+        };
+        for (header, expected) in [
+            (elf(1, 0, 0x3e), format("linux", &["x86_64"])),
+            (elf(1, 3, 0xb7), format("linux", &["aarch64"])),
+            (elf(2, 0, 0x28), format("linux", &["arm"])),
+            (elf(1, 0, 0xf3), format("linux", &["riscv64"])),
+            (elf(1, 0, 0x1234), format("linux", &["unknown"])),
+            // FreeBSD
+            (elf(1, 9, 0x3e), None),
+            (pe(0x8664), format("windows", &["x86_64"])),
+            (pe(0xaa64), format("windows", &["aarch64"])),
+            (pe(0x014c), format("windows", &["x86"])),
+            (macho(0x0100_000c), format("macos", &["aarch64"])),
+            (macho(0x0100_0007), format("macos", &["x86_64"])),
+            (
+                fat(&[0x0100_0007, 0x0100_000c]),
+                format("macos", &["x86_64", "aarch64"]),
+            ),
+            // A Java class file (major version 52) shares the universal binary's magic
+            (fat(&[0; 52]), None),
+            (fat(&[]), None),
+            (b"#!/bin/sh\n".to_vec(), None),
+            (b"MZ".to_vec(), None),
+            (Vec::new(), None),
+        ] {
+            assert_eq!(detect(&header), expected, "{:02x?}", header);
+        }
+    }
+
+    #[test]
+    fn dos_stubs_without_a_pe_header_are_unknown() {
+        let mut header = pe(0x8664);
+        header[0x80] = b'N';
+        assert_eq!(detect(&header), None);
+    }
+}
@@ -0,0 +1,518 @@
+//! Bundles: manifest tools packed for places without network access
+//!
+//! `grd bundle-dir` installs them into a directory shipped as is; `grd bundle create` packs their
+//! release assets into a single archive that `grd bundle install` installs from:
+//!
+//! ```text
+//! bundle.json                          tools, tags, and the name, URL and SHA-256 of each asset
+//! manifest/<file>                      the manifest the bundle was created from
+//! assets/<tool>/<os>-<arch>/<asset>    the assets, as downloaded
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+    fs::{self, File},
+    io::BufReader,
+    path::Path,
+};
+
+use anyhow::{Context, Result, anyhow};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use tempfile::{NamedTempFile, TempPath};
+use ureq::Agent;
+
+use crate::{
+    Layout,
+    config::Config,
+    download::{DownloadSource, download_asset},
+    gha,
+    hooks::{Change, Event, ToolHooks},
+    host,
+    install::{
+        InstallOptions, Installed, default_bin_name, expectations, install, install_asset,
+        installed_bin_name, post_install, receipt_for,
+    },
+    lockfile::{self, Lockfile},
+    manifest::Manifest,
+    release::Asset,
+    release_files,
+    resolve::{locked_asset, resolve_release},
+    select::{parse_platform, resolve_platform, select_asset},
+    state::{self, Scope, State},
+    token,
+    units::format_size,
+    update::remove_stale_files,
+    verify, wrapper,
+};
+
+/// Format version of `bundle.json`
+const BUNDLE_VERSION: u32 = 1;
+
+/// Metadata file at the root of a bundle archive
+const METADATA: &str = "bundle.json";
+
+#[derive(Serialize, Deserialize)]
+struct Metadata {
+    version: u32,
+    /// Unix time the bundle was created at
+    created_at: u64,
+    /// File name of the manifest under `manifest/`
+    manifest: String,
+    tools: BTreeMap<String, BundledTool>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundledTool {
+    repo: String,
+    tag: String,
+    /// Asset of each platform (`<os>-<arch>`)
+    assets: BTreeMap<String, BundledAsset>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BundledAsset {
+    name: String,
+    url: String,
+    size: u64,
+    sha256: String,
+    content_type: Option<String>,
+}
+
+/// Describes one tool of a bundle; paths are relative to the bundle root
+#[derive(Serialize, Deserialize, Debug)]
+struct BundleReceipt {
+    name: String,
+    repo: String,
+    tag: String,
+    asset: String,
+    files: Vec<String>,
+}
+
+/// Install every manifest entry into a self-contained directory that can be shipped as is
+///
+/// Re-running refreshes an existing bundle; held tools keep the version they were bundled with.
+pub fn bundle_dir(agent: &Agent, manifest_path: &Path, output: &Path) -> Result<()> {
+    let manifest = Manifest::load(manifest_path)?;
+    let config = Config::load()?;
+    token::preflight(agent, &manifest.repos())?;
+
+    let bin_dir = output.join("bin");
+    let receipts_dir = output.join("receipts");
+    for dir in [&bin_dir, &output.join("share"), &receipts_dir] {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut bundled = 0;
+    for (name, spec) in &manifest.tools {
+        let receipt_path = receipts_dir.join(format!("{}.json", name));
+        if spec.hold
+            && let Ok(text) = fs::read_to_string(&receipt_path)
+        {
+            let receipt: BundleReceipt = serde_json::from_str(&text)?;
+            eprintln!("==> {} held at {}", name, receipt.tag);
+            continue;
+        }
+
+        eprintln!("==> {} ({})", name, spec.repo);
+        let opts = InstallOptions {
+            destination: output.to_path_buf(),
+            layout: Layout::Fhs,
+            ..spec.install_options(name, &config)?
+        };
+        let installed = install(agent, &spec.repo, &opts)?;
+        let receipt = bundle_receipt(output, name, &spec.repo, installed)?;
+        fs::write(&receipt_path, serde_json::to_string_pretty(&receipt)?)?;
+        bundled += 1;
+    }
+
+    eprintln!("Bundled {} tool(s) into {:?}", bundled, output);
+    gha::notice(&format!(
+        "Bundled {} tool(s) into {}",
+        bundled,
+        output.display()
+    ));
+    Ok(())
+}
+
+/// The receipt of a tool installed into the bundle directory `output`
+fn bundle_receipt(
+    output: &Path,
+    name: &str,
+    repo: &str,
+    installed: Installed,
+) -> Result<BundleReceipt> {
+    let files = installed
+        .files
+        .iter()
+        .map(|path| {
+            Ok(path
+                .strip_prefix(output)?
+                .to_string_lossy()
+                .replace('\\', "/"))
+        })
+        .collect::<Result<_>>()?;
+    Ok(BundleReceipt {
+        name: name.to_string(),
+        repo: repo.to_string(),
+        tag: installed.tag,
+        asset: installed.asset.name,
+        files,
+    })
+}
+
+/// Pack the release assets of every manifest tool for `platforms` (the host by default) into the
+/// archive `output`, verified as an install would verify them
+///
+/// With `locked`, the releases and assets pinned in the manifest's lockfile are packed.
+pub fn create(
+    agent: &Agent,
+    manifest_path: &Path,
+    output: &Path,
+    platforms: &[String],
+    locked: bool,
+) -> Result<()> {
+    let manifest = Manifest::load(manifest_path)?;
+    let config = Config::load()?;
+    token::preflight(agent, &manifest.repos())?;
+    let lockfile = locked
+        .then(|| Lockfile::load(&lockfile::path_for(manifest_path)))
+        .transpose()?;
+    let mut platforms: BTreeSet<(String, String)> = platforms
+        .iter()
+        .map(|p| parse_platform(p))
+        .collect::<Result<_>>()?;
+    if platforms.is_empty() {
+        platforms.insert(resolve_platform(None, None)?);
+    }
+    let manifest_name = manifest_path
+        .file_name()
+        .ok_or_else(|| anyhow!("{:?} is not a manifest file", manifest_path))?
+        .to_string_lossy()
+        .to_string();
+
+    let staging = tempfile::tempdir()?;
+    let mut tools = BTreeMap::new();
+    for (name, spec) in &manifest.tools {
+        let mut opts = spec
+            .install_options(name, &config)
+            .with_context(|| format!("Invalid manifest entry '{}'", name))?;
+        if let Some(lockfile) = &lockfile {
+            // Never empty: the host platform is added above when none is given
+            let (os, arch) = platforms.first().expect("at least one platform");
+            opts.tag = Some(lockfile.pin(&spec.repo, Some(name), os, arch)?.0);
+        }
+        let release = resolve_release(agent, &spec.repo, &opts)?;
+        eprintln!("==> {} ({}) {}", name, spec.repo, release.tag_name);
+
+        let mut assets = BTreeMap::new();
+        for (os, arch) in &platforms {
+            opts.os = os.clone();
+            opts.arch = arch.clone();
+            let platform = format!("{}-{}", os, arch);
+            if let Some(lockfile) = &lockfile {
+                opts.locked = Some(lockfile.pin(&spec.repo, Some(name), os, arch)?.1);
+            }
+            let asset = match &opts.locked {
+                Some(locked) => locked_asset(&release, locked)?,
+                None => select_asset(&release.assets, &opts)
+                    .with_context(|| format!("Failed to bundle {} for {}", name, platform))?,
+            };
+            release_files::check_name(&asset.name)?;
+            eprintln!(
+                "{}: {} ({})",
+                platform,
+                asset.name,
+                format_size(asset.size, opts.si)
+            );
+            let expected = expectations(agent, &release, &asset, &opts)?;
+            let source = download_asset(agent, &asset, &opts)?;
+            let sha256 = source.sha256()?;
+            expected.check(&asset.name, &source, &sha256)?;
+            let dir = staging.path().join("assets").join(name).join(&platform);
+            fs::create_dir_all(&dir)?;
+            source.write_to(&dir.join(&asset.name))?;
+            assets.insert(
+                platform,
+                BundledAsset {
+                    name: asset.name,
+                    url: asset.browser_download_url,
+                    size: asset.size,
+                    sha256,
+                    content_type: asset.content_type,
+                },
+            );
+        }
+        tools.insert(
+            name.clone(),
+            BundledTool {
+                repo: spec.repo.clone(),
+                tag: release.tag_name,
+                assets,
+            },
+        );
+    }
+
+    let metadata = Metadata {
+        version: BUNDLE_VERSION,
+        created_at: state::now(),
+        manifest: manifest_name.clone(),
+        tools,
+    };
+    fs::write(
+        staging.path().join(METADATA),
+        serde_json::to_string_pretty(&metadata)?,
+    )?;
+    // Written next to the output and renamed, so that a failure leaves no partial bundle behind
+    let dir = output
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut builder = tar::Builder::new(GzEncoder::new(
+        NamedTempFile::new_in(dir)?,
+        Compression::default(),
+    ));
+    builder.append_path_with_name(staging.path().join(METADATA), METADATA)?;
+    builder.append_path_with_name(manifest_path, format!("manifest/{}", manifest_name))?;
+    if staging.path().join("assets").is_dir() {
+        builder.append_dir_all("assets", staging.path().join("assets"))?;
+    }
+    let file = builder.into_inner()?.finish()?;
+    file.persist(output)
+        .with_context(|| format!("Failed to write {:?}", output))?;
+
+    let size = fs::metadata(output)?.len();
+    eprintln!(
+        "Bundled {} tool(s) for {} platform(s) into {:?} ({})",
+        metadata.tools.len(),
+        platforms.len(),
+        output,
+        format_size(size, false)
+    );
+    gha::notice(&format!(
+        "Bundled {} tool(s) into {}",
+        metadata.tools.len(),
+        output.display()
+    ));
+    Ok(())
+}
+
+/// Install every tool of the bundle archive `path` in `scope` without network access, skipping
+/// those already at the bundled version
+///
+/// Tools are installed as `grd sync` would install the manifest; destinations it sets are taken
+/// relative to the current directory. The manifest's hooks are shell commands from whoever built
+/// the bundle, so they only run (in the current directory) and are only recorded with `run_hooks`.
+pub fn install_bundle(path: &Path, scope: Scope, run_hooks: bool) -> Result<()> {
+    let config = Config::load()?;
+    let unpacked = tempfile::tempdir()?;
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    tar::Archive::new(GzDecoder::new(BufReader::new(file)))
+        .unpack(unpacked.path())
+        .with_context(|| format!("Failed to unpack the bundle {:?}", path))?;
+    let metadata: Metadata = fs::read_to_string(unpacked.path().join(METADATA))
+        .map_err(anyhow::Error::from)
+        .and_then(|text| Ok(serde_json::from_str(&text)?))
+        .with_context(|| format!("{:?} is not a grd bundle", path))?;
+    if metadata.version != BUNDLE_VERSION {
+        return Err(anyhow!(
+            "{:?} has bundle format {}; this grd reads format {}",
+            path,
+            metadata.version,
+            BUNDLE_VERSION
+        ));
+    }
+    let manifest = Manifest::load(&unpacked.path().join("manifest").join(&metadata.manifest))?;
+    let base_dir = env::current_dir()?;
+    if scope == Scope::System {
+        state::ensure_writable(&scope.data_dir()?)?;
+    }
+
+    let mut state = State::load(scope)?;
+    let mut changed = 0;
+    for (name, spec) in &manifest.tools {
+        let tool = metadata
+            .tools
+            .get(name)
+            .ok_or_else(|| anyhow!("The bundle has no assets of {}", name))?;
+        let destination = match &spec.destination {
+            Some(dir) => base_dir.join(dir),
+            None => scope.bin_dir()?,
+        };
+        let mut hooks = spec.hooks(&base_dir);
+        if !run_hooks && !hooks.is_empty() {
+            gha::warning(&format!(
+                "Skipping the hooks of {} in the bundle; pass --run-hooks to run them",
+                name
+            ));
+            hooks = ToolHooks::default();
+        }
+        let opts = InstallOptions {
+            destination,
+            hooks,
+            scope,
+            ..spec
+                .install_options(name, &config)
+                .with_context(|| format!("Invalid manifest entry '{}'", name))?
+        };
+        let platform = format!("{}-{}", opts.os, opts.arch);
+        let bundled = tool.assets.get(&platform).ok_or_else(|| {
+            anyhow!(
+                "The bundle has no asset of {} for {} (only for {})",
+                name,
+                platform,
+                tool.assets.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })?;
+        let current = state
+            .tools
+            .get(name)
+            .filter(|r| r.repo.eq_ignore_ascii_case(&tool.repo))
+            .cloned();
+        if let Some(current) = current
+            .as_ref()
+            .filter(|c| c.tag == tool.tag && c.destination == opts.destination)
+        {
+            eprintln!("==> {} is up to date ({})", name, current.tag);
+            continue;
+        }
+
+        eprintln!("==> {} ({}) {}", name, tool.repo, tool.tag);
+        let asset = Asset {
+            name: bundled.name.clone(),
+            browser_download_url: bundled.url.clone(),
+            size: bundled.size,
+            digest: None,
+            content_type: bundled.content_type.clone(),
+            download_count: 0,
+        };
+        if opts.os == "linux" && env::consts::OS == "linux" {
+            host::check_libc(&asset.name, opts.strict_libc)?;
+        }
+        let change = Change {
+            tool: name,
+            repo: &tool.repo,
+            from: current.as_ref().map(|c| c.tag.as_str()),
+            to: &tool.tag,
+            destination: &opts.destination,
+        };
+        opts.hooks.run(Event::PreInstall, &change)?;
+        let event = match current {
+            Some(_) => Event::PostUpdate,
+            None => Event::PostInstall,
+        };
+
+        let mut expected = verify::Expectations::default();
+        expected.digests.extend(opts.checksum.iter().cloned());
+        expected
+            .digests
+            .push(verify::ExpectedDigest::new(&bundled.sha256, "the bundle"));
+        let asset_path = unpacked
+            .path()
+            .join("assets")
+            .join(name)
+            .join(&platform)
+            .join(&asset.name);
+        // The unpacked copy is read like a download and goes away with it
+        let fetch = || {
+            let file = File::open(&asset_path)
+                .with_context(|| format!("The bundle lacks {:?}", asset_path))?;
+            Ok(DownloadSource::Disk(NamedTempFile::from_parts(
+                file,
+                TempPath::from_path(&asset_path),
+            )))
+        };
+        let bin_name = opts
+            .bin_name
+            .clone()
+            .unwrap_or_else(|| default_bin_name(&tool.repo, &asset.name));
+        let (files, sha256) = install_asset(fetch, &asset, &bin_name, &tool.tag, &opts, &expected)?;
+        let mut installed = Installed {
+            tag: tool.tag.clone(),
+            published_at: None,
+            bin_name: installed_bin_name(bin_name, &files),
+            asset,
+            files,
+            sha256,
+        };
+        installed.files = wrapper::wrap(installed.files, &installed.tag, &config.wrappers, scope)?;
+        let receipt = receipt_for(&tool.repo, &installed, &opts)?;
+        if let Some(current) = &current {
+            remove_stale_files(current, &receipt)?;
+        }
+        state.record(name, receipt);
+        state.save()?;
+        post_install(&config, &state.tools[name])?;
+        opts.hooks.run(event, &change)?;
+        changed += 1;
+    }
+
+    eprintln!(
+        "Installed the bundle {:?}: {} of {} tool(s) installed or updated",
+        path,
+        changed,
+        manifest.tools.len()
+    );
+    if changed > 0 {
+        gha::notice(&format!("Installed {} tool(s) from a bundle", changed));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A release archive with an executable, a man page and a completion script
+    fn archive(dir: &Path) -> std::path::PathBuf {
+        let path = dir.join("tool-1.0-linux-x86_64.tar.gz");
+        let encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        for (name, mode) in [
+            ("tool-1.0/tool", 0o755),
+            ("tool-1.0/doc/tool.1", 0o644),
+            ("tool-1.0/completions/tool.bash", 0o644),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(5);
+            header.set_mode(mode);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, &b"data\n"[..])
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn receipts_list_files_relative_to_the_bundle() {
+        let assets = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let opts = InstallOptions {
+            destination: output.path().to_path_buf(),
+            layout: Layout::Fhs,
+            os: "linux".to_string(),
+            arch: "x86_64".to_string(),
+            tag: Some("v1.0".to_string()),
+            ..InstallOptions::default()
+        };
+        let installed =
+            crate::local::install(&archive(assets.path()), Some("owner/tool"), &opts).unwrap();
+        let receipt = bundle_receipt(output.path(), "tool", "owner/tool", installed).unwrap();
+
+        assert_eq!(receipt.tag, "v1.0");
+        assert_eq!(receipt.asset, "tool-1.0-linux-x86_64.tar.gz");
+        // Executables go to bin/, man pages and completions to share/
+        for file in [
+            "bin/tool",
+            "share/man/man1/tool.1",
+            "share/completions/tool.bash",
+        ] {
+            assert!(receipt.files.iter().any(|f| f == file), "{} missing", file);
+            assert!(output.path().join(file).is_file());
+        }
+        assert!(receipt.files.iter().all(|f| !Path::new(f).is_absolute()));
+    }
+}
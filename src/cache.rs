@@ -0,0 +1,159 @@
+//! Maintenance of the cache directory: downloaded assets, `grd run` objects and completion tags
+
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{asset_cache, exec, state, units::format_size};
+
+/// Print the cache directory
+pub fn dir() -> Result<()> {
+    println!("{}", state::cache_dir()?.display());
+    Ok(())
+}
+
+/// Remove everything from the cache directory
+pub fn clear() -> Result<()> {
+    let dir = state::cache_dir()?;
+    let Ok(entries) = fs::read_dir(&dir) else {
+        eprintln!("The cache {:?} is empty", dir);
+        return Ok(());
+    };
+    let mut freed = 0;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            freed += exec::dir_size(&path);
+            fs::remove_dir_all(&path)
+        } else {
+            freed += fs::metadata(&path).map_or(0, |m| m.len());
+            fs::remove_file(&path)
+        }
+        .with_context(|| format!("Failed to remove {:?}", path))?;
+    }
+    eprintln!(
+        "Cleared the cache {:?} ({})",
+        dir,
+        format_size(freed, false)
+    );
+    Ok(())
+}
+
+/// Remove cached assets, `grd run` objects and completion tags not used for `older_than`, then the
+/// least recently used ones until the cache fits in `max_size`
+pub fn prune(older_than: Option<Duration>, max_size: Option<u64>) -> Result<()> {
+    let cutoff = older_than.and_then(|age| SystemTime::now().checked_sub(age));
+    let (prunable, mut total) = select(items()?, cutoff, max_size);
+    let (mut removed, mut freed) = (0, 0);
+    for (size, path) in prunable {
+        if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        }
+        .with_context(|| format!("Failed to remove {:?}", path))?;
+        total -= size;
+        freed += size;
+        removed += 1;
+    }
+    eprintln!(
+        "Pruned {} cache entry(ies), freeing {}; {} left",
+        removed,
+        format_size(freed, false),
+        format_size(total, false)
+    );
+    Ok(())
+}
+
+/// The entries last used before `cutoff`, then the least recently used ones until the rest fits
+/// in `max_size`, with their sizes; and the current total size
+fn select(
+    mut entries: Vec<(SystemTime, u64, PathBuf)>,
+    cutoff: Option<SystemTime>,
+    max_size: Option<u64>,
+) -> (Vec<(u64, PathBuf)>, u64) {
+    entries.sort();
+    let total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+    let mut left = total;
+    let mut selected = Vec::new();
+    for (last_used, size, path) in entries {
+        let stale = cutoff.is_some_and(|cutoff| last_used < cutoff);
+        let over = max_size.is_some_and(|max| left > max);
+        if stale || over {
+            left -= size;
+            selected.push((size, path));
+        }
+    }
+    (selected, total)
+}
+
+/// Every prunable entry of the cache, with the time it was last used and its size
+fn items() -> Result<Vec<(SystemTime, u64, PathBuf)>> {
+    let dir = state::cache_dir()?;
+    let mut items = asset_cache::entries()?;
+    items.extend(exec::objects(&dir.join("run").join("objects"))?);
+    if let Ok(tags) = fs::read_dir(dir.join("tags")) {
+        for entry in tags {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                items.push((modified, metadata.len(), entry.path()));
+            }
+        }
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<(SystemTime, u64, PathBuf)> {
+        // Listed out of order, as read from the directories
+        [
+            (30, 100, "c"),
+            (10, 300, "a"),
+            (20, 200, "b"),
+            (40, 50, "d"),
+        ]
+        .into_iter()
+        .map(|(secs, size, name)| {
+            let used = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+            (used, size, PathBuf::from(name))
+        })
+        .collect()
+    }
+
+    fn names(selected: &[(u64, PathBuf)]) -> Vec<&str> {
+        selected
+            .iter()
+            .map(|(_, path)| path.to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn prunes_least_recently_used_entries() {
+        let at = |secs| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        for (cutoff, max_size, expected) in [
+            (None, None, vec![]),
+            (at(25), None, vec!["a", "b"]),
+            (at(5), None, vec![]),
+            (None, Some(650), vec![]),
+            (None, Some(400), vec!["a"]),
+            (None, Some(300), vec!["a", "b"]),
+            (None, Some(0), vec!["a", "b", "c", "d"]),
+            // Entries over the size budget go whether or not they're stale
+            (at(15), Some(200), vec!["a", "b"]),
+            (at(35), Some(600), vec!["a", "b", "c"]),
+        ] {
+            let (selected, total) = select(entries(), cutoff, max_size);
+            assert_eq!(total, 650);
+            assert_eq!(names(&selected), expected, "{:?} {:?}", cutoff, max_size);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::io;
+
+use anyhow::Result;
+use semver::VersionReq;
+use sha2::{Digest, Sha256};
+use ureq::Agent;
+
+use crate::{
+    http, install::InstallOptions, progress::ProgressBar, resolve::resolve_release,
+    select::select_asset, verify, version,
+};
+
+/// Print a key identifying exactly what grd would install, for CI caches
+///
+/// The asset digest comes from a published checksum file when there is one; otherwise the asset
+/// is downloaded and hashed, without being stored.
+pub fn cache_key(
+    agent: &Agent,
+    repo: &str,
+    version_req: Option<&VersionReq>,
+    opts: &InstallOptions,
+) -> Result<()> {
+    let release = match version_req {
+        Some(req) => version::latest_matching(agent, repo, req)?,
+        None => resolve_release(agent, repo, opts)?,
+    };
+    let asset = select_asset(&release.assets, opts)?;
+
+    let digest = match verify::api_digest(&asset) {
+        Some(digest) => Some(digest),
+        None => verify::published_digest(agent, &release.assets, &asset)?,
+    };
+    let sha256 = match digest {
+        Some(digest) => digest.sha256,
+        None => {
+            let mut response =
+                http::get_with_retry(agent, &asset.browser_download_url, &ProgressBar::hidden())?;
+            let mut hasher = Sha256::new();
+            io::copy(&mut response.body_mut().as_reader(), &mut hasher)?;
+            format!("{:x}", hasher.finalize())
+        }
+    };
+
+    println!(
+        "grd-{}-{}-{}",
+        repo.replace('/', "-"),
+        release.tag_name,
+        &sha256[..16]
+    );
+    Ok(())
+}
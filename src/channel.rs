@@ -0,0 +1,191 @@
+//! Release channels: which releases count as "latest", classified by tag conventions
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use ureq::Agent;
+
+use crate::{release::Release, resolve::fetch_release_info};
+
+/// Which releases count as "latest" for a tool
+///
+/// A channel includes the more stable ones, so that a tool tracking release candidates moves on
+/// to the final release once it is out.
+#[derive(
+    clap::ValueEnum,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// Only regular releases
+    #[default]
+    Stable,
+    /// Release candidates (`-rc`, `.rc1`) as well
+    Rc,
+    /// Betas, alphas and previews as well
+    Beta,
+    /// Nightly, canary and snapshot builds as well
+    Nightly,
+    /// Any prerelease, however it is tagged
+    Pre,
+}
+
+impl Channel {
+    /// The channel a release belongs to, from the conventions of its tag; other releases GitHub
+    /// flags as prereleases count as betas
+    pub fn of(release: &Release) -> Channel {
+        let tag = release.tag_name.to_ascii_lowercase();
+        let channel = if ["nightly", "canary", "snapshot"]
+            .iter()
+            .any(|word| tag.contains(word))
+        {
+            Channel::Nightly
+        } else if has_marker(&tag, "rc") {
+            Channel::Rc
+        } else if ["beta", "alpha", "preview", "pre"]
+            .iter()
+            .any(|word| has_marker(&tag, word))
+        {
+            Channel::Beta
+        } else {
+            Channel::Stable
+        };
+        match channel {
+            Channel::Stable if release.prerelease => Channel::Beta,
+            channel => channel,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Stable => "stable",
+            Channel::Rc => "rc",
+            Channel::Beta => "beta",
+            Channel::Nightly => "nightly",
+            Channel::Pre => "pre",
+        }
+    }
+
+    /// Whether `release` is part of this channel
+    pub fn accepts(self, release: &Release) -> bool {
+        !release.draft && Channel::of(release) <= self
+    }
+}
+
+/// The latest release of `repo` in `channel`
+///
+/// GitHub's own notion of latest serves the stable channel; the others take the newest matching
+/// entry of the release list, fetching further pages only until one is found.
+pub fn latest(agent: &Agent, repo: &str, channel: Channel) -> Result<Release> {
+    match channel {
+        Channel::Stable => return fetch_release_info(agent, repo, None, false),
+        Channel::Pre => return fetch_release_info(agent, repo, None, true),
+        _ => {}
+    }
+    const PER_PAGE: usize = 100;
+    for page in 1.. {
+        let url = format!(
+            "https://api.github.com/repos/{}/releases?per_page={}&page={}",
+            repo, PER_PAGE, page
+        );
+        let mut response = agent.get(&url).call()?;
+        let releases: Vec<Release> = response.body_mut().read_json()?;
+        let last = releases.len() < PER_PAGE;
+        if let Some(release) = releases
+            .into_iter()
+            .find(|r| channel.accepts(r) && !r.assets.is_empty())
+        {
+            return Ok(release);
+        }
+        if last {
+            break;
+        }
+    }
+    Err(anyhow!(
+        "No release of {} is in the {} channel",
+        repo,
+        channel.name()
+    ))
+}
+
+/// Whether `word` appears in `tag` as a marker of its own, like `rc` in `1.0.0-rc.1` or
+/// `v2.0rc1`, and unlike `rc` in `source`
+fn has_marker(tag: &str, word: &str) -> bool {
+    tag.match_indices(word).any(|(start, _)| {
+        let before = tag[..start].chars().next_back();
+        let after = tag[start + word.len()..].chars().next();
+        before.is_none_or(|c| !c.is_ascii_alphabetic())
+            && after.is_none_or(|c| !c.is_ascii_alphabetic())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(tag: &str, prerelease: bool, draft: bool) -> Release {
+        serde_json::from_value(serde_json::json!({
+            "tag_name": tag,
+            "assets": [],
+            "prerelease": prerelease,
+            "draft": draft,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn classifies_tags() {
+        for (tag, channel) in [
+            ("v1.2.3", Channel::Stable),
+            ("v1.0.0-source", Channel::Stable),
+            ("v1.0.0-rc.1", Channel::Rc),
+            ("v2.0rc1", Channel::Rc),
+            ("1.0.0-beta.2", Channel::Beta),
+            ("v3.0.0-Alpha", Channel::Beta),
+            ("v1.1-preview", Channel::Beta),
+            ("v1.0-pre1", Channel::Beta),
+            ("nightly-20240601", Channel::Nightly),
+            ("v1.0.0-SNAPSHOT", Channel::Nightly),
+            ("canary", Channel::Nightly),
+        ] {
+            assert_eq!(Channel::of(&release(tag, false, false)), channel, "{}", tag);
+        }
+    }
+
+    #[test]
+    fn flagged_prereleases_are_betas() {
+        assert_eq!(Channel::of(&release("v1.2.3", true, false)), Channel::Beta);
+        assert_eq!(
+            Channel::of(&release("v1.2.3-rc1", true, false)),
+            Channel::Rc
+        );
+    }
+
+    #[test]
+    fn channels_include_the_more_stable_ones() {
+        let rc = release("v1.0.0-rc.1", false, false);
+        let beta = release("v1.0.0-beta.1", false, false);
+        let stable = release("v1.0.0", false, false);
+        assert!(Channel::Rc.accepts(&rc) && Channel::Rc.accepts(&stable));
+        assert!(!Channel::Rc.accepts(&beta));
+        assert!(!Channel::Stable.accepts(&rc));
+        assert!(Channel::Pre.accepts(&release("nightly", false, false)));
+        assert!(!Channel::Pre.accepts(&release("v1.0.0", false, true)));
+    }
+
+    #[test]
+    fn markers_stand_on_their_own() {
+        assert!(has_marker("1.0.0-rc.1", "rc"));
+        assert!(has_marker("rc1", "rc"));
+        assert!(!has_marker("source", "rc"));
+        assert!(!has_marker("prepare", "pre"));
+    }
+}
@@ -0,0 +1,47 @@
+use anyhow::{Result, anyhow};
+use ureq::Agent;
+
+use crate::{
+    install::InstallOptions,
+    resolve::fetch_release_info,
+    select::{matching_assets, parse_platform},
+};
+
+/// Fail unless the release has an asset for every expected `os-arch` platform
+///
+/// Meant as a post-release CI gate for maintainers, catching targets that failed to build.
+pub fn check_assets(agent: &Agent, repo: &str, tag: Option<&str>, expect: &[String]) -> Result<()> {
+    let platforms = expect
+        .iter()
+        .map(|platform| parse_platform(platform))
+        .collect::<Result<Vec<_>>>()?;
+
+    let release = fetch_release_info(agent, repo, tag, false)?;
+    eprintln!("Checking assets of {} {}", repo, release.tag_name);
+    let mut missing = 0;
+    for (os, arch) in platforms {
+        let opts = InstallOptions {
+            os,
+            arch,
+            ..InstallOptions::default()
+        };
+        let matches = matching_assets(&release.assets, &opts);
+        if matches.is_empty() {
+            println!("missing  {}-{}", opts.os, opts.arch);
+            missing += 1;
+        } else {
+            let names: Vec<_> = matches.iter().map(|a| a.name.as_str()).collect();
+            println!("ok       {}-{}  {}", opts.os, opts.arch, names.join(", "));
+        }
+    }
+
+    if missing > 0 {
+        return Err(anyhow!(
+            "{} of {} expected platform(s) have no asset in {}",
+            missing,
+            expect.len(),
+            release.tag_name
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+//! Helpers called by shell completion scripts, hidden from the help
+
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Result, anyhow};
+use ureq::Agent;
+
+use crate::{release::Release, state};
+
+/// How long fetched tags are reused, so that repeated tab presses don't each hit the API
+const TAGS_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Print the tags of the recent releases of `repo`, newest first, one per line
+pub fn tags(agent: &Agent, repo: &str) -> Result<()> {
+    let path = state::cache_dir()?
+        .join("tags")
+        .join(format!("{}.txt", repo.replace('/', "__")));
+    if is_fresh(&path)
+        && let Ok(tags) = fs::read_to_string(&path)
+    {
+        print!("{}", tags);
+        return Ok(());
+    }
+
+    let url = format!(
+        "https://api.github.com/repos/{}/releases?per_page=100",
+        repo
+    );
+    let mut response = agent.get(&url).call()?;
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Failed to fetch releases of {}: {}",
+            repo,
+            response.status()
+        ));
+    }
+    let releases: Vec<Release> = response.body_mut().read_json()?;
+    let tags: String = releases
+        .iter()
+        .filter(|r| !r.draft)
+        .map(|r| format!("{}\n", r.tag_name))
+        .collect();
+    print!("{}", tags);
+
+    // The cache only saves requests; failing to write it doesn't matter
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir).and_then(|_| fs::write(&path, &tags));
+    }
+    Ok(())
+}
+
+fn is_fresh(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < TAGS_TTL)
+}
@@ -0,0 +1,229 @@
+use std::{collections::BTreeMap, env, fs, path::PathBuf, time::Duration};
+
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+
+use crate::{
+    channel::Channel,
+    definitions::{self, Definitions},
+    install::InstallOptions,
+    state,
+    units::parse_size,
+    wrapper::Wrapper,
+};
+
+/// User configuration, read from `config.toml`
+///
+/// ```toml
+/// definitions_repo = "https://github.com/owner/grd-definitions"
+///
+/// [minisign_keys]
+/// "jedisct1/minisign" = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3"
+///
+/// [gpg_keys]
+/// "owner/repo" = "/etc/grd/keys/owner.asc"
+///
+/// [extractors]
+/// "7z" = "7z"
+///
+/// [wrappers.mytool]
+/// env = { JAVA_HOME = "/opt/jdk-21" }
+///
+/// [aliases]
+/// rg = "BurntSushi/ripgrep"
+///
+/// [repo."owner/repo"]
+/// asset = "tool-{os}-{arch}.tar.gz"
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Minisign public keys trusted for each repository (`owner/repo` -> key)
+    #[serde(default)]
+    pub minisign_keys: BTreeMap<String, String>,
+
+    /// OpenPGP public key files trusted for each repository (`owner/repo` -> path)
+    #[serde(default)]
+    pub gpg_keys: BTreeMap<String, PathBuf>,
+
+    /// Extraction backend for each archive format (`zip`, `tar`, `7z`, `dmg` -> backend name)
+    #[serde(default)]
+    pub extractors: BTreeMap<String, String>,
+
+    /// Wrapper scripts generated around installed executables (executable name -> settings)
+    #[serde(default)]
+    pub wrappers: BTreeMap<String, Wrapper>,
+
+    /// Tool names standing for repositories, on top of the built-in ones (name -> `owner/repo`)
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+
+    /// Settings for repositories whose releases the generic heuristics get wrong (`owner/repo` ->
+    /// settings)
+    #[serde(default)]
+    pub repo: BTreeMap<String, RepoConfig>,
+
+    /// Git repository of shared tool definitions, fetched by `grd definitions update`
+    pub definitions_repo: Option<String>,
+
+    /// Refuse to install into destinations other users can tamper with, like `--paranoid`
+    #[serde(default)]
+    pub paranoid: bool,
+
+    /// Cache of the releases `grd run` executes
+    #[serde(default)]
+    pub run: RunConfig,
+
+    /// Aliases and repository rules from the definition files, which this file overrides
+    #[serde(skip)]
+    pub definitions: Definitions,
+}
+
+/// Limits of the `grd run` cache
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RunConfig {
+    /// How long the release a run resolved to is reused before looking it up again (e.g. `12h`)
+    pub ttl: Option<String>,
+
+    /// Size the cache is trimmed to, evicting the least recently run releases (e.g. `2GiB`)
+    pub max_size: Option<String>,
+}
+
+impl RunConfig {
+    pub fn ttl(&self) -> Result<Duration> {
+        match &self.ttl {
+            Some(ttl) => humantime::parse_duration(ttl)
+                .map_err(|e| anyhow!("Invalid run.ttl '{}': {}", ttl, e)),
+            None => Ok(Duration::from_secs(24 * 60 * 60)),
+        }
+    }
+
+    pub fn max_size(&self) -> Result<u64> {
+        match &self.max_size {
+            Some(size) => parse_size(size).map_err(|e| anyhow!("Invalid run.max_size: {}", e)),
+            None => Ok(1 << 30),
+        }
+    }
+}
+
+/// Per-repository overrides; options given on the command line or in a manifest win
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RepoConfig {
+    /// Executable name
+    pub bin_name: Option<String>,
+
+    /// Glob selecting the asset by name instead of the OS/architecture heuristics, with `{os}` and
+    /// `{arch}` replaced by the target platform
+    pub asset: Option<String>,
+
+    /// Comma-separated list of words to exclude from asset matching
+    pub exclude: Option<String>,
+
+    /// Path inside the archive of the file to install (exact or glob)
+    pub member: Option<String>,
+
+    /// Glob naming the release asset that lists the asset's SHA-256, with `{asset}` replaced by
+    /// the asset name (e.g. `{asset}.sha256sum`)
+    pub checksums: Option<String>,
+
+    /// Release channel tracked when no tag is given
+    pub channel: Option<Channel>,
+
+    /// Take the highest semantic version as the latest release, not GitHub's latest
+    pub latest_by_version: Option<bool>,
+
+    /// Shell commands run in the destination directory after each install or update
+    #[serde(default)]
+    pub post_install: Vec<String>,
+}
+
+impl RepoConfig {
+    /// Fill the options `opts` leaves unset from these settings
+    pub fn apply(&self, opts: &mut InstallOptions) -> Result<()> {
+        if let Some(asset) = &self.asset {
+            check_asset_pattern(asset)?;
+        }
+        opts.bin_name = opts.bin_name.take().or_else(|| self.bin_name.clone());
+        opts.exclude = opts.exclude.take().or_else(|| self.exclude.clone());
+        opts.channel = opts.channel.or(self.channel);
+        opts.latest_by_version |= self.latest_by_version.unwrap_or(false);
+        opts.asset_pattern = opts.asset_pattern.take().or_else(|| self.asset.clone());
+        opts.checksum_asset = opts
+            .checksum_asset
+            .take()
+            .or_else(|| self.checksums.clone());
+        // A member only replaces the default selection, never other selectors
+        let selects = opts.member.is_some() || opts.bin_pattern.is_some() || !opts.bins.is_empty();
+        if !selects && !opts.extract_all {
+            opts.member = self.member.clone();
+        }
+        Ok(())
+    }
+
+    /// These settings, with those they leave unset taken from `fallback`
+    fn or(self, fallback: &RepoConfig) -> RepoConfig {
+        RepoConfig {
+            bin_name: self.bin_name.or_else(|| fallback.bin_name.clone()),
+            asset: self.asset.or_else(|| fallback.asset.clone()),
+            exclude: self.exclude.or_else(|| fallback.exclude.clone()),
+            member: self.member.or_else(|| fallback.member.clone()),
+            checksums: self.checksums.or_else(|| fallback.checksums.clone()),
+            channel: self.channel.or(fallback.channel),
+            latest_by_version: self.latest_by_version.or(fallback.latest_by_version),
+            post_install: self.post_install,
+        }
+    }
+}
+
+/// Fail unless `asset` is a valid glob once its placeholders are filled in
+pub fn check_asset_pattern(asset: &str) -> Result<()> {
+    glob::Pattern::new(&asset.replace("{os}", "os").replace("{arch}", "arch"))
+        .map(|_| ())
+        .map_err(|e| anyhow!("Invalid asset glob '{}': {}", asset, e))
+}
+
+impl Config {
+    /// `$GRD_CONFIG`, or `grd/config.toml` in the platform's config directory
+    pub fn path() -> Result<PathBuf> {
+        if let Some(path) = env::var_os("GRD_CONFIG") {
+            return Ok(PathBuf::from(path));
+        }
+        let dir = if cfg!(windows) {
+            env::var_os("APPDATA")
+                .map(PathBuf::from)
+                .ok_or_else(|| anyhow!("APPDATA is not set"))?
+        } else if let Some(xdg) = env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+            PathBuf::from(xdg)
+        } else {
+            state::home()?.join(".config")
+        };
+        Ok(dir.join("grd").join("config.toml"))
+    }
+
+    /// Load the configuration, or the defaults if there is no config file, along with the tool
+    /// definitions
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        let mut config: Config = if path.exists() {
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read config file {:?}", path))?;
+            toml::from_str(&text)
+                .with_context(|| format!("Failed to parse config file {:?}", path))?
+        } else {
+            Self::default()
+        };
+        config.definitions = definitions::load()?;
+        Ok(config)
+    }
+
+    /// The overrides configured for `repo`, completed by its definition; empty when there are none
+    pub fn repo(&self, repo: &str) -> RepoConfig {
+        let configured = self.repo.get(repo).cloned().unwrap_or_default();
+        match self.definitions.repo.get(repo) {
+            Some(defined) => configured.or(defined),
+            None => configured,
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, anyhow};
+use ureq::Agent;
+
+use crate::{
+    alias,
+    config::{self, Config},
+    definitions, extract,
+    manifest::{Manifest, ToolSpec},
+    minisign,
+    plan::is_wildcard,
+    verify,
+};
+
+/// Validate the config file and the given manifests, printing every problem found
+///
+/// With `online`, every repository they mention is also looked up on GitHub.
+pub fn check(agent: &Agent, manifests: &[PathBuf], online: bool) -> Result<()> {
+    let mut problems = Vec::new();
+    let mut repos = BTreeSet::new();
+
+    let config_path = Config::path()?;
+    if config_path.exists() {
+        match Config::load() {
+            Ok(config) => {
+                check_config(&config, &config_path, &mut problems);
+                repos.extend(config.minisign_keys.keys().cloned());
+                repos.extend(config.gpg_keys.keys().cloned());
+                repos.extend(config.repo.keys().cloned());
+            }
+            Err(e) => problems.push(format!("{:#}", e)),
+        }
+        eprintln!("Checked {}", config_path.display());
+    } else {
+        eprintln!("No config file at {}", config_path.display());
+    }
+
+    for path in definitions::files()? {
+        if let Err(e) = definitions::read(&path) {
+            problems.push(format!("{:#}", e));
+        }
+        eprintln!("Checked {}", path.display());
+    }
+
+    for path in manifests {
+        match Manifest::load(path) {
+            Ok(manifest) => {
+                for (name, spec) in &manifest.tools {
+                    check_tool(
+                        spec,
+                        &format!("{}: tool '{}'", path.display(), name),
+                        &mut problems,
+                    );
+                    repos.insert(spec.repo.clone());
+                }
+            }
+            Err(e) => problems.push(format!("{:#}", e)),
+        }
+        eprintln!("Checked {}", path.display());
+    }
+
+    if online {
+        check_repos(agent, &repos, &mut problems);
+    }
+
+    if problems.is_empty() {
+        println!("No problems found");
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("  - {}", problem);
+    }
+    Err(anyhow!("{} problem(s) found", problems.len()))
+}
+
+fn check_config(config: &Config, path: &Path, problems: &mut Vec<String>) {
+    let at = path.display();
+    for (repo, key) in &config.minisign_keys {
+        check_repo_name(repo, &format!("{}: minisign_keys", at), problems);
+        if let Err(e) = minisign::parse_key(key) {
+            problems.push(format!("{}: minisign_keys.\"{}\": {}", at, repo, e));
+        }
+    }
+    for (repo, key) in &config.gpg_keys {
+        check_repo_name(repo, &format!("{}: gpg_keys", at), problems);
+        if !key.is_file() {
+            problems.push(format!(
+                "{}: gpg_keys.\"{}\": key file {:?} does not exist",
+                at, repo, key
+            ));
+        }
+    }
+    for (name, wrapper) in &config.wrappers {
+        for key in wrapper.env.keys() {
+            if key.is_empty() || key.contains(['=', '\0']) {
+                problems.push(format!(
+                    "{}: wrappers.\"{}\": invalid environment variable name '{}'",
+                    at, name, key
+                ));
+            }
+        }
+    }
+    for (repo, overrides) in &config.repo {
+        check_repo_name(repo, &format!("{}: repo", at), problems);
+        if let Some(asset) = &overrides.asset
+            && let Err(e) = config::check_asset_pattern(asset)
+        {
+            problems.push(format!("{}: repo.\"{}\": {}", at, repo, e));
+        }
+        if overrides.post_install.iter().any(|c| c.trim().is_empty()) {
+            problems.push(format!(
+                "{}: repo.\"{}\": empty post_install command",
+                at, repo
+            ));
+        }
+    }
+    if let Err(e) = config.run.ttl() {
+        problems.push(format!("{}: {}", at, e));
+    }
+    if let Err(e) = config.run.max_size() {
+        problems.push(format!("{}: {}", at, e));
+    }
+    for (name, repo) in &config.aliases {
+        if let Err(e) = alias::check(name, repo) {
+            problems.push(format!("{}: aliases: {}", at, e));
+        }
+    }
+    for (format, backend) in &config.extractors {
+        if let Err(e) = extract::check_choice(format, backend) {
+            problems.push(format!("{}: extractors.\"{}\": {}", at, format, e));
+        }
+    }
+}
+
+fn check_tool(spec: &ToolSpec, at: &str, problems: &mut Vec<String>) {
+    check_repo_name(&spec.repo, at, problems);
+    let globs = [
+        ("member", spec.member.as_deref()),
+        ("bin_pattern", spec.bin_pattern.as_deref()),
+        (
+            "bin_name",
+            spec.bin_name.as_deref().filter(|n| is_wildcard(n)),
+        ),
+    ];
+    for (field, glob) in globs {
+        if let Some(glob) = glob
+            && let Err(e) = glob::Pattern::new(glob)
+        {
+            problems.push(format!("{}: invalid {} glob '{}': {}", at, field, glob, e));
+        }
+    }
+    let selectors = [
+        spec.member.is_some(),
+        spec.bin_pattern.is_some(),
+        !spec.bins.is_empty(),
+    ];
+    if selectors.iter().filter(|set| **set).count() > 1 {
+        problems.push(format!(
+            "{}: member, bin_pattern and bins are mutually exclusive",
+            at
+        ));
+    }
+    if let Some(bin) = spec.bins.iter().find(|b| b.is_empty() || b.contains('/')) {
+        problems.push(format!("{}: invalid bins entry '{}'", at, bin));
+    }
+    if let Some(checksum) = &spec.checksum
+        && let Err(e) = verify::parse_checksum(checksum)
+    {
+        problems.push(format!("{}: {}", at, e));
+    }
+    if let Err(e) = spec.version_req() {
+        problems.push(format!("{}: {}", at, e));
+    }
+}
+
+/// Repositories are given as `owner/repo`
+fn check_repo_name(repo: &str, at: &str, problems: &mut Vec<String>) {
+    let valid = repo
+        .split_once('/')
+        .is_some_and(|(owner, name)| !owner.is_empty() && !name.is_empty() && !name.contains('/'));
+    if !valid {
+        problems.push(format!(
+            "{}: invalid repository '{}', expected owner/repo",
+            at, repo
+        ));
+    }
+}
+
+/// Look every repository up on GitHub, stopping at the first connection failure
+fn check_repos(agent: &Agent, repos: &BTreeSet<String>, problems: &mut Vec<String>) {
+    for repo in repos {
+        let url = format!("https://api.github.com/repos/{}", repo);
+        let response = agent
+            .get(&url)
+            .config()
+            .http_status_as_error(false)
+            .build()
+            .call();
+        match response {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => problems.push(format!(
+                "repository {} is not reachable: {}",
+                repo,
+                response.status()
+            )),
+            Err(e) => {
+                problems.push(format!("api.github.com is unreachable: {}", e));
+                return;
+            }
+        }
+    }
+    eprintln!("Looked up {} repositories on GitHub", repos.len());
+}
@@ -0,0 +1,161 @@
+use std::{env, fs, path::Path, process::Command};
+
+use anyhow::{Context, Result, anyhow};
+use toml_edit::DocumentMut;
+
+use crate::{alias, config::Config, extract, minisign};
+
+/// Tables of the config file, with what their entries are keyed by
+const SECTIONS: [(&str, &str); 4] = [
+    ("minisign_keys", "<owner/repo>"),
+    ("gpg_keys", "<owner/repo>"),
+    ("extractors", "<format>"),
+    ("aliases", "<name>"),
+];
+
+/// Print the value of `key` (`<section>.<entry>`), or every entry of a section
+pub fn get(key: &str) -> Result<()> {
+    let (section, repo) = split_key(key)?;
+    let doc = read_document(&Config::path()?)?;
+    let table = doc.get(section).and_then(|item| item.as_table_like());
+    match repo {
+        Some(repo) => {
+            let value = table
+                .and_then(|t| t.get(repo))
+                .and_then(|item| item.as_str())
+                .ok_or_else(|| anyhow!("{} is not set", key))?;
+            println!("{}", value);
+        }
+        None => {
+            for (repo, item) in table.into_iter().flat_map(|t| t.iter()) {
+                println!("{} = {}", repo, item.as_str().unwrap_or_default());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Set `key` (`<section>.<entry>`) to `value`, keeping the rest of the file as written
+pub fn set(key: &str, value: &str) -> Result<()> {
+    let (section, repo) = split_key(key)?;
+    let repo = repo.ok_or_else(|| anyhow!("Missing entry name in '{}'", key))?;
+    match section {
+        "minisign_keys" => {
+            minisign::parse_key(value)?;
+        }
+        "extractors" => {
+            extract::check_choice(repo, value)?;
+        }
+        "aliases" => {
+            alias::check(repo, value)?;
+        }
+        _ if !Path::new(value).is_file() => {
+            return Err(anyhow!("PGP public key file {:?} does not exist", value));
+        }
+        _ => {}
+    }
+
+    let path = Config::path()?;
+    let mut doc = read_document(&path)?;
+    let table = doc
+        .entry(section)
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .ok_or_else(|| anyhow!("{} is not a table in {:?}", section, path))?;
+    table.insert(repo, toml_edit::value(value));
+    write_document(&path, &doc)?;
+    eprintln!("Set {}", key);
+    Ok(())
+}
+
+/// Remove `key` (`<section>.<entry>`), or a whole section
+pub fn unset(key: &str) -> Result<()> {
+    let (section, repo) = split_key(key)?;
+    let path = Config::path()?;
+    let mut doc = read_document(&path)?;
+    let removed = match repo {
+        Some(repo) => doc
+            .get_mut(section)
+            .and_then(|item| item.as_table_like_mut())
+            .and_then(|t| t.remove(repo))
+            .is_some(),
+        None => doc.remove(section).is_some(),
+    };
+    if !removed {
+        return Err(anyhow!("{} is not set", key));
+    }
+    write_document(&path, &doc)?;
+    eprintln!("Unset {}", key);
+    Ok(())
+}
+
+/// Open the config file in `$VISUAL`/`$EDITOR`, then check that it still parses
+pub fn edit() -> Result<()> {
+    let path = Config::path()?;
+    if !path.exists() {
+        write_document(&path, &DocumentMut::new())?;
+    }
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+    // Editors are often configured with arguments, e.g. `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let status = Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to run editor '{}'", editor))?;
+    if !status.success() {
+        return Err(anyhow!("Editor '{}' exited with {}", editor, status));
+    }
+    Config::load()?;
+    Ok(())
+}
+
+/// Split `minisign_keys.owner/repo` into the section and the entry name
+fn split_key(key: &str) -> Result<(&str, Option<&str>)> {
+    let (section, repo) = match key.split_once('.') {
+        Some((section, repo)) => (section, Some(repo)),
+        None => (key, None),
+    };
+    if !SECTIONS.iter().any(|(name, _)| *name == section) {
+        let expected: Vec<_> = SECTIONS
+            .iter()
+            .map(|(name, entry)| format!("{}.{}", name, entry))
+            .collect();
+        return Err(anyhow!(
+            "Unknown config key '{}': expected {}",
+            key,
+            expected.join(", ")
+        ));
+    }
+    Ok((section, repo.filter(|r| !r.is_empty())))
+}
+
+fn read_document(path: &Path) -> Result<DocumentMut> {
+    if !path.exists() {
+        return Ok(DocumentMut::new());
+    }
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {:?}", path))?;
+    text.parse()
+        .with_context(|| format!("Failed to parse config file {:?}", path))
+}
+
+/// Write the config file atomically, refusing to produce one grd can't load
+fn write_document(path: &Path, doc: &DocumentMut) -> Result<()> {
+    let text = doc.to_string();
+    toml::from_str::<Config>(&text)
+        .with_context(|| format!("Refusing to write an invalid config file {:?}", path))?;
+    let dir = path
+        .parent()
+        .ok_or_else(|| anyhow!("Invalid config path {:?}", path))?;
+    fs::create_dir_all(dir)?;
+    let tmp = path.with_extension("toml.tmp");
+    fs::write(&tmp, text)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
@@ -0,0 +1,189 @@
+//! Tool definitions: shared rules for repositories whose releases the heuristics get wrong
+//!
+//! A definition file is a fragment of the config file, holding only `[aliases]` and
+//! `[repo."owner/repo"]` tables:
+//!
+//! ```toml
+//! [aliases]
+//! rg = "BurntSushi/ripgrep"
+//!
+//! [repo."BurntSushi/ripgrep"]
+//! asset = "ripgrep-*-{arch}-*{os}*.tar.gz"
+//! member = "*/rg"
+//! checksums = "{asset}.sha256"
+//! ```
+//!
+//! Files are read from `tools.d/` next to the config file, then from a Git repository configured
+//! with `definitions_repo` and fetched by `grd definitions update`. The config file overrides
+//! both, and definitions can't run commands (`post_install`).
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+
+use crate::{
+    alias,
+    config::{self, Config, RepoConfig},
+    state::Scope,
+    table::print_table,
+};
+
+/// Aliases and repository rules merged from every definition file
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Definitions {
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub repo: BTreeMap<String, RepoConfig>,
+
+    /// File each repository rule was read from
+    #[serde(skip)]
+    pub origins: BTreeMap<String, PathBuf>,
+}
+
+/// Directory of the user's own definition files, next to the config file
+pub fn local_dir() -> Result<PathBuf> {
+    let config = Config::path()?;
+    Ok(config.parent().unwrap_or(Path::new(".")).join("tools.d"))
+}
+
+/// Checkout of the configured definitions repository
+pub fn checkout_dir() -> Result<PathBuf> {
+    Ok(Scope::User.data_dir()?.join("definitions"))
+}
+
+/// Load every definition file; local files win over the fetched repository, and within a
+/// directory later files (by name) win over earlier ones
+///
+/// Broken files are skipped with a warning rather than breaking every command; `grd config
+/// check` reports them.
+pub fn load() -> Result<Definitions> {
+    let mut definitions = Definitions::default();
+    for path in files()? {
+        let file = match read(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Warning: {:#} (ignored)", e);
+                continue;
+            }
+        };
+        definitions.aliases.extend(file.aliases);
+        for (repo, rules) in file.repo {
+            definitions.origins.insert(repo.clone(), path.clone());
+            definitions.repo.insert(repo, rules);
+        }
+    }
+    Ok(definitions)
+}
+
+/// Every definition file, in the order they are applied
+pub fn files() -> Result<Vec<PathBuf>> {
+    let mut files = toml_files(&checkout_dir()?)?;
+    files.extend(toml_files(&local_dir()?)?);
+    Ok(files)
+}
+
+/// Read and validate one definition file
+pub fn read(path: &Path) -> Result<Definitions> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read definitions {:?}", path))?;
+    let file: Definitions =
+        toml::from_str(&text).with_context(|| format!("Failed to parse definitions {:?}", path))?;
+    check(&file).with_context(|| format!("Invalid definitions {:?}", path))?;
+    Ok(file)
+}
+
+/// Clone the configured definitions repository, or fast-forward the existing checkout
+pub fn update() -> Result<()> {
+    let config = Config::load()?;
+    let path = Config::path()?;
+    let url = config
+        .definitions_repo
+        .as_deref()
+        .ok_or_else(|| anyhow!("No definitions_repo is set in {:?}", path))?;
+    let dir = checkout_dir()?;
+    let mut git = Command::new("git");
+    if dir.join(".git").is_dir() {
+        eprintln!("Updating definitions in {:?}", dir);
+        git.arg("-C")
+            .arg(&dir)
+            .args(["pull", "--ff-only", "--quiet"]);
+    } else {
+        eprintln!("Fetching definitions from {}", url);
+        fs::create_dir_all(dir.parent().unwrap_or(Path::new(".")))?;
+        git.args(["clone", "--depth", "1", "--quiet", url])
+            .arg(&dir);
+    }
+    let status = git.status().context("Failed to run git")?;
+    if !status.success() {
+        return Err(anyhow!("git failed: {}", status));
+    }
+    let definitions = load()?;
+    eprintln!(
+        "{} alias(es) and rules for {} repositories available",
+        definitions.aliases.len(),
+        definitions.repo.len()
+    );
+    Ok(())
+}
+
+/// Print the repositories that have rules, with the file each comes from
+pub fn list() -> Result<()> {
+    let definitions = load()?;
+    if definitions.repo.is_empty() {
+        eprintln!(
+            "No definitions; add files to {:?} or set definitions_repo and run `grd definitions update`",
+            local_dir()?
+        );
+        return Ok(());
+    }
+    let rows: Vec<Vec<String>> = definitions
+        .origins
+        .iter()
+        .map(|(repo, path)| vec![repo.clone(), path.display().to_string()])
+        .collect();
+    print_table(&["REPO", "FILE"], &rows);
+    Ok(())
+}
+
+fn check(file: &Definitions) -> Result<()> {
+    for (name, repo) in &file.aliases {
+        alias::check(name, repo)?;
+    }
+    for (repo, rules) in &file.repo {
+        if !rules.post_install.is_empty() {
+            return Err(anyhow!(
+                "repo.\"{}\": post_install is only allowed in the config file",
+                repo
+            ));
+        }
+        if let Some(asset) = &rules.asset {
+            config::check_asset_pattern(asset)?;
+        }
+    }
+    Ok(())
+}
+
+/// `*.toml` files of `dir` sorted by name, none if it doesn't exist
+fn toml_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "toml") && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
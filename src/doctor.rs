@@ -0,0 +1,108 @@
+use std::{fs, path::Path};
+
+use anyhow::{Result, anyhow};
+
+use crate::{
+    index,
+    state::{Action, Scope, State},
+};
+
+/// Find receipts that no longer match the filesystem and, with `fix`, repair them
+///
+/// Receipts whose files are all gone are dropped, missing files are forgotten, recorded
+/// symlinks whose target is gone are removed and an outdated index is rebuilt.
+pub fn doctor(scope: Scope, fix: bool) -> Result<()> {
+    let mut state = State::load(scope)?;
+    let mut problems = 0;
+    let mut fixed = 0;
+
+    let names: Vec<String> = state.tools.keys().cloned().collect();
+    for name in &names {
+        let mut receipt = state.tools[name].clone();
+        let broken: Vec<_> = receipt
+            .files
+            .iter()
+            .filter(|path| is_broken_link(path))
+            .cloned()
+            .collect();
+        let missing: Vec<_> = receipt
+            .files
+            .iter()
+            .filter(|path| fs::symlink_metadata(path).is_err())
+            .cloned()
+            .collect();
+
+        if !receipt.files.is_empty() && missing.len() == receipt.files.len() {
+            println!(
+                "  - {}: none of its {} file(s) exist anymore",
+                name,
+                receipt.files.len()
+            );
+            problems += 1;
+            if fix {
+                state.log(name, Action::Uninstall, &receipt);
+                state.tools.remove(name);
+                eprintln!("Removed the receipt of {}", name);
+                fixed += 1;
+            }
+            continue;
+        }
+
+        for path in &missing {
+            println!("  - {}: {} is missing", name, path.display());
+        }
+        for path in &broken {
+            println!("  - {}: {} is a broken symlink", name, path.display());
+        }
+        problems += missing.len() + broken.len();
+        if fix && !(missing.is_empty() && broken.is_empty()) {
+            for path in &broken {
+                fs::remove_file(path)?;
+            }
+            receipt
+                .files
+                .retain(|path| !missing.contains(path) && !broken.contains(path));
+            receipt
+                .digests
+                .retain(|path, _| !missing.contains(path) && !broken.contains(path));
+            state.tools.insert(name.clone(), receipt);
+            eprintln!(
+                "Forgot {} missing or broken file(s) of {}; reinstall it to restore them",
+                missing.len() + broken.len(),
+                name
+            );
+            fixed += missing.len() + broken.len();
+        }
+    }
+
+    // Saving rewrites the index as well
+    if fixed > 0 {
+        state.save()?;
+    } else if State::path(scope)?.exists()
+        && index::is_stale(&index::path(scope)?, &State::path(scope)?)
+    {
+        println!("  - the installed-tools index is out of date");
+        problems += 1;
+        if fix {
+            index::write(&state)?;
+            eprintln!("Rebuilt the installed-tools index");
+            fixed += 1;
+        }
+    }
+    if problems == 0 {
+        println!("No problems found");
+        return Ok(());
+    }
+    if fixed == problems {
+        eprintln!("Fixed {} problem(s)", fixed);
+        return Ok(());
+    }
+    Err(anyhow!(
+        "{} problem(s) found; run `grd doctor --fix` to repair the receipts",
+        problems
+    ))
+}
+
+fn is_broken_link(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.is_symlink()) && fs::metadata(path).is_err()
+}
@@ -0,0 +1,334 @@
+//! Downloading assets into memory or a temp file, and undoing single-file compression
+
+use std::{
+    fs::{self, File},
+    io::{self, Cursor, Read, Seek, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result, anyhow};
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+use ureq::Agent;
+
+use crate::{
+    asset_cache, http,
+    install::{InstallOptions, file_sha256},
+    progress::{self, ProgressBar},
+    release::Asset,
+    units::format_size,
+};
+
+pub enum DownloadSource {
+    Memory(Vec<u8>),
+    Disk(NamedTempFile),
+}
+
+impl DownloadSource {
+    pub fn into_reader(self) -> Result<Box<dyn ReadSeek>> {
+        Ok(match self {
+            DownloadSource::Memory(bytes) => Box::new(Cursor::new(bytes)),
+            DownloadSource::Disk(temp_file) => Box::new(File::open(temp_file.path())?),
+        })
+    }
+
+    /// A reader of its own over the data, so that several threads can read it at once
+    pub fn reader(&self) -> Result<Box<dyn ReadSeek + '_>> {
+        Ok(match self {
+            DownloadSource::Memory(bytes) => Box::new(Cursor::new(bytes.as_slice())),
+            DownloadSource::Disk(temp_file) => Box::new(File::open(temp_file.path())?),
+        })
+    }
+
+    /// Write the data to `path`
+    pub fn write_to(self, path: &Path) -> Result<()> {
+        match self {
+            DownloadSource::Memory(bytes) => fs::write(path, bytes),
+            DownloadSource::Disk(temp_file) => fs::copy(temp_file.path(), path).map(|_| ()),
+        }
+        .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// Get the source as a file on disk, spilling in-memory data to a temp file
+    pub fn into_temp_file(self, suffix: &str) -> Result<NamedTempFile> {
+        match self {
+            DownloadSource::Memory(bytes) => {
+                let mut temp_file = tempfile::Builder::new().suffix(suffix).tempfile()?;
+                temp_file.write_all(&bytes)?;
+                temp_file.flush()?;
+                Ok(temp_file)
+            }
+            DownloadSource::Disk(temp_file) => Ok(temp_file),
+        }
+    }
+
+    /// Hex-encoded SHA-256 digest of the whole source
+    pub fn sha256(&self) -> Result<String> {
+        let mut hasher = Sha256::new();
+        match self {
+            DownloadSource::Memory(bytes) => hasher.update(bytes),
+            DownloadSource::Disk(temp_file) => return file_sha256(temp_file.path()),
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Read up to `len` bytes from the start of the source
+    pub fn peek(&self, len: usize) -> Result<Vec<u8>> {
+        match self {
+            DownloadSource::Memory(bytes) => Ok(bytes[..len.min(bytes.len())].to_vec()),
+            DownloadSource::Disk(temp_file) => {
+                let mut buf = Vec::with_capacity(len);
+                File::open(temp_file.path())?
+                    .take(len as u64)
+                    .read_to_end(&mut buf)?;
+                Ok(buf)
+            }
+        }
+    }
+}
+
+/// Single-file compression formats recognized by asset file extension
+#[derive(Debug, Clone, Copy)]
+pub enum Compression {
+    Gzip,
+    Xz,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    /// Detect the compression of an asset, returning it with the name of the decompressed file
+    pub fn detect(filename: &str) -> Option<(Compression, String)> {
+        const SUFFIXES: &[(&str, Compression, &str)] = &[
+            (".tgz", Compression::Gzip, ".tar"),
+            (".gz", Compression::Gzip, ""),
+            (".txz", Compression::Xz, ".tar"),
+            (".xz", Compression::Xz, ""),
+            (".tzst", Compression::Zstd, ".tar"),
+            (".zst", Compression::Zstd, ""),
+            (".tbz2", Compression::Bzip2, ".tar"),
+            (".tbz", Compression::Bzip2, ".tar"),
+            (".bz2", Compression::Bzip2, ""),
+        ];
+        let lower = filename.to_lowercase();
+        SUFFIXES
+            .iter()
+            .find_map(|(suffix, compression, replacement)| {
+                lower.ends_with(suffix).then(|| {
+                    let stem = &filename[..filename.len() - suffix.len()];
+                    (*compression, format!("{}{}", stem, replacement))
+                })
+            })
+    }
+
+    /// Name of the format, which is also the cargo feature providing its decoder (except gzip)
+    fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Xz => "xz",
+            Compression::Zstd => "zstd",
+            Compression::Bzip2 => "bzip2",
+        }
+    }
+
+    /// Compression declared by a media type, trusted only when the data starts with its magic
+    /// bytes since uploaders pick content types carelessly
+    pub fn from_content_type(media_type: &str, source: &DownloadSource) -> Result<Option<Self>> {
+        let compression = match media_type {
+            "application/gzip" | "application/x-gzip" => Compression::Gzip,
+            "application/x-xz" => Compression::Xz,
+            "application/zstd" => Compression::Zstd,
+            "application/x-bzip2" => Compression::Bzip2,
+            _ => return Ok(None),
+        };
+        let magic: &[u8] = match compression {
+            Compression::Gzip => b"\x1f\x8b",
+            Compression::Xz => b"\xfd7zXZ\0",
+            Compression::Zstd => b"\x28\xb5\x2f\xfd",
+            Compression::Bzip2 => b"BZh",
+        };
+        Ok(source
+            .peek(magic.len())?
+            .starts_with(magic)
+            .then_some(compression))
+    }
+}
+
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek + ?Sized> ReadSeek for T {}
+
+pub fn download_asset(
+    agent: &Agent,
+    asset: &Asset,
+    opts: &InstallOptions,
+) -> Result<DownloadSource> {
+    let memory_threshold = opts.memory_limit;
+    // A cache that can't be located (no home directory) is only skipped
+    let cached = opts
+        .cache
+        .then(|| asset_cache::Entry::of(asset).ok())
+        .flatten();
+    if let Some(source) = cached
+        .as_ref()
+        .and_then(|entry| entry.fresh(asset, memory_threshold))
+    {
+        eprintln!("Using the cached download of {}", asset.name);
+        return Ok(source);
+    }
+    eprintln!("Downloading...");
+    let pb = progress::download_bar(asset.size, opts.si);
+    if asset.size > memory_threshold {
+        eprintln!(
+            "Using temp file due to size > {}",
+            format_size(memory_threshold, opts.si)
+        );
+    }
+
+    // A connection dropped mid-transfer can end the body early without an error
+    const MAX_ATTEMPTS: u32 = 3;
+    let mut attempt = 1;
+    let mut revalidate = true;
+    loop {
+        let etag = cached
+            .as_ref()
+            .filter(|_| revalidate)
+            .and_then(|entry| entry.etag());
+        let Some(mut response) =
+            http::get_if_none_match(agent, &asset.browser_download_url, etag, &pb)?
+        else {
+            if let Some(source) = cached.as_ref().and_then(|e| e.load(memory_threshold).ok()) {
+                pb.finish_with_message("Not modified");
+                eprintln!("Using the cached download of {} (not modified)", asset.name);
+                return Ok(source);
+            }
+            // The cached copy turned out corrupt, so download it unconditionally
+            revalidate = false;
+            continue;
+        };
+        let etag = response
+            .headers()
+            .get("etag")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let mut reader = response.body_mut().as_reader();
+        let (source, received) = if asset.size > memory_threshold {
+            let mut temp_file = NamedTempFile::new()?;
+            let writer = |buf: &[u8]| temp_file.write_all(buf);
+            let received = download_with_progress(&mut reader, &pb, writer)?;
+            (DownloadSource::Disk(temp_file), received)
+        } else {
+            let mut bytes = Vec::new();
+            let writer = |buf: &[u8]| {
+                bytes.extend_from_slice(buf);
+                Ok(())
+            };
+            let received = download_with_progress(&mut reader, &pb, writer)?;
+            (DownloadSource::Memory(bytes), received)
+        };
+        if received == asset.size {
+            pb.finish_with_message("Downloaded");
+            if let Some(entry) = &cached
+                && let Err(e) = entry.store(asset, &source, etag.as_deref())
+            {
+                eprintln!("Warning: failed to cache {}: {}", asset.name, e);
+            }
+            return Ok(source);
+        }
+        if attempt >= MAX_ATTEMPTS {
+            pb.abandon();
+            return Err(anyhow!(
+                "Download of {} is truncated or corrupt: received {} bytes, expected {} (after {} attempts)",
+                asset.name,
+                received,
+                asset.size,
+                MAX_ATTEMPTS
+            ));
+        }
+        attempt += 1;
+        pb.reset();
+        pb.set_message(format!(
+            "received {} of {} bytes, retrying ({}/{})",
+            received, asset.size, attempt, MAX_ATTEMPTS
+        ));
+    }
+}
+
+/// Copy the response body to `writer`, returning the number of bytes received
+pub fn download_with_progress<R: Read, F>(
+    reader: &mut R,
+    pb: &ProgressBar,
+    mut writer: F,
+) -> Result<u64>
+where
+    F: FnMut(&[u8]) -> io::Result<()>,
+{
+    let mut buf = [0; 8192];
+    let mut received = 0;
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        writer(&buf[..n])?;
+        pb.inc(n as u64);
+        received += n as u64;
+    }
+    Ok(received)
+}
+
+/// Decompress a single-file compressed source, keeping it in memory or on disk like the input
+pub fn decompress(source: DownloadSource, compression: Compression) -> Result<DownloadSource> {
+    let in_memory = matches!(source, DownloadSource::Memory(_));
+    let input = io::BufReader::new(source.into_reader()?);
+    if in_memory {
+        let mut bytes = Vec::new();
+        decompress_into(input, compression, &mut bytes)?;
+        Ok(DownloadSource::Memory(bytes))
+    } else {
+        let mut temp_file = NamedTempFile::new()?;
+        let mut writer = io::BufWriter::new(temp_file.as_file_mut());
+        decompress_into(input, compression, &mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        Ok(DownloadSource::Disk(temp_file))
+    }
+}
+
+fn decompress_into<R: io::BufRead, W: Write>(
+    input: R,
+    compression: Compression,
+    writer: &mut W,
+) -> Result<()> {
+    match compression {
+        Compression::Gzip => {
+            io::copy(&mut flate2::read::MultiGzDecoder::new(input), writer)?;
+        }
+        #[cfg(feature = "xz")]
+        Compression::Xz => {
+            let mut input = input;
+            lzma_rs::xz_decompress(&mut input, writer)
+                .map_err(|e| anyhow!("Failed to decompress xz data: {}", e))?;
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => {
+            let mut decoder = ruzstd::decoding::StreamingDecoder::new(input)
+                .map_err(|e| anyhow!("Failed to decompress zstd data: {}", e))?;
+            io::copy(&mut decoder, writer)?;
+        }
+        #[cfg(feature = "bzip2")]
+        Compression::Bzip2 => {
+            io::copy(&mut bzip2::read::MultiBzDecoder::new(input), writer)?;
+        }
+        #[allow(unreachable_patterns)]
+        other => {
+            let name = other.name();
+            return Err(anyhow!(
+                "This grd is built without {} support (the `{}` feature)",
+                name,
+                name
+            ));
+        }
+    }
+    Ok(())
+}
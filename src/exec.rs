@@ -0,0 +1,243 @@
+//! Running a release's executable without installing it, like `npx` or `pipx run`
+//!
+//! Releases are unpacked into a content-addressed cache under `run/` in the cache directory:
+//!
+//! - `objects/<sha256>-<bin>/` holds an unpacked asset, named after its digest and the executable
+//!   selected from it, and is reused by every run resolving to the same asset;
+//! - `refs/<owner>__<repo>/<tag or @latest>/<os>-<arch>/<bin>` names the object a run resolved
+//!   to, and is trusted for the configured TTL, after which the release is looked up again (so
+//!   that a moved `nightly` tag or a new latest release is picked up).
+//!
+//! The least recently run objects are evicted beyond the configured maximum size.
+
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::Command,
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result, anyhow};
+use sha2::{Digest, Sha256};
+use ureq::Agent;
+
+use crate::{
+    config::Config,
+    install::{InstallOptions, install},
+    resolve::resolve_release,
+    select::select_asset,
+    state,
+    units::format_size,
+    verify,
+};
+
+/// File of an object holding the executable's path, relative to the object; written last, so
+/// objects without it are incomplete. Its modification time records the last run.
+const EXE_FILE: &str = ".grd-exe";
+
+/// Run the executable of `repo`'s release selected by `opts` with `args`, installing it into the
+/// cache unless it is there already, and exit with its exit code
+pub fn run(agent: &Agent, repo: &str, mut opts: InstallOptions, args: &[OsString]) -> Result<()> {
+    let config = Config::load()?;
+    config.repo(repo).apply(&mut opts)?;
+    let ttl = config.run.ttl()?;
+    let max_size = config.run.max_size()?;
+
+    let root = state::cache_dir()?.join("run");
+    let bin = opts.bin_name.clone().unwrap_or_else(|| "_".to_string());
+    let ref_path = root
+        .join("refs")
+        .join(repo.replace('/', "__"))
+        .join(match (&opts.tag, &opts.version_req, &opts.tag_pattern) {
+            (Some(tag), _, _) => tag.replace('/', "_"),
+            (None, Some(req), _) => format!("@{}", req).replace(['/', ' ', '<', '>', '*'], "_"),
+            // Patterns can hold any character, so they are named by their digest
+            (None, None, Some(pattern)) => {
+                let digest = Sha256::digest(pattern.as_str().as_bytes());
+                format!("@pattern-{:x}", digest)[..25].to_string()
+            }
+            (None, None, None) => "@latest".to_string(),
+        })
+        .join(format!("{}-{}", opts.os, opts.arch))
+        .join(&bin);
+    let objects = root.join("objects");
+
+    let fresh = fs::metadata(&ref_path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < ttl);
+    let cached = fresh
+        .then(|| fs::read_to_string(&ref_path).ok())
+        .flatten()
+        .and_then(|key| cached_exe(&objects.join(key.trim())));
+    let (object, exe) = match cached {
+        Some(found) => found,
+        None => {
+            // Pin the selected asset, so that installing it doesn't select (or prompt) again
+            let release = resolve_release(agent, repo, &opts)?;
+            let asset = select_asset(&release.assets, &opts)?;
+            opts.tag = Some(release.tag_name.clone());
+            opts.asset_pattern = Some(glob::Pattern::escape(&asset.name));
+            opts.first = true;
+            let known = verify::api_digest(&asset)
+                .and_then(|digest| cached_exe(&objects.join(object_key(&digest.sha256, &bin))));
+            let (object, exe) = match known {
+                Some(found) => found,
+                None => populate(agent, repo, opts, &objects, &bin)?,
+            };
+            let key = object.file_name().unwrap_or_default().to_string_lossy();
+            fs::create_dir_all(ref_path.parent().unwrap_or(Path::new(".")))?;
+            fs::write(&ref_path, key.as_bytes())?;
+            (object, exe)
+        }
+    };
+
+    // Mark the object as just run, then make room without evicting it
+    File::options()
+        .write(true)
+        .open(object.join(EXE_FILE))
+        .and_then(|f| f.set_modified(SystemTime::now()))?;
+    evict(&objects, max_size, &object)?;
+    exec(&exe, args)
+}
+
+fn object_key(sha256: &str, bin: &str) -> String {
+    format!("{}-{}", sha256.to_ascii_lowercase(), bin)
+}
+
+/// The object and its executable, if the object is complete
+fn cached_exe(object: &Path) -> Option<(PathBuf, PathBuf)> {
+    let relative = fs::read_to_string(object.join(EXE_FILE)).ok()?;
+    let exe = object.join(relative.trim_end());
+    exe.is_file().then(|| (object.to_path_buf(), exe))
+}
+
+/// Install the release into a staging directory, then move it into place as the object named
+/// after the downloaded asset's digest
+fn populate(
+    agent: &Agent,
+    repo: &str,
+    mut opts: InstallOptions,
+    objects: &Path,
+    bin: &str,
+) -> Result<(PathBuf, PathBuf)> {
+    fs::create_dir_all(objects)?;
+    let staging = tempfile::Builder::new()
+        .prefix(".staging")
+        .tempdir_in(objects)?;
+    opts.destination = staging.path().to_path_buf();
+    let installed = install(agent, repo, &opts)?;
+    let exe = installed
+        .files
+        .iter()
+        .rev()
+        .find(|f| f.is_file())
+        .ok_or_else(|| anyhow!("The {} release installed no executable", installed.tag))?;
+    let relative = exe.strip_prefix(staging.path())?.to_path_buf();
+    fs::write(
+        staging.path().join(EXE_FILE),
+        relative.to_string_lossy().as_bytes(),
+    )?;
+
+    let object = objects.join(object_key(&installed.sha256, bin));
+    if let Some(found) = cached_exe(&object) {
+        return Ok(found);
+    }
+    // An incomplete object left by an interrupted run is replaced
+    if object.exists() {
+        fs::remove_dir_all(&object)?;
+    }
+    let staged = staging.keep();
+    if let Err(e) = fs::rename(&staged, &object) {
+        let _ = fs::remove_dir_all(&staged);
+        // Another run may have filled the object meanwhile
+        return cached_exe(&object)
+            .ok_or(e)
+            .with_context(|| format!("Failed to move the release into {:?}", object));
+    }
+    let exe = object.join(relative);
+    Ok((object, exe))
+}
+
+/// Remove the least recently run objects until the cache fits in `max_size`, keeping `keep`
+fn evict(objects: &Path, max_size: u64, keep: &Path) -> Result<()> {
+    let mut entries = self::objects(objects)?;
+    let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+    entries.sort();
+    for (_, size, path) in entries {
+        if total <= max_size {
+            break;
+        }
+        if path == keep {
+            continue;
+        }
+        fs::remove_dir_all(&path)?;
+        total -= size;
+        eprintln!(
+            "Evicted {:?} ({}) from the run cache",
+            path,
+            format_size(size, false)
+        );
+    }
+    Ok(())
+}
+
+/// The objects in `objects` (without staging directories), with the time they were last run and
+/// their size
+pub fn objects(objects: &Path) -> Result<Vec<(SystemTime, u64, PathBuf)>> {
+    let mut entries = Vec::new();
+    let Ok(dir) = fs::read_dir(objects) else {
+        return Ok(entries);
+    };
+    for entry in dir {
+        let path = entry?.path();
+        // Staging directories of runs in progress start with a dot
+        let staging = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if staging || !path.is_dir() {
+            continue;
+        }
+        let last_run = fs::metadata(path.join(EXE_FILE))
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        entries.push((last_run, dir_size(&path), path));
+    }
+    Ok(entries)
+}
+
+/// Total size of the regular files under `dir`, not following symlinks
+pub fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            Ok(t) if t.is_file() => entry.metadata().map_or(0, |m| m.len()),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Replace this process with `exe`, so that signals and the exit code pass through
+#[cfg(unix)]
+pub fn exec(exe: &Path, args: &[OsString]) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let e = Command::new(exe).args(args).exec();
+    Err(e).with_context(|| format!("Failed to run {:?}", exe))
+}
+
+/// Run `exe` and exit with its exit code
+#[cfg(not(unix))]
+pub fn exec(exe: &Path, args: &[OsString]) -> Result<()> {
+    let status = Command::new(exe)
+        .args(args)
+        .status()
+        .with_context(|| format!("Failed to run {:?}", exe))?;
+    std::process::exit(status.code().unwrap_or(1));
+}
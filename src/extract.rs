@@ -0,0 +1,969 @@
+//! Archive extraction backends
+//!
+//! Every archive format is unpacked by a registered [`Extractor`]. The built-in decoders are used
+//! unless the `[extractors]` table of the config file picks another backend for a format. Formats
+//! without a built-in decoder (RAR, CAB, ISO images) can only be installed through an external
+//! program configured this way:
+//!
+//! ```toml
+//! [extractors]
+//! "7z" = "7z"     # the system 7-Zip instead of the built-in decoder
+//! tar = "bsdtar"
+//! rar = "unrar"
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, File},
+    io::{self, Read, Seek},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::{Result, anyhow};
+use zip::ZipArchive;
+
+#[cfg(unix)]
+use crate::install::set_permissions;
+use crate::{
+    download::DownloadSource,
+    install::{create_dest_dir, create_parent_dirs},
+    plan::{ExtractPlan, is_executable},
+};
+
+/// Archive formats grd knows how to unpack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Zip,
+    Tar,
+    SevenZip,
+    Dmg,
+    Rar,
+    Cab,
+    Iso,
+}
+
+impl Format {
+    const ALL: [Format; 7] = [
+        Format::Zip,
+        Format::Tar,
+        Format::SevenZip,
+        Format::Dmg,
+        Format::Rar,
+        Format::Cab,
+        Format::Iso,
+    ];
+
+    /// Name of the format in the `[extractors]` config table
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Zip => "zip",
+            Format::Tar => "tar",
+            Format::SevenZip => "7z",
+            Format::Dmg => "dmg",
+            Format::Rar => "rar",
+            Format::Cab => "cab",
+            Format::Iso => "iso",
+        }
+    }
+
+    /// File extension external programs recognize the format by
+    fn suffix(self) -> &'static str {
+        match self {
+            Format::Zip => ".zip",
+            Format::Tar => ".tar",
+            Format::SevenZip => ".7z",
+            Format::Dmg => ".dmg",
+            Format::Rar => ".rar",
+            Format::Cab => ".cab",
+            Format::Iso => ".iso",
+        }
+    }
+
+    /// Detect the archive format of a decompressed asset, or `None` for a plain file
+    ///
+    /// The file name is checked first, then the data, then the asset's declared media type.
+    pub fn detect(
+        filename: &str,
+        media_type: Option<&str>,
+        source: &DownloadSource,
+    ) -> Result<Option<Format>> {
+        let format = if filename.ends_with(".zip") {
+            Format::Zip
+        } else if filename.ends_with(".7z") {
+            Format::SevenZip
+        } else if is_tar(source)? || filename.to_lowercase().ends_with(".tar") {
+            // Archives written by pre-POSIX tar implementations lack the magic
+            Format::Tar
+        } else {
+            let lower = filename.to_lowercase();
+            let by_name = [Format::Dmg, Format::Rar, Format::Cab, Format::Iso]
+                .into_iter()
+                .find(|f| lower.ends_with(f.suffix()));
+            let Some(format) = by_name.or_else(|| media_type.and_then(Format::from_media_type))
+            else {
+                return Ok(None);
+            };
+            format
+        };
+        Ok(Some(format))
+    }
+
+    /// Archive format declared by a media type (tarballs are recognized from their data)
+    fn from_media_type(media_type: &str) -> Option<Format> {
+        Some(match media_type {
+            "application/zip" | "application/x-zip-compressed" => Format::Zip,
+            "application/x-7z-compressed" => Format::SevenZip,
+            "application/x-apple-diskimage" => Format::Dmg,
+            "application/vnd.rar" | "application/x-rar-compressed" => Format::Rar,
+            "application/vnd.ms-cab-compressed" => Format::Cab,
+            "application/x-iso9660-image" => Format::Iso,
+            _ => return None,
+        })
+    }
+}
+
+/// A way of unpacking archives into the entries an [`ExtractPlan`] selects
+pub trait Extractor: Sync {
+    /// Name selecting this backend in the `[extractors]` config table
+    fn name(&self) -> &'static str;
+
+    fn supports(&self, format: Format) -> bool;
+
+    /// Install the selected entries of an archive, returning the written paths
+    fn extract(
+        &self,
+        format: Format,
+        source: DownloadSource,
+        plan: &ExtractPlan,
+    ) -> Result<Vec<PathBuf>>;
+}
+
+/// Every registered backend
+static EXTRACTORS: &[&dyn Extractor] = &[
+    &Native,
+    &External {
+        name: "tar",
+        formats: &[Format::Tar],
+        args: &["-xf", "{archive}", "-C", "{dir}"],
+    },
+    &External {
+        name: "bsdtar",
+        formats: &[
+            Format::Tar,
+            Format::Zip,
+            Format::SevenZip,
+            Format::Rar,
+            Format::Cab,
+            Format::Iso,
+        ],
+        args: &["-xf", "{archive}", "-C", "{dir}"],
+    },
+    &External {
+        name: "7z",
+        formats: &[
+            Format::SevenZip,
+            Format::Zip,
+            Format::Tar,
+            Format::Dmg,
+            Format::Rar,
+            Format::Cab,
+            Format::Iso,
+        ],
+        args: &["x", "-y", "-bd", "-o{dir}", "{archive}"],
+    },
+    &External {
+        name: "unzip",
+        formats: &[Format::Zip],
+        args: &["-q", "-o", "{archive}", "-d", "{dir}"],
+    },
+    &External {
+        name: "unrar",
+        formats: &[Format::Rar],
+        args: &["x", "-y", "-idq", "{archive}", "{dir}"],
+    },
+    &External {
+        name: "cabextract",
+        formats: &[Format::Cab],
+        args: &["-q", "-d", "{dir}", "{archive}"],
+    },
+];
+
+/// Backend for `format`: the one `choices` (format name -> backend name) picks, or the built-in one
+pub fn extractor_for(
+    format: Format,
+    choices: &BTreeMap<String, String>,
+) -> Result<&'static dyn Extractor> {
+    match choices.get(format.name()) {
+        Some(name) => lookup(format, name),
+        None if Native.supports(format) => Ok(&Native),
+        None => {
+            let names: Vec<_> = EXTRACTORS
+                .iter()
+                .filter(|e| e.supports(format))
+                .map(|e| e.name())
+                .collect();
+            Err(anyhow!(
+                "grd has no built-in decoder for {} archives. Configure an external one ({}) with \
+                 `grd config set extractors.{} <name>`, or use --no-decompress to save the asset as is",
+                format.name(),
+                names.join(", "),
+                format.name()
+            ))
+        }
+    }
+}
+
+/// Validate one entry of the `[extractors]` config table
+pub fn check_choice(format: &str, backend: &str) -> Result<()> {
+    let format = Format::ALL
+        .into_iter()
+        .find(|f| f.name() == format)
+        .ok_or_else(|| {
+            let names: Vec<_> = Format::ALL.iter().map(|f| f.name()).collect();
+            anyhow!(
+                "Unknown archive format '{}'. Expected one of: {}",
+                format,
+                names.join(", ")
+            )
+        })?;
+    lookup(format, backend).map(|_| ())
+}
+
+fn lookup(format: Format, name: &str) -> Result<&'static dyn Extractor> {
+    let extractor = EXTRACTORS
+        .iter()
+        .find(|e| e.name() == name)
+        .ok_or_else(|| {
+            let names: Vec<_> = EXTRACTORS.iter().map(|e| e.name()).collect();
+            anyhow!(
+                "Unknown extractor '{}'. Expected one of: {}",
+                name,
+                names.join(", ")
+            )
+        })?;
+    if !extractor.supports(format) {
+        return Err(anyhow!(
+            "Extractor '{}' cannot unpack {} archives",
+            name,
+            format.name()
+        ));
+    }
+    Ok(*extractor)
+}
+
+/// The pure-Rust decoders (and `hdiutil` for disk images)
+struct Native;
+
+impl Extractor for Native {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn supports(&self, format: Format) -> bool {
+        matches!(format, Format::Zip | Format::Tar)
+            || (cfg!(feature = "sevenz") && format == Format::SevenZip)
+            || (cfg!(feature = "dmg") && format == Format::Dmg)
+    }
+
+    fn extract(
+        &self,
+        format: Format,
+        source: DownloadSource,
+        plan: &ExtractPlan,
+    ) -> Result<Vec<PathBuf>> {
+        match format {
+            Format::Zip => extract_zip(source, plan),
+            Format::Tar => extract_tar(source, plan),
+            #[cfg(feature = "sevenz")]
+            Format::SevenZip => extract_7z(source, plan),
+            #[cfg(feature = "dmg")]
+            Format::Dmg => extract_dmg(source, plan),
+            _ => Err(anyhow!(
+                "No built-in decoder for {} archives",
+                format.name()
+            )),
+        }
+    }
+}
+
+/// A system command unpacking the whole archive into a private temporary directory, from which the
+/// selected entries are installed
+struct External {
+    /// Backend name, which is also the program run
+    name: &'static str,
+    formats: &'static [Format],
+    /// Arguments, with `{archive}` and `{dir}` standing for the archive and the output directory
+    args: &'static [&'static str],
+}
+
+impl Extractor for External {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn supports(&self, format: Format) -> bool {
+        self.formats.contains(&format)
+    }
+
+    fn extract(
+        &self,
+        format: Format,
+        source: DownloadSource,
+        plan: &ExtractPlan,
+    ) -> Result<Vec<PathBuf>> {
+        let archive = source.into_temp_file(format.suffix())?;
+        let dir = tempfile::tempdir()?;
+        let args = self.args.iter().map(|arg| {
+            arg.replace("{archive}", &archive.path().to_string_lossy())
+                .replace("{dir}", &dir.path().to_string_lossy())
+        });
+        // Run confined to the temporary directory, without input or the caller's environment
+        let mut command = Command::new(self.name);
+        command
+            .args(args)
+            .current_dir(dir.path())
+            .env_clear()
+            .stdin(Stdio::null())
+            .stdout(Stdio::null());
+        for var in ["PATH", "SystemRoot"] {
+            if let Some(value) = env::var_os(var) {
+                command.env(var, value);
+            }
+        }
+        let status = command
+            .status()
+            .map_err(|e| anyhow!("Failed to run {}: {}", self.name, e))?;
+        if !status.success() {
+            return Err(anyhow!(
+                "{} failed to unpack the archive ({})",
+                self.name,
+                status
+            ));
+        }
+        install_tree(dir.path(), plan)
+    }
+}
+
+/// Install the entries of an unpacked archive tree at `root` that the plan selects
+fn install_tree(root: &Path, plan: &ExtractPlan) -> Result<Vec<PathBuf>> {
+    let mut installed = Vec::new();
+    for path in walk(root)? {
+        let entry_path = path
+            .strip_prefix(root)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        plan.check_entry(&entry_path)?;
+        let Some(out_path) = plan.target(&entry_path) else {
+            continue;
+        };
+        let metadata = fs::symlink_metadata(&path)?;
+        let executable = is_executable(&entry_path, unix_mode(&metadata));
+        if plan.rejects(&out_path, metadata.is_file(), executable, &installed) {
+            continue;
+        }
+        plan.check_write(&out_path)?;
+        if metadata.is_dir() {
+            installed.extend(create_dest_dir(&out_path)?);
+            continue;
+        }
+        installed.extend(create_parent_dirs(&out_path)?);
+        if metadata.is_symlink() {
+            // Only whole-archive extraction gets here with a symlink; executables are regular files
+            #[cfg(unix)]
+            {
+                let link_target = fs::read_link(&path)?;
+                plan.check_link(&out_path, &link_target)?;
+                if fs::symlink_metadata(&out_path).is_ok() {
+                    fs::remove_file(&out_path)?;
+                }
+                std::os::unix::fs::symlink(link_target, &out_path)?;
+                installed.push(out_path);
+            }
+            continue;
+        }
+        // Copying keeps the mode the archive recorded
+        fs::copy(&path, &out_path)?;
+        #[cfg(unix)]
+        if plan.is_executable_selection() && !plan.is_companion(&out_path) {
+            set_permissions(&out_path)?;
+        }
+        installed.push(out_path);
+        if plan.is_single() {
+            break;
+        }
+    }
+    plan.check_found(installed)
+}
+
+/// Every path below `dir` in name order, directories before their contents, without following
+/// symlinks
+fn walk(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry.path();
+        let is_dir = entry.file_type()?.is_dir();
+        paths.push(path.clone());
+        if is_dir {
+            paths.extend(walk(&path)?);
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(unix)]
+fn unix_mode(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode())
+}
+
+#[cfg(not(unix))]
+fn unix_mode(_: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// Check for the POSIX tar magic in the first header block
+fn is_tar(source: &DownloadSource) -> Result<bool> {
+    let header = source.peek(512)?;
+    Ok(header.len() == 512 && &header[257..262] == b"ustar")
+}
+
+/// What to write for a selected zip entry, once every entry has been checked
+enum ZipWrite {
+    /// Entry index, its target, and its uncompressed size
+    File(usize, PathBuf, u64),
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Link(PathBuf, String),
+}
+
+/// Most worker threads used to write the files of one zip archive
+const MAX_ZIP_WORKERS: usize = 8;
+
+fn extract_zip(source: DownloadSource, plan: &ExtractPlan) -> Result<Vec<PathBuf>> {
+    let mut archive = ZipArchive::new(source.reader()?)?;
+    let mut installed = Vec::new();
+    let mut writes = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        plan.check_entry(file.name())?;
+        let Some(out_path) = plan.target(file.name()) else {
+            continue;
+        };
+        let executable = is_executable(file.name(), file.unix_mode());
+        let is_file = file.is_file() && !file.is_symlink();
+        if plan.rejects(&out_path, is_file, executable, &installed) {
+            continue;
+        }
+        plan.check_write(&out_path)?;
+        if file.is_dir() {
+            installed.extend(create_dest_dir(&out_path)?);
+            continue;
+        }
+        installed.extend(create_parent_dirs(&out_path)?);
+        // Only whole-archive extraction gets here with a symlink; executables are regular files
+        #[cfg(unix)]
+        if file.is_symlink() {
+            let mut link_target = String::new();
+            file.read_to_string(&mut link_target)?;
+            plan.check_link(&out_path, Path::new(&link_target))?;
+            writes.push(ZipWrite::Link(out_path.clone(), link_target));
+            installed.push(out_path);
+            continue;
+        }
+        writes.push(ZipWrite::File(i, out_path.clone(), file.size()));
+        installed.push(out_path);
+        if plan.is_single() {
+            break;
+        }
+    }
+
+    // Entries are independent in a zip, so each worker reads its share through its own handle.
+    // Files written through symlinks depend on the order of entries, which only one worker keeps,
+    // checking each path again once the links before it exist.
+    let mut files: Vec<(usize, &Path, u64)> = writes
+        .iter()
+        .filter_map(|write| match write {
+            ZipWrite::File(i, path, size) => Some((*i, path.as_path(), *size)),
+            ZipWrite::Link(..) => None,
+        })
+        .collect();
+    let has_links = files.len() < writes.len();
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(files.len())
+        .min(MAX_ZIP_WORKERS);
+    if has_links || workers <= 1 {
+        for write in &writes {
+            match write {
+                ZipWrite::File(i, out_path, _) => {
+                    plan.check_write(out_path)?;
+                    write_zip_file(&mut archive, *i, out_path, plan)?;
+                }
+                #[cfg(unix)]
+                ZipWrite::Link(out_path, link_target) => {
+                    plan.check_write(out_path)?;
+                    if fs::symlink_metadata(out_path).is_ok() {
+                        fs::remove_file(out_path)?;
+                    }
+                    std::os::unix::fs::symlink(link_target, out_path)?;
+                }
+                #[cfg(not(unix))]
+                ZipWrite::Link(..) => {}
+            }
+        }
+        return plan.check_found(installed);
+    }
+    drop(archive);
+
+    // Largest files first, so that one big member doesn't start last and hold up the rest
+    files.sort_by_key(|(_, _, size)| std::cmp::Reverse(*size));
+    let next = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| -> Result<()> {
+                    let mut archive = ZipArchive::new(source.reader()?)?;
+                    while let Some((i, out_path, _)) =
+                        files.get(next.fetch_add(1, Ordering::Relaxed))
+                    {
+                        write_zip_file(&mut archive, *i, out_path, plan)?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        handles.into_iter().try_for_each(|handle| {
+            handle
+                .join()
+                .map_err(|_| anyhow!("A zip extraction worker panicked"))?
+        })
+    })?;
+    plan.check_found(installed)
+}
+
+/// Write the regular file at `index` of a zip archive to `out_path`
+fn write_zip_file<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+    out_path: &Path,
+    plan: &ExtractPlan,
+) -> Result<()> {
+    let mut file = archive.by_index(index)?;
+    let mut outfile = File::create(out_path)?;
+    io::copy(&mut file, &mut outfile)?;
+    apply_mode(out_path, file.unix_mode(), plan)
+}
+
+/// Set the permissions of an extracted zip or 7z file from the mode the archive recorded
+fn apply_mode(out_path: &Path, mode: Option<u32>, plan: &ExtractPlan) -> Result<()> {
+    #[cfg(unix)]
+    match mode {
+        // Whole archives keep their recorded modes; selected executables are always runnable
+        Some(mode) if !plan.is_executable_selection() => {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(out_path, fs::Permissions::from_mode(mode & 0o777))?;
+        }
+        _ if plan.is_companion(out_path) => {}
+        _ => set_permissions(out_path)?,
+    }
+    #[cfg(not(unix))]
+    let _ = (out_path, mode, plan);
+    Ok(())
+}
+
+fn extract_tar(source: DownloadSource, plan: &ExtractPlan) -> Result<Vec<PathBuf>> {
+    let mut archive = tar::Archive::new(source.into_reader()?);
+    let mut installed = Vec::new();
+    for entry in archive.entries()? {
+        let mut file = entry?;
+        let path = file.path()?.to_string_lossy().into_owned();
+        plan.check_entry(&path)?;
+        let Some(out_path) = plan.target(&path) else {
+            continue;
+        };
+        let executable = is_executable(&path, file.header().mode().ok());
+        if plan.rejects(
+            &out_path,
+            file.header().entry_type().is_file(),
+            executable,
+            &installed,
+        ) {
+            continue;
+        }
+        plan.check_write(&out_path)?;
+        installed.extend(create_parent_dirs(&out_path)?);
+        let entry_type = file.header().entry_type();
+        if entry_type.is_hard_link() {
+            // Hard links point at an earlier entry, which has to be mapped the same way
+            let Some(name) = file.link_name()? else {
+                continue;
+            };
+            let name = name.to_string_lossy();
+            plan.check_entry(&name)?;
+            let Some(link_target) = plan.target(&name) else {
+                continue;
+            };
+            plan.check_parents(&link_target)?;
+            fs::hard_link(link_target, &out_path)?;
+        } else {
+            if entry_type.is_symlink()
+                && let Some(link_target) = file.link_name()?
+            {
+                plan.check_link(&out_path, &link_target)?;
+            }
+            file.unpack(&out_path)?;
+        }
+        #[cfg(unix)]
+        if plan.is_executable_selection() && !plan.is_companion(&out_path) {
+            set_permissions(&out_path)?;
+        }
+        installed.push(out_path);
+        if plan.is_single() {
+            break;
+        }
+    }
+    plan.check_found(installed)
+}
+
+#[cfg(feature = "sevenz")]
+fn extract_7z(source: DownloadSource, plan: &ExtractPlan) -> Result<Vec<PathBuf>> {
+    let mut rdr = source.into_reader()?;
+    let len = rdr.seek(io::SeekFrom::End(0))?;
+    rdr.seek(io::SeekFrom::Start(0))?;
+    let mut archive = sevenz_rust::SevenZReader::new(rdr, len, sevenz_rust::Password::empty())?;
+
+    let mut installed = Vec::new();
+    let mut done = false;
+    archive.for_each_entries(|entry, file| {
+        if done {
+            return Ok(false);
+        }
+        // The high 16 bits of the attributes hold the Unix mode when flagged by 0x8000
+        let mode = (entry.has_windows_attributes && entry.windows_attributes & 0x8000 != 0)
+            .then_some(entry.windows_attributes >> 16);
+        let executable = is_executable(entry.name(), mode);
+        plan.check_entry(entry.name())
+            .map_err(|e| sevenz_rust::Error::other(e.to_string()))?;
+        let Some(out_path) = plan
+            .target(entry.name())
+            .filter(|p| !plan.rejects(p, !entry.is_directory(), executable, &installed))
+        else {
+            // Entries of a solid block share one stream, so skipped entries must be drained
+            io::copy(file, &mut io::sink())?;
+            return Ok(true);
+        };
+        write_7z_entry(
+            entry.is_directory(),
+            mode,
+            file,
+            &out_path,
+            plan,
+            &mut installed,
+        )
+        .map_err(|e| sevenz_rust::Error::other(e.to_string()))?;
+        done = plan.is_single() && !entry.is_directory();
+        Ok(!done)
+    })?;
+    plan.check_found(installed)
+}
+
+#[cfg(feature = "sevenz")]
+fn write_7z_entry(
+    is_dir: bool,
+    mode: Option<u32>,
+    file: &mut dyn Read,
+    out_path: &Path,
+    plan: &ExtractPlan,
+    installed: &mut Vec<PathBuf>,
+) -> Result<()> {
+    plan.check_write(out_path)?;
+    if is_dir {
+        installed.extend(create_dest_dir(out_path)?);
+        return Ok(());
+    }
+    installed.extend(create_parent_dirs(out_path)?);
+    let mut outfile = File::create(out_path)?;
+    io::copy(file, &mut outfile)?;
+    apply_mode(out_path, mode, plan)?;
+    installed.push(out_path.to_path_buf());
+    Ok(())
+}
+
+/// Attach a disk image with `hdiutil` and install the entries the plan selects from it, taking
+/// an app bundle's executable when no file carries the executable name
+#[cfg(all(target_os = "macos", feature = "dmg"))]
+fn extract_dmg(source: DownloadSource, plan: &ExtractPlan) -> Result<Vec<PathBuf>> {
+    use std::process::Command;
+
+    use crate::plan::Selection;
+
+    /// Detaches the mounted image when dropped
+    struct Mount(tempfile::TempDir);
+    impl Drop for Mount {
+        fn drop(&mut self) {
+            let _ = Command::new("hdiutil")
+                .args(["detach", "-quiet", "-force"])
+                .arg(self.0.path())
+                .status();
+        }
+    }
+
+    let image = source.into_temp_file(".dmg")?;
+    let mount_dir = tempfile::tempdir()?;
+    let status = Command::new("hdiutil")
+        .args(["attach", "-nobrowse", "-readonly", "-noautoopen", "-quiet"])
+        .arg("-mountpoint")
+        .arg(mount_dir.path())
+        .arg(image.path())
+        .status()
+        .map_err(|e| anyhow!("Failed to run hdiutil: {}", e))?;
+    if !status.success() {
+        return Err(anyhow!("hdiutil failed to attach the disk image"));
+    }
+    let mount = Mount(mount_dir);
+    // Other selections treat the mounted image like an unpacked archive
+    if !matches!(plan.selection, Selection::Binary) || !plan.is_single() {
+        return install_tree(mount.0.path(), plan);
+    }
+
+    // Prefer a plain executable, then the executable inside an .app bundle
+    let target_bin_name = plan.exe_name.as_str();
+    let found = find_file(mount.0.path(), &|path| {
+        path.file_name().is_some_and(|n| n == target_bin_name)
+    })
+    .or_else(|| {
+        find_file(mount.0.path(), &|path| {
+            path.parent().is_some_and(|p| p.ends_with("Contents/MacOS"))
+                && path
+                    .file_name()
+                    .is_some_and(|n| n.to_string_lossy().eq_ignore_ascii_case(target_bin_name))
+        })
+    })
+    .ok_or_else(|| anyhow!("Executable '{}' not found in disk image", target_bin_name))?;
+
+    let out_path = plan.dest_dir.join(target_bin_name);
+    let mut installed = create_parent_dirs(&out_path)?;
+    fs::copy(&found, &out_path)?;
+    set_permissions(&out_path)?;
+    drop(mount);
+    installed.push(out_path);
+    Ok(installed)
+}
+
+#[cfg(all(not(target_os = "macos"), feature = "dmg"))]
+fn extract_dmg(_: DownloadSource, _: &ExtractPlan) -> Result<Vec<PathBuf>> {
+    Err(anyhow!(
+        "Disk image (.dmg) assets can only be installed on macOS, or with `grd config set extractors.dmg 7z`; \
+         use --no-decompress to save it as is"
+    ))
+}
+
+/// Depth-first search for a regular file matching `pred`, without following symlinks
+#[cfg(all(target_os = "macos", feature = "dmg"))]
+fn find_file(dir: &Path, pred: &dyn Fn(&Path) -> bool) -> Option<PathBuf> {
+    let mut entries: Vec<_> = fs::read_dir(dir).ok()?.flatten().collect();
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let file_type = entry.file_type().ok()?;
+        let path = entry.path();
+        if file_type.is_file() && pred(&path) {
+            return Some(path);
+        }
+        if file_type.is_dir()
+            && let Some(found) = find_file(&path, pred)
+        {
+            return Some(found);
+        }
+    }
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use tempfile::TempDir;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    use super::*;
+    use crate::plan::Selection;
+
+    /// A destination directory, next to an `outside` directory entries must not reach
+    fn setup() -> (TempDir, ExtractPlan) {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir(root.path().join("dest")).unwrap();
+        fs::create_dir(root.path().join("outside")).unwrap();
+        let plan = ExtractPlan::new(root.path().join("dest"), "tool", Selection::All);
+        (root, plan)
+    }
+
+    fn escaped(root: &TempDir) -> bool {
+        root.path().join("outside/pwned.txt").exists() || root.path().join("pwned.txt").exists()
+    }
+
+    enum Entry {
+        File(&'static str),
+        Link(&'static str, &'static str),
+    }
+
+    /// A tar archive of `entries`, with names written as is so that unsafe ones can be built
+    fn tar(entries: &[Entry]) -> DownloadSource {
+        let mut builder = tar::Builder::new(Vec::new());
+        for entry in entries {
+            let mut header = tar::Header::new_gnu();
+            let (name, data): (&str, &[u8]) = match entry {
+                Entry::File(name) => {
+                    header.set_entry_type(tar::EntryType::Regular);
+                    (name, b"pwned")
+                }
+                Entry::Link(name, target) => {
+                    header.set_entry_type(tar::EntryType::Symlink);
+                    header.set_link_name_literal(target).unwrap();
+                    (name, b"")
+                }
+            };
+            let raw = &mut header.as_gnu_mut().unwrap().name;
+            raw[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_mode(0o644);
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            builder.append(&header, data).unwrap();
+        }
+        DownloadSource::Memory(builder.into_inner().unwrap())
+    }
+
+    fn zip(entries: &[Entry]) -> DownloadSource {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for entry in entries {
+            match entry {
+                Entry::File(name) => {
+                    writer.start_file(*name, options).unwrap();
+                    writer.write_all(b"pwned").unwrap();
+                }
+                Entry::Link(name, target) => writer.add_symlink(*name, *target, options).unwrap(),
+            }
+        }
+        DownloadSource::Memory(writer.finish().unwrap().into_inner())
+    }
+
+    const CHAINED: [Entry; 3] = [
+        Entry::Link("a", "."),
+        Entry::Link("a/b", "../outside"),
+        Entry::File("a/b/pwned.txt"),
+    ];
+
+    #[test]
+    fn tar_rejects_parent_and_absolute_paths() {
+        let (root, plan) = setup();
+        assert!(extract_tar(tar(&[Entry::File("../pwned.txt")]), &plan).is_err());
+        let absolute = format!("{}/outside/pwned.txt", root.path().display());
+        let absolute: &'static str = absolute.leak();
+        assert!(extract_tar(tar(&[Entry::File(absolute)]), &plan).is_err());
+        assert!(!escaped(&root));
+    }
+
+    #[test]
+    fn tar_rejects_chained_symlinks() {
+        let (root, plan) = setup();
+        assert!(extract_tar(tar(&CHAINED), &plan).is_err());
+        assert!(!escaped(&root));
+    }
+
+    #[test]
+    fn tar_rejects_links_out() {
+        let (root, plan) = setup();
+        let entries = [Entry::Link("b", "../outside"), Entry::File("b/pwned.txt")];
+        assert!(extract_tar(tar(&entries), &plan).is_err());
+        assert!(!escaped(&root));
+    }
+
+    #[test]
+    fn tar_keeps_links_inside() {
+        let (root, plan) = setup();
+        let entries = [
+            Entry::File("bin/tool"),
+            Entry::Link("lib/tool", "../bin/tool"),
+        ];
+        extract_tar(tar(&entries), &plan).unwrap();
+        let link = root.path().join("dest/lib/tool");
+        assert_eq!(fs::read_link(link).unwrap(), Path::new("../bin/tool"));
+    }
+
+    #[test]
+    fn zip_rejects_parent_and_absolute_paths() {
+        let (root, plan) = setup();
+        assert!(extract_zip(zip(&[Entry::File("../pwned.txt")]), &plan).is_err());
+        let absolute = format!("{}/outside/pwned.txt", root.path().display());
+        let absolute: &'static str = absolute.leak();
+        assert!(extract_zip(zip(&[Entry::File(absolute)]), &plan).is_err());
+        assert!(!escaped(&root));
+    }
+
+    #[test]
+    fn zip_rejects_chained_symlinks() {
+        let (root, plan) = setup();
+        assert!(extract_zip(zip(&CHAINED), &plan).is_err());
+        assert!(!escaped(&root));
+    }
+
+    #[test]
+    fn writes_through_no_symlink_left_in_the_destination() {
+        let (root, plan) = setup();
+        std::os::unix::fs::symlink("../outside", root.path().join("dest/b")).unwrap();
+        assert!(extract_tar(tar(&[Entry::File("b/pwned.txt")]), &plan).is_err());
+        assert!(extract_zip(zip(&[Entry::File("b/pwned.txt")]), &plan).is_err());
+        assert!(!escaped(&root));
+
+        // A symlink at the entry's own path is replaced, not written through
+        std::os::unix::fs::symlink("../outside/pwned.txt", root.path().join("dest/c")).unwrap();
+        extract_tar(tar(&[Entry::File("c")]), &plan).unwrap();
+        assert!(!escaped(&root));
+        assert!(
+            fs::symlink_metadata(root.path().join("dest/c"))
+                .unwrap()
+                .is_file()
+        );
+    }
+
+    #[test]
+    fn tarballs_without_magic_are_known_by_name() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_old();
+        header.set_path("tool").unwrap();
+        header.set_mode(0o755);
+        header.set_size(5);
+        header.set_cksum();
+        builder.append(&header, &b"tool\n"[..]).unwrap();
+        let source = DownloadSource::Memory(builder.into_inner().unwrap());
+        assert_eq!(
+            Format::detect("tool-linux.tar", None, &source).unwrap(),
+            Some(Format::Tar)
+        );
+        assert_eq!(Format::detect("tool-linux", None, &source).unwrap(), None);
+    }
+
+    #[test]
+    fn external_trees_are_checked_too() {
+        let (root, plan) = setup();
+        let tree = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink("../../outside", tree.path().join("b")).unwrap();
+        assert!(install_tree(tree.path(), &plan).is_err());
+
+        // A directory of the tree replaces a symlink the destination holds at its path
+        let tree = tempfile::tempdir().unwrap();
+        fs::create_dir(tree.path().join("b")).unwrap();
+        fs::write(tree.path().join("b/pwned.txt"), "pwned").unwrap();
+        std::os::unix::fs::symlink("../outside", root.path().join("dest/b")).unwrap();
+        install_tree(tree.path(), &plan).unwrap();
+        assert!(!escaped(&root));
+        assert!(root.path().join("dest/b/pwned.txt").is_file());
+    }
+}
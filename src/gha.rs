@@ -0,0 +1,38 @@
+//! GitHub Actions workflow commands, so failures and summaries show up as annotations
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Emit annotations from now on
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn error(message: &str) {
+    annotate("error", message);
+}
+
+/// Print a warning, annotated as such in GitHub Actions
+pub fn warning(message: &str) {
+    eprintln!("Warning: {}", message);
+    annotate("warning", message);
+}
+
+pub fn notice(message: &str) {
+    annotate("notice", message);
+}
+
+fn annotate(level: &str, message: &str) {
+    if ENABLED.load(Ordering::Relaxed) {
+        println!("::{} title=grd::{}", level, escape(message));
+    }
+}
+
+/// Escape the characters that would end or corrupt a workflow command
+fn escape(message: &str) -> String {
+    message
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
@@ -0,0 +1,90 @@
+//! Checks that a destination directory is safe to install executables into
+//!
+//! Installed files are run later, often from `PATH`, so a directory that other users can write
+//! to, or a symlink they control, lets them swap in their own executable.
+
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+
+/// Warn about anything that lets other users tamper with files installed in `dir`, or fail
+/// instead when `paranoid`
+pub fn check_destination(dir: &Path, paranoid: bool) -> Result<()> {
+    let problems = problems(dir)?;
+    if problems.is_empty() {
+        return Ok(());
+    }
+    if paranoid {
+        return Err(anyhow!(
+            "Refusing to install into {:?}:\n  - {}",
+            dir,
+            problems.join("\n  - ")
+        ));
+    }
+    for problem in &problems {
+        eprintln!("Warning: {}", problem);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn problems(dir: &Path) -> Result<Vec<String>> {
+    use std::{fs, os::unix::fs::MetadataExt};
+
+    // std has no getuid(); a file we just created is owned by the effective user
+    let uid = tempfile::tempfile()?.metadata()?.uid();
+    let trusted = |owner: u32| owner == uid || owner == 0;
+    let mut problems = Vec::new();
+
+    // The directory may not exist yet; it will be created in its nearest existing ancestor
+    let dir = std::path::absolute(dir)?;
+    let Some(existing) = dir.ancestors().find(|d| d.exists()) else {
+        return Ok(problems);
+    };
+    for path in existing.ancestors() {
+        let metadata = fs::symlink_metadata(path)?;
+        if metadata.file_type().is_symlink() && !trusted(metadata.uid()) {
+            problems.push(format!(
+                "{:?} is a symlink owned by another user (uid {})",
+                path,
+                metadata.uid()
+            ));
+        }
+    }
+
+    let resolved = fs::canonicalize(existing)?;
+    if resolved != existing {
+        eprintln!("Destination {:?} resolves to {:?}", existing, resolved);
+    }
+    for (depth, path) in resolved.ancestors().enumerate() {
+        let metadata = fs::metadata(path)?;
+        if !trusted(metadata.uid()) {
+            problems.push(format!(
+                "{:?} is owned by another user (uid {})",
+                path,
+                metadata.uid()
+            ));
+        }
+        let world_writable = metadata.mode() & 0o002 != 0;
+        let sticky = metadata.mode() & 0o1000 != 0;
+        if depth == 0 && world_writable {
+            // Even with the sticky bit (like /tmp), others can plant files before grd writes
+            problems.push(format!(
+                "{:?} is world-writable, so any user can place executables in it",
+                path
+            ));
+        } else if world_writable && !sticky {
+            problems.push(format!(
+                "{:?} is world-writable, so any user can replace the directories in it",
+                path
+            ));
+        }
+    }
+    Ok(problems)
+}
+
+/// Windows ACLs aren't inspected
+#[cfg(not(unix))]
+fn problems(_dir: &Path) -> Result<Vec<String>> {
+    Ok(Vec::new())
+}
@@ -0,0 +1,24 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::{Result, anyhow};
+
+use crate::state::{Scope, State};
+
+/// Print when each version of a tool was installed, updated or removed
+pub fn history(name: &str, scope: Scope) -> Result<()> {
+    let state = State::load(scope)?;
+    let events: Vec<_> = state.history.iter().filter(|e| e.tool == name).collect();
+    if events.is_empty() {
+        return Err(anyhow!("No history recorded for '{}'", name));
+    }
+
+    eprintln!("History of {}:", name);
+    for event in events {
+        let at = humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(event.at));
+        println!(
+            "  {}  {:<9}  {:<12}  {}",
+            at, event.action, event.tag, event.asset
+        );
+    }
+    Ok(())
+}
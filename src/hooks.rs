@@ -0,0 +1,190 @@
+//! Shell commands run around installs
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+/// Commands a manifest entry runs around changes to its tool
+///
+/// They are recorded with the install, so that `grd update` and `grd rollback` run them too.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ToolHooks {
+    /// Run before installing or replacing the tool; a failure leaves it untouched
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_install: Vec<String>,
+
+    /// Run after the tool was installed for the first time
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_install: Vec<String>,
+
+    /// Run after another version replaced the installed one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_update: Vec<String>,
+
+    /// Directory the commands run in, the manifest's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+}
+
+/// When tool hooks run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    PreInstall,
+    PostInstall,
+    PostUpdate,
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::PreInstall => "pre_install",
+            Event::PostInstall => "post_install",
+            Event::PostUpdate => "post_update",
+        }
+    }
+}
+
+/// The change hooks are told about through their environment
+pub struct Change<'a> {
+    pub tool: &'a str,
+    pub repo: &'a str,
+    /// Installed version being replaced, if any
+    pub from: Option<&'a str>,
+    pub to: &'a str,
+    pub destination: &'a Path,
+}
+
+impl ToolHooks {
+    pub fn is_empty(&self) -> bool {
+        self.pre_install.is_empty() && self.post_install.is_empty() && self.post_update.is_empty()
+    }
+
+    /// Run the commands of `event` for `change`, in the manifest's directory (or the destination)
+    ///
+    /// Their environment has `GRD_EVENT`, `GRD_TOOL`, `GRD_REPO`, `GRD_TAG`, `GRD_DESTINATION`,
+    /// and `GRD_PREVIOUS_TAG` when a version is replaced.
+    pub fn run(&self, event: Event, change: &Change) -> Result<()> {
+        let commands = match event {
+            Event::PreInstall => &self.pre_install,
+            Event::PostInstall => &self.post_install,
+            Event::PostUpdate => &self.post_update,
+        };
+        if commands.is_empty() {
+            return Ok(());
+        }
+        let mut env = vec![
+            ("GRD_EVENT", event.name().to_string()),
+            ("GRD_TOOL", change.tool.to_string()),
+            ("GRD_REPO", change.repo.to_string()),
+            ("GRD_TAG", change.to.to_string()),
+            ("GRD_DESTINATION", change.destination.display().to_string()),
+        ];
+        if let Some(from) = change.from {
+            env.push(("GRD_PREVIOUS_TAG", from.to_string()));
+        }
+        let dir = self.dir.as_deref().unwrap_or(change.destination);
+        eprintln!(
+            "Running the {} hook(s) of '{}' in {:?}",
+            event.name(),
+            change.tool,
+            dir
+        );
+        run(commands, &env, dir)
+            .with_context(|| format!("{} hook of '{}' failed", event.name(), change.tool))
+    }
+}
+
+/// Run each command through the platform shell in `dir`, with `env` added to the environment,
+/// stopping at the first failure
+pub fn run(commands: &[String], env: &[(&str, String)], dir: &Path) -> Result<()> {
+    for command in commands {
+        eprintln!("Running: {}", command);
+        let mut shell = if cfg!(windows) {
+            let mut shell = Command::new("cmd");
+            shell.arg("/C").arg(command);
+            shell
+        } else {
+            let mut shell = Command::new("sh");
+            shell.arg("-c").arg(command);
+            shell
+        };
+        let status = shell
+            .current_dir(dir)
+            .envs(env.iter().map(|(key, value)| (key, value)))
+            .status()
+            .with_context(|| format!("Failed to run '{}'", command))?;
+        if !status.success() {
+            return Err(anyhow!("'{}' failed: {}", command, status));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn change(destination: &Path) -> Change<'_> {
+        Change {
+            tool: "rg",
+            repo: "BurntSushi/ripgrep",
+            from: Some("14.0.0"),
+            to: "14.1.0",
+            destination,
+        }
+    }
+
+    #[test]
+    fn hooks_are_told_about_the_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let hooks = ToolHooks {
+            post_update: vec![
+                "echo $GRD_EVENT $GRD_TOOL $GRD_REPO > log".to_string(),
+                "echo $GRD_PREVIOUS_TAG $GRD_TAG >> log".to_string(),
+            ],
+            ..ToolHooks::default()
+        };
+        // Without a manifest directory they run in the destination
+        hooks.run(Event::PostUpdate, &change(dir.path())).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("log")).unwrap(),
+            "post_update rg BurntSushi/ripgrep\n14.0.0 14.1.0\n"
+        );
+        assert!(!hooks.is_empty());
+        assert!(ToolHooks::default().is_empty());
+    }
+
+    #[test]
+    fn hooks_run_in_the_manifest_directory_until_one_fails() {
+        let (manifest, destination) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let hooks = ToolHooks {
+            pre_install: vec![
+                "touch first".to_string(),
+                "false".to_string(),
+                "touch never".to_string(),
+            ],
+            post_install: vec!["touch installed".to_string()],
+            dir: Some(manifest.path().to_path_buf()),
+            ..ToolHooks::default()
+        };
+        let error = hooks
+            .run(Event::PreInstall, &change(destination.path()))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "pre_install hook of 'rg' failed");
+        assert!(manifest.path().join("first").exists());
+        assert!(!manifest.path().join("never").exists());
+
+        // Other events' commands are left alone
+        hooks
+            .run(Event::PostUpdate, &change(destination.path()))
+            .unwrap();
+        assert!(!manifest.path().join("installed").exists());
+        assert_eq!(fs::read_dir(destination.path()).unwrap().count(), 0);
+    }
+}
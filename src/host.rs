@@ -0,0 +1,133 @@
+use std::fs;
+
+use anyhow::{Result, anyhow};
+
+use crate::gha;
+
+/// C library a Linux binary is built against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Libc {
+    Gnu,
+    Musl,
+}
+
+impl std::fmt::Display for Libc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Libc::Gnu => "glibc",
+            Libc::Musl => "musl",
+        })
+    }
+}
+
+/// Detect the host's C library from the dynamic loaders it ships
+///
+/// glibc wins when both are present, since musl loaders are commonly installed next to it
+/// (e.g. by `musl-tools`) while glibc binaries still run natively.
+pub fn host_libc() -> Option<Libc> {
+    let names = ["/lib", "/lib64", "/usr/lib"]
+        .into_iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned());
+    loader_libc(names)
+}
+
+/// C library of the dynamic loaders among the file `names` of the library directories
+fn loader_libc(names: impl IntoIterator<Item = String>) -> Option<Libc> {
+    let mut found = None;
+    for name in names {
+        if name.starts_with("ld-linux") {
+            return Some(Libc::Gnu);
+        }
+        if name.starts_with("ld-musl-") {
+            found = Some(Libc::Musl);
+        }
+    }
+    found
+}
+
+/// C library an asset is built against, judging by its name
+pub fn asset_libc(asset_name: &str) -> Option<Libc> {
+    let name = asset_name.to_lowercase();
+    if name.contains("musl") {
+        Some(Libc::Musl)
+    } else if name.contains("gnu") {
+        Some(Libc::Gnu)
+    } else {
+        None
+    }
+}
+
+/// Warn (or fail when `strict`) if a Linux asset targets a different C library than the host
+pub fn check_libc(asset_name: &str, strict: bool) -> Result<()> {
+    let (Some(host), Some(asset)) = (host_libc(), asset_libc(asset_name)) else {
+        return Ok(());
+    };
+    if host == asset {
+        return Ok(());
+    }
+    let message = format!(
+        "'{}' is built for {} but this host uses {}; the installed binary may not run",
+        asset_name, asset, host
+    );
+    if strict {
+        return Err(anyhow!("{} (--strict-libc)", message));
+    }
+    gha::warning(&message);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_the_host_libc_from_its_loaders() {
+        for (names, expected) in [
+            (&["ld-linux-x86-64.so.2", "libc.so.6"][..], Some(Libc::Gnu)),
+            (&["ld-linux-aarch64.so.1"], Some(Libc::Gnu)),
+            (
+                &["ld-musl-x86_64.so.1", "libc.musl-x86_64.so.1"],
+                Some(Libc::Musl),
+            ),
+            // musl-tools next to glibc
+            (
+                &["ld-musl-x86_64.so.1", "ld-linux-x86-64.so.2"],
+                Some(Libc::Gnu),
+            ),
+            (
+                &["ld-linux-x86-64.so.2", "ld-musl-x86_64.so.1"],
+                Some(Libc::Gnu),
+            ),
+            (&["libz.so.1", "firmware"], None),
+            (&[], None),
+        ] {
+            let found = loader_libc(names.iter().map(|name| name.to_string()));
+            assert_eq!(found, expected, "{:?}", names);
+        }
+    }
+
+    #[test]
+    fn reads_the_libc_from_asset_names() {
+        for (name, expected) in [
+            (
+                "ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz",
+                Some(Libc::Musl),
+            ),
+            (
+                "ripgrep-14.1.0-x86_64-unknown-linux-gnu.tar.gz",
+                Some(Libc::Gnu),
+            ),
+            (
+                "fd-v10.1.0-aarch64-unknown-linux-GNU.tar.gz",
+                Some(Libc::Gnu),
+            ),
+            ("tool_linux-musleabihf_armv7.zip", Some(Libc::Musl)),
+            ("bat-v0.24.0-x86_64-apple-darwin.tar.gz", None),
+            ("gh_2.40.0_linux_amd64.tar.gz", None),
+        ] {
+            assert_eq!(asset_libc(name), expected, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    io,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Result, anyhow};
+use ureq::{
+    Agent, Body,
+    http::{Response, StatusCode},
+};
+
+use crate::{mirror, progress::ProgressBar, token};
+
+/// Build the agent shared by every request of a run
+///
+/// A single agent pools keep-alive connections, so the release lookup and every download from
+/// the same host reuse connections instead of paying a new TLS handshake each time. API requests
+/// carry the token from `GITHUB_TOKEN`/`GH_TOKEN` when one is set, and go to the mirror named by
+/// `GRD_MIRROR` instead when that is set.
+pub fn build_agent() -> Agent {
+    let ua = format!("lucidfrontier45/grd-{}", env!("CARGO_PKG_VERSION"));
+    let builder = Agent::config_builder()
+        .user_agent(&ua)
+        .max_idle_connections_per_host(4)
+        .max_idle_age(Duration::from_secs(60));
+    // Redirected requests leave GitHub, so the token isn't added to them
+    let builder = match mirror::base() {
+        Some(base) => builder.middleware(mirror::redirect(base)),
+        None => builder,
+    };
+    let builder = match token::from_env() {
+        Some((variable, token)) => match token::authorize(&token) {
+            Some(middleware) => builder.middleware(middleware),
+            None => {
+                eprintln!("Warning: ignoring {}, which is not a valid token", variable);
+                builder
+            }
+        },
+        None => builder,
+    };
+    builder.build().into()
+}
+
+/// Longest `Retry-After` waited out; a server asking for more is given up on instead of hanging
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// GET a URL, waiting out 429/503 throttling responses as instructed by `Retry-After`
+pub fn get_with_retry(agent: &Agent, url: &str, pb: &ProgressBar) -> Result<Response<Body>> {
+    get_if_none_match(agent, url, None, pb)?
+        .ok_or_else(|| anyhow!("Failed to download {}: {}", url, StatusCode::NOT_MODIFIED))
+}
+
+/// GET a URL like [`get_with_retry`], unless it still has the entity tag `etag` (`None`)
+pub fn get_if_none_match(
+    agent: &Agent,
+    url: &str,
+    etag: Option<&str>,
+    pb: &ProgressBar,
+) -> Result<Option<Response<Body>>> {
+    const MAX_RETRIES: u32 = 5;
+    let mut attempt = 0;
+    loop {
+        let mut request = agent.get(url);
+        if let Some(etag) = etag {
+            request = request.header("If-None-Match", etag);
+        }
+        let response = request
+            .config()
+            .http_status_as_error(false)
+            .build()
+            .call()?;
+        let status = response.status();
+        if status.is_success() {
+            pb.set_message("");
+            return Ok(Some(response));
+        }
+        if status == StatusCode::NOT_MODIFIED && etag.is_some() {
+            return Ok(None);
+        }
+        let throttled =
+            status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE;
+        if !throttled || attempt >= MAX_RETRIES {
+            return Err(anyhow!("Failed to download {}: {}", url, status));
+        }
+        attempt += 1;
+        // Fall back to exponential backoff when the server gives no hint
+        let wait = retry_after(&response).unwrap_or(Duration::from_secs(1 << attempt));
+        if wait > MAX_WAIT {
+            return Err(anyhow!(
+                "Failed to download {}: {}, and the server asks to retry in {}s, longer than grd waits ({}s)",
+                url,
+                status,
+                wait.as_secs(),
+                MAX_WAIT.as_secs()
+            ));
+        }
+        // Drain the error body so the connection goes back to the pool
+        io::copy(&mut response.into_body().into_reader(), &mut io::sink())?;
+
+        pb.set_message(format!(
+            "throttled ({}), retrying in {}s ({}/{})",
+            status.as_u16(),
+            wait.as_secs(),
+            attempt,
+            MAX_RETRIES
+        ));
+        std::thread::sleep(wait);
+    }
+}
+
+/// Parse a `Retry-After` header given either as delay seconds or as an HTTP date
+fn retry_after<B>(response: &Response<B>) -> Option<Duration> {
+    let value = response.headers().get("retry-after")?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(
+        at.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(retry_after: &str) -> Response<()> {
+        Response::builder()
+            .header("retry-after", retry_after)
+            .body(())
+            .expect("valid response")
+    }
+
+    #[test]
+    fn retry_after_reads_seconds() {
+        assert_eq!(
+            retry_after(&response(" 120 ")),
+            Some(Duration::from_secs(120))
+        );
+    }
+
+    #[test]
+    fn retry_after_reads_dates() {
+        assert_eq!(
+            retry_after(&response("Wed, 21 Oct 2015 07:28:00 GMT")),
+            Some(Duration::ZERO)
+        );
+        let later = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(3600));
+        let wait = retry_after(&response(&later)).expect("a date");
+        assert!(wait > MAX_WAIT && wait <= Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn retry_after_ignores_garbage() {
+        assert_eq!(retry_after(&response("soon")), None);
+        assert_eq!(retry_after(&Response::new(())), None);
+    }
+}
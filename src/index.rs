@@ -0,0 +1,222 @@
+//! Compact index of the installed tools, so listings don't parse every receipt and the history
+//!
+//! After a header naming the format, one tab-separated line per tool: name, repository, tag,
+//! destination, install time, the tracked channel and the release's publication time (both
+//! possibly empty), executable name, asset and its SHA-256, then one `<sha256> <path>` field per
+//! file written, with `-` for links and directories. It is rewritten with the state file, and rebuilt from it when missing,
+//! older or of another format.
+
+use std::{
+    fs,
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+
+use clap::ValueEnum;
+
+use crate::{
+    channel::Channel,
+    state::{Scope, State},
+};
+
+/// First line of the index, changed with its columns so that older indexes are rebuilt
+const HEADER: &str = "# grd index v2";
+
+pub struct Entry {
+    pub name: String,
+    pub repo: String,
+    pub tag: String,
+    pub destination: PathBuf,
+    /// Unix timestamp of the install
+    pub installed_at: u64,
+    pub channel: Option<Channel>,
+    pub published_at: Option<String>,
+    pub bin_name: String,
+    pub asset: String,
+    /// SHA-256 of the downloaded asset
+    pub sha256: String,
+    /// Files and directories written by the install, with the SHA-256 of regular files
+    pub files: Vec<(PathBuf, Option<String>)>,
+}
+
+/// Location of the index, next to the state file
+pub fn path(scope: Scope) -> Result<PathBuf> {
+    Ok(scope.data_dir()?.join("index.tsv"))
+}
+
+/// The installed tools of `scope`, from the index
+pub fn load(scope: Scope) -> Result<Vec<Entry>> {
+    let path = path(scope)?;
+    if is_stale(&path, &State::path(scope)?) {
+        let state = State::load(scope)?;
+        // Best effort: a read-only data directory only costs the next listing a rebuild too
+        if State::path(scope)?.exists() {
+            let _ = write(&state);
+        }
+        return Ok(entries(&state));
+    }
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read index {:?}", path))?;
+    text.lines()
+        .skip(1)
+        .filter(|line| !line.is_empty())
+        .map(|line| parse_line(line).ok_or_else(|| anyhow!("Corrupt index {:?}: {:?}", path, line)))
+        .collect()
+}
+
+/// Write the index of `state` atomically
+pub fn write(state: &State) -> Result<()> {
+    let path = path(state.scope)?;
+    let mut text = format!("{}\n", HEADER);
+    for e in entries(state) {
+        text.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            e.name,
+            e.repo,
+            e.tag,
+            e.destination.display(),
+            e.installed_at,
+            e.channel.map(Channel::name).unwrap_or_default(),
+            e.published_at.as_deref().unwrap_or_default(),
+            e.bin_name,
+            e.asset,
+            e.sha256
+        ));
+        for (path, sha256) in &e.files {
+            let sha256 = sha256.as_deref().unwrap_or("-");
+            text.push_str(&format!("\t{} {}", sha256, path.display()));
+        }
+        text.push('\n');
+    }
+    let tmp = path.with_extension("tsv.tmp");
+    fs::write(&tmp, text)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+/// Whether the index is missing, older than the state file it is built from, or of another
+/// format
+pub fn is_stale(index: &Path, state: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let current = || {
+        let mut header = String::new();
+        fs::File::open(index)
+            .and_then(|file| io::BufReader::new(file).read_line(&mut header))
+            .is_ok_and(|_| header.trim_end() == HEADER)
+    };
+    !matches!(
+        (modified(index), modified(state)),
+        (Some(index), Some(state)) if index >= state
+    ) || !current()
+}
+
+fn entries(state: &State) -> Vec<Entry> {
+    state
+        .tools
+        .iter()
+        .map(|(name, receipt)| Entry {
+            name: name.clone(),
+            repo: receipt.repo.clone(),
+            tag: receipt.tag.clone(),
+            destination: receipt.destination.clone(),
+            installed_at: receipt.installed_at,
+            channel: receipt.channel,
+            published_at: receipt.published_at.clone(),
+            bin_name: receipt.bin_name.clone(),
+            asset: receipt.asset.clone(),
+            sha256: receipt.sha256.clone(),
+            files: receipt
+                .files
+                .iter()
+                .map(|f| (f.clone(), receipt.digests.get(f).cloned()))
+                .collect(),
+        })
+        .collect()
+}
+
+fn parse_line(line: &str) -> Option<Entry> {
+    let mut fields = line.split('\t');
+    let entry = Entry {
+        name: fields.next()?.to_string(),
+        repo: fields.next()?.to_string(),
+        tag: fields.next()?.to_string(),
+        destination: PathBuf::from(fields.next()?),
+        installed_at: fields.next()?.parse().ok()?,
+        channel: match fields.next()? {
+            "" => None,
+            name => Some(Channel::from_str(name, false).ok()?),
+        },
+        published_at: Some(fields.next()?)
+            .filter(|at| !at.is_empty())
+            .map(str::to_string),
+        bin_name: fields.next()?.to_string(),
+        asset: fields.next()?.to_string(),
+        sha256: fields.next()?.to_string(),
+        files: fields
+            .map(|field| {
+                let (sha256, path) = field.split_once(' ')?;
+                let sha256 = (sha256 != "-").then(|| sha256.to_string());
+                Some((PathBuf::from(path), sha256))
+            })
+            .collect::<Option<_>>()?,
+    };
+    Some(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_lines() {
+        let entry = parse_line(
+            "rg\tBurntSushi/ripgrep\t14.1.0\t/home/u/.local/bin\t1700000000\t\t\trg\trg.tar.gz\tabc",
+        )
+        .unwrap();
+        assert_eq!(entry.name, "rg");
+        assert_eq!(entry.repo, "BurntSushi/ripgrep");
+        assert_eq!(entry.tag, "14.1.0");
+        assert_eq!(entry.destination, PathBuf::from("/home/u/.local/bin"));
+        assert_eq!(entry.installed_at, 1700000000);
+        assert_eq!(entry.channel, None);
+        assert_eq!(entry.published_at, None);
+        assert_eq!(
+            (entry.bin_name.as_str(), entry.asset.as_str()),
+            ("rg", "rg.tar.gz")
+        );
+        assert_eq!(entry.sha256, "abc");
+        assert!(entry.files.is_empty());
+
+        let entry = parse_line(
+            "fd\tsharkdp/fd\tv10.0.0-rc1\t/bin\t1\trc\t2024-06-01T00:00:00Z\tfd\tfd.zip\tabc\tdef /bin/fd\t- /bin/my fd",
+        )
+        .unwrap();
+        assert_eq!(entry.channel, Some(Channel::Rc));
+        assert_eq!(entry.published_at.as_deref(), Some("2024-06-01T00:00:00Z"));
+        assert_eq!(
+            entry.files,
+            [
+                (PathBuf::from("/bin/fd"), Some("def".to_string())),
+                (PathBuf::from("/bin/my fd"), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_corrupt_lines() {
+        assert!(parse_line("rg\tBurntSushi/ripgrep\t14.1.0\t/bin").is_none());
+        assert!(parse_line("rg\tBurntSushi/ripgrep\t14.1.0\t/bin\t1700000000").is_none());
+        assert!(
+            parse_line("rg\tBurntSushi/ripgrep\t14.1.0\t/bin\tyesterday\t\t\trg\ta\tabc").is_none()
+        );
+        assert!(
+            parse_line("rg\tBurntSushi/ripgrep\t14.1.0\t/bin\t1\tweekly\t\trg\ta\tabc").is_none()
+        );
+        assert!(
+            parse_line("rg\tBurntSushi/ripgrep\t14.1.0\t/bin\t1\tbeta\t\trg\ta\tabc\tnospace")
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::Result;
+use serde::Serialize;
+use ureq::Agent;
+
+use crate::{
+    install::InstallOptions,
+    release::{Asset, Release},
+    resolve::{fetch_release_info, resolve_release},
+    select::matching_assets,
+    table::print_table,
+    units::format_size,
+};
+
+/// A release as printed by `--json` (see `grd schema release`)
+#[derive(Serialize)]
+pub struct ReleaseReport<'a> {
+    repo: &'a str,
+    tag: &'a str,
+    name: Option<&'a str>,
+    published_at: Option<&'a str>,
+    /// Account that published the release
+    author: Option<&'a str>,
+    prerelease: bool,
+    draft: bool,
+    url: Option<&'a str>,
+    assets: Vec<AssetReport<'a>>,
+}
+
+#[derive(Serialize)]
+struct AssetReport<'a> {
+    name: &'a str,
+    size: u64,
+    downloads: u64,
+    /// Media type declared by the uploader
+    content_type: Option<&'a str>,
+    url: &'a str,
+    /// Digest computed by GitHub, like `sha256:<hex>`
+    digest: Option<&'a str>,
+    /// Whether the asset matches the selected platform, for `grd info`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matches: Option<bool>,
+}
+
+impl<'a> ReleaseReport<'a> {
+    pub fn new(repo: &'a str, release: &'a Release) -> Self {
+        ReleaseReport {
+            repo,
+            tag: &release.tag_name,
+            name: release.name.as_deref().filter(|n| !n.is_empty()),
+            published_at: release.published_at.as_deref(),
+            author: release.author.as_ref().map(|a| a.login.as_str()),
+            prerelease: release.prerelease,
+            draft: release.draft,
+            url: release.html_url.as_deref(),
+            assets: release.assets.iter().map(AssetReport::new).collect(),
+        }
+    }
+}
+
+impl<'a> AssetReport<'a> {
+    fn new(asset: &'a Asset) -> Self {
+        AssetReport {
+            name: &asset.name,
+            size: asset.size,
+            downloads: asset.download_count,
+            content_type: asset.content_type.as_deref(),
+            url: &asset.browser_download_url,
+            digest: asset.digest.as_deref(),
+            matches: None,
+        }
+    }
+}
+
+/// Print the assets of a release with their sizes, or the release as JSON
+pub fn list_assets(
+    agent: &Agent,
+    repo: &str,
+    tag: Option<&str>,
+    si: bool,
+    json: bool,
+) -> Result<()> {
+    let release = fetch_release_info(agent, repo, tag, false)?;
+    if json {
+        let report = ReleaseReport::new(repo, &release);
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    eprintln!("Assets of {} {}:", repo, release.tag_name);
+    for asset in &release.assets {
+        println!("{:<60} {:>12}", asset.name, format_size(asset.size, si));
+    }
+    Ok(())
+}
+
+/// Print every asset of a release with its size and content type, marking those matching the
+/// selected platform, and why none would be picked when nothing matches
+pub fn assets(agent: &Agent, repo: &str, opts: &InstallOptions, json: bool) -> Result<()> {
+    let release = resolve_release(agent, repo, opts)?;
+    let matches = matching_assets(&release.assets, opts);
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report(repo, &release, &matches))?
+        );
+        return Ok(());
+    }
+    eprintln!(
+        "Assets of {} {} ({} matching {}-{}, marked with *):",
+        repo,
+        release.tag_name,
+        matches.len(),
+        opts.os,
+        opts.arch
+    );
+    let rows: Vec<Vec<String>> = release
+        .assets
+        .iter()
+        .map(|asset| {
+            let marker = if matches.iter().any(|m| m.name == asset.name) {
+                "*"
+            } else {
+                ""
+            };
+            vec![
+                marker.to_string(),
+                asset.name.clone(),
+                format_size(asset.size, opts.si),
+                asset
+                    .content_type
+                    .clone()
+                    .unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+    print_table(&["", "NAME", "SIZE", "CONTENT TYPE"], &rows);
+    match (matches.len(), &opts.asset_pattern) {
+        (0, Some(pattern)) => eprintln!(
+            "No asset matches the configured asset glob '{}' for {}-{}",
+            pattern, opts.os, opts.arch
+        ),
+        (0, None) => eprintln!(
+            "No asset names both the OS ({}) and the architecture ({}); pick one with an asset glob in the config file",
+            opts.os, opts.arch
+        ),
+        (1, _) => {}
+        (_, _) => eprintln!(
+            "Several assets match; grd prompts for one unless --first, --exclude or --prefer narrows them down"
+        ),
+    }
+    Ok(())
+}
+
+/// The JSON report of `release`, telling for each asset whether it is among `matches`
+fn report<'a>(repo: &'a str, release: &'a Release, matches: &[&Asset]) -> ReleaseReport<'a> {
+    let mut report = ReleaseReport::new(repo, release);
+    for asset in &mut report.assets {
+        asset.matches = Some(matches.iter().any(|m| m.name == asset.name));
+    }
+    report
+}
+
+/// Print a release and the assets matching the selected platform, without downloading anything
+pub fn info(agent: &Agent, repo: &str, opts: &InstallOptions, json: bool) -> Result<()> {
+    let release = resolve_release(agent, repo, opts)?;
+    let matches = matching_assets(&release.assets, opts);
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report(repo, &release, &matches))?
+        );
+        return Ok(());
+    }
+    println!("Repository: {}", repo);
+    println!("Release:    {}", release.tag_name);
+    if let Some(name) = release.name.as_deref().filter(|n| !n.is_empty()) {
+        println!("Name:       {}", name);
+    }
+    if let Some(published_at) = &release.published_at {
+        println!("Published:  {}", published_at);
+    }
+    if let Some(author) = &release.author {
+        println!("Author:     {}", author.login);
+    }
+    if release.prerelease {
+        println!("Prerelease: yes");
+    }
+    if let Some(url) = &release.html_url {
+        println!("URL:        {}", url);
+    }
+
+    println!(
+        "Assets:     {} ({} matching {}-{}, marked with *)",
+        release.assets.len(),
+        matches.len(),
+        opts.os,
+        opts.arch
+    );
+    println!();
+    let rows: Vec<Vec<String>> = release
+        .assets
+        .iter()
+        .map(|asset| {
+            let marker = if matches.iter().any(|m| m.name == asset.name) {
+                "*"
+            } else {
+                ""
+            };
+            vec![
+                marker.to_string(),
+                asset.name.clone(),
+                format_size(asset.size, opts.si),
+                asset.download_count.to_string(),
+                asset.digest.clone().unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+    print_table(&["", "NAME", "SIZE", "DOWNLOADS", "DIGEST"], &rows);
+    Ok(())
+}
@@ -0,0 +1,995 @@
+//! Installing a release asset: download, verification, extraction and placement of the files
+
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{Context, Result, anyhow};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use ureq::Agent;
+
+use crate::{
+    InstallArgs, Layout, alias, binfmt,
+    channel::Channel,
+    config::Config,
+    download::{Compression, DownloadSource, decompress, download_asset},
+    extract, gha, guard, hooks, host, local, lockfile, minisign, notes, pgp,
+    plan::{ExtractPlan, Selection, is_wildcard},
+    release::{Asset, Release, is_document, media_type},
+    release_files,
+    resolve::{ReleaseFilter, list_releases, locked_asset, resolve_release},
+    select::{Prefer, is_appimage, print_platforms, resolve_platform, select_asset},
+    source,
+    state::{self, Receipt, Scope, State},
+    timings, user_path, verify, version, versions, wrapper,
+};
+
+pub const DEFAULT_MEMORY_LIMIT: u64 = 100 * 1024 * 1024;
+
+/// Install a tool as asked on the command line and record it in the scope's state
+pub fn install_command(agent: &Agent, args: InstallArgs) -> Result<()> {
+    if args.list_platforms {
+        print_platforms();
+        return Ok(());
+    }
+    // Only a local file can be installed without knowing its repository
+    let repo = args.repo.as_deref().map(alias::resolve).transpose()?;
+    let github_repo = || {
+        repo.as_deref()
+            .ok_or_else(|| anyhow!("Repository is required"))
+    };
+    if args.list {
+        return list_releases(agent, github_repo()?, &ReleaseFilter::default(), false);
+    }
+    if args.source {
+        source::fetch(
+            agent,
+            github_repo()?,
+            args.tag.as_deref(),
+            args.destination.as_deref().unwrap_or(Path::new(".")),
+            args.extract_dir.as_deref(),
+            args.si,
+            &Config::load()?.extractors,
+        )?;
+        return Ok(());
+    }
+
+    let (os, arch) = resolve_platform(args.os.as_deref(), args.arch.as_deref())?;
+    if args.os.is_none() && args.arch.is_none() {
+        eprintln!("Detected platform: {}-{}", os, arch);
+    } else {
+        eprintln!("Using platform: {}-{}", os, arch);
+    }
+
+    // An explicit key wins over the one configured for the repository
+    let config = Config::load()?;
+    let minisign_key = args.minisign_key.or_else(|| {
+        repo.as_ref()
+            .and_then(|repo| config.minisign_keys.get(repo))
+            .cloned()
+    });
+    let gpg_key = args.gpg_key.or_else(|| {
+        repo.as_ref()
+            .and_then(|repo| config.gpg_keys.get(repo))
+            .cloned()
+    });
+
+    let checksum = args
+        .checksum
+        .as_deref()
+        .map(verify::parse_checksum)
+        .transpose()?;
+
+    let ownership = args
+        .dest_ownership
+        .as_deref()
+        .map(parse_ownership)
+        .transpose()?;
+
+    // Reproducible installs pin every timestamp, falling back to the Unix epoch
+    let mtime = args
+        .source_date_epoch
+        .or(args.reproducible.then_some(0))
+        .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+
+    let scope = args.scope.unwrap_or_default();
+    let destination = match (args.destination, args.scope) {
+        (Some(dir), _) => dir,
+        (None, Some(scope)) => scope.bin_dir()?,
+        (None, None) if args.install => state::path_bin_dir()?,
+        (None, None) => PathBuf::from("."),
+    };
+    if scope == Scope::System {
+        state::ensure_writable(&destination)?;
+        state::ensure_writable(&scope.data_dir()?)?;
+    }
+    if args.add_to_path && !cfg!(windows) {
+        return Err(anyhow!(
+            "--add-to-path is only supported on Windows; add {:?} to PATH in your shell profile",
+            destination
+        ));
+    }
+
+    let version_req = args
+        .tag
+        .as_deref()
+        .map(version::parse_range)
+        .transpose()?
+        .flatten();
+    let tag_pattern = args
+        .tag_pattern
+        .as_deref()
+        .map(version::parse_tag_pattern)
+        .transpose()?;
+    let (tag, locked) = if args.locked {
+        let lockfile = lockfile::Lockfile::load(&args.lockfile)?;
+        let (tag, asset) = lockfile.pin(github_repo()?, args.bin_name.as_deref(), &os, &arch)?;
+        let allowed = match (&version_req, &tag_pattern) {
+            (Some(req), _) => version::parse_tag(&tag).is_some_and(|v| req.matches(&v)),
+            (None, Some(pattern)) => pattern.is_match(&tag),
+            (None, None) => args.tag.as_ref().is_none_or(|t| *t == tag),
+        };
+        if !allowed {
+            return Err(anyhow!(
+                "--tag {} differs from {} locked in {:?}",
+                args.tag.or(args.tag_pattern).unwrap_or_default(),
+                tag,
+                args.lockfile
+            ));
+        }
+        (Some(tag), Some(asset))
+    } else {
+        (args.tag.filter(|_| version_req.is_none()), None)
+    };
+
+    let mut opts = InstallOptions {
+        tag,
+        version_req,
+        tag_pattern,
+        prerelease: args.prerelease,
+        channel: args.channel,
+        latest_by_version: args.latest_by_version,
+        os,
+        arch,
+        first: args.first,
+        prompt_timeout: args.prompt_timeout,
+        fallback_previous: args.fallback_previous,
+        notes: args.notes || args.confirm,
+        confirm: args.confirm,
+        exclude: args.exclude,
+        asset_pattern: None,
+        bin_name: args.bin_name,
+        destination,
+        no_decompress: args.no_decompress,
+        extract_all: args.extract_all,
+        extract_dir: args.extract_dir,
+        strip_components: args.strip_components,
+        member: args.member,
+        bin_pattern: args.bin_pattern,
+        bins: args.bins,
+        strict_libc: args.strict_libc,
+        strict_format: args.strict_format,
+        prefer: args.prefer,
+        previous_asset: None,
+        locked,
+        checksum,
+        checksum_file: args.checksum_file,
+        checksum_asset: None,
+        layout: args.layout,
+        extractors: config.extractors.clone(),
+        minisign_key,
+        gpg_key,
+        require_signature: args.require_signature,
+        allow_unsigned: args.allow_unsigned,
+        si: args.si,
+        memory_limit: args.memory_limit,
+        cache: !args.no_cache,
+        chmod_dirs: !args.no_chmod_dirs,
+        keep_quarantine: args.keep_quarantine,
+        paranoid: args.paranoid || config.paranoid,
+        ownership,
+        mtime,
+        normalize_modes: args.reproducible,
+        hooks: hooks::ToolHooks::default(),
+        hold: false,
+        scope,
+    };
+    if let Some(repo) = &repo {
+        config.repo(repo).apply(&mut opts)?;
+    }
+    if args.all_assets {
+        return release_files::download_all(
+            agent,
+            github_repo()?,
+            &opts,
+            args.asset_filter.as_ref(),
+        );
+    }
+    // An alias names the executable, e.g. `rg` for BurntSushi/ripgrep
+    if opts.bin_name.is_none() && args.repo != repo {
+        opts.bin_name = args.repo;
+    }
+    if repo.is_none() && args.json {
+        return Err(anyhow!(
+            "--json reports a recorded install; pass the repository along with --from-file"
+        ));
+    }
+    let mut installed = match &args.from_file {
+        Some(path) => local::install(path, repo.as_deref(), &opts)?,
+        None => install(agent, github_repo()?, &opts)?,
+    };
+    installed.files = wrapper::wrap(installed.files, &installed.tag, &config.wrappers, scope)?;
+    let Some(repo) = repo else {
+        eprintln!(
+            "Successfully installed '{}' to {:?} (not recorded: no repository given)",
+            installed.bin_name, opts.destination
+        );
+        if args.add_to_path && !state::on_path(&opts.destination) {
+            user_path::add(&opts.destination)?;
+        }
+        if args.print_digest {
+            print_digests(&installed)?;
+        }
+        return Ok(());
+    };
+
+    let started = Instant::now();
+    let mut state = State::load(scope)?;
+    state.record(&installed.bin_name, receipt_for(&repo, &installed, &opts)?);
+    state.save()?;
+    timings::record("install", started, 0);
+    post_install(&config, &state.tools[&installed.bin_name])?;
+
+    eprintln!(
+        "Successfully installed '{}' to {:?}",
+        installed.bin_name, opts.destination
+    );
+    gha::notice(&format!(
+        "Installed {} {} ({}) to {}",
+        installed.bin_name,
+        installed.tag,
+        installed.asset.name,
+        opts.destination.display()
+    ));
+    if args.add_to_path && !state::on_path(&opts.destination) {
+        user_path::add(&opts.destination)?;
+    }
+    if args.print_digest {
+        print_digests(&installed)?;
+    }
+    if args.json {
+        let receipt = &state.tools[&installed.bin_name];
+        let report = InstallReport {
+            name: &installed.bin_name,
+            repo: &receipt.repo,
+            tag: &receipt.tag,
+            asset: &receipt.asset,
+            url: &receipt.url,
+            sha256: &receipt.sha256,
+            destination: &receipt.destination,
+            files: &receipt.files,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+    Ok(())
+}
+
+/// Output of `grd install --json`; its schema is in the schema module
+#[derive(Serialize)]
+struct InstallReport<'a> {
+    name: &'a str,
+    repo: &'a str,
+    tag: &'a str,
+    asset: &'a str,
+    url: &'a str,
+    sha256: &'a str,
+    destination: &'a Path,
+    files: &'a [PathBuf],
+}
+
+/// Run the post-install commands configured for the repository of a recorded install
+pub fn post_install(config: &Config, receipt: &Receipt) -> Result<()> {
+    let commands = config.repo(&receipt.repo).post_install;
+    if commands.is_empty() {
+        return Ok(());
+    }
+    let env = [
+        ("GRD_REPO", receipt.repo.clone()),
+        ("GRD_TAG", receipt.tag.clone()),
+        ("GRD_BIN_NAME", receipt.bin_name.clone()),
+        ("GRD_DESTINATION", receipt.destination.display().to_string()),
+    ];
+    hooks::run(&commands, &env, &receipt.destination)
+        .with_context(|| format!("Post-install step of {} failed", receipt.repo))
+}
+
+/// Print SHA-256 digests of the asset and of every installed file, in `sha256sum` format
+fn print_digests(installed: &Installed) -> Result<()> {
+    println!("{}  {}", installed.sha256, installed.asset.name);
+    for path in &installed.files {
+        // Directories and symlinks have no content of their own
+        if fs::symlink_metadata(path)?.is_file() {
+            println!("{}  {}", file_sha256(path)?, path.display());
+        }
+    }
+    Ok(())
+}
+
+pub fn file_sha256(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// SHA-256 of each regular file among `paths`, keyed by absolute path
+pub fn file_digests(paths: &[PathBuf]) -> Result<BTreeMap<PathBuf, String>> {
+    paths
+        .iter()
+        .filter(|path| fs::symlink_metadata(path).is_ok_and(|m| m.is_file()))
+        .map(|path| Ok((std::path::absolute(path)?, file_sha256(path)?)))
+        .collect()
+}
+
+/// Settings for installing one executable from a release
+pub struct InstallOptions {
+    pub tag: Option<String>,
+    /// Semantic version requirement picking the newest matching release instead of the latest
+    pub version_req: Option<semver::VersionReq>,
+    /// Channel the latest release is taken from, when no tag is given
+    pub channel: Option<Channel>,
+    /// Take the highest semantic version of the channel as the latest release
+    pub latest_by_version: bool,
+    /// Regular expression picking the most recently published release whose tag it matches
+    pub tag_pattern: Option<regex_lite::Regex>,
+    pub prerelease: bool,
+    pub os: String,
+    pub arch: String,
+    pub first: bool,
+    pub prompt_timeout: Option<Duration>,
+    pub fallback_previous: usize,
+    /// Print the release notes of the selected release
+    pub notes: bool,
+    /// Ask before installing the selected release
+    pub confirm: bool,
+    pub exclude: Option<String>,
+    /// Glob selecting assets by name instead of the OS/architecture heuristics
+    pub asset_pattern: Option<String>,
+    pub bin_name: Option<String>,
+    pub destination: PathBuf,
+    pub no_decompress: bool,
+    pub extract_all: bool,
+    pub extract_dir: Option<PathBuf>,
+    pub strip_components: usize,
+    pub member: Option<String>,
+    pub bin_pattern: Option<String>,
+    pub bins: Vec<String>,
+    pub strict_libc: bool,
+    pub strict_format: bool,
+    pub prefer: Option<Prefer>,
+    /// Asset of the installed version, whose counterpart is picked when updating
+    pub previous_asset: Option<String>,
+    /// Asset pinned by a lockfile, installed instead of selecting one
+    pub locked: Option<lockfile::LockedAsset>,
+    pub checksum: Option<verify::ExpectedDigest>,
+    pub checksum_file: Option<PathBuf>,
+    /// Glob naming the release asset that lists the asset's digest (`{asset}` is its name)
+    pub checksum_asset: Option<String>,
+    pub layout: Layout,
+    /// Extraction backend per archive format, from the config file
+    pub extractors: BTreeMap<String, String>,
+    pub minisign_key: Option<String>,
+    pub gpg_key: Option<PathBuf>,
+    pub require_signature: bool,
+    pub allow_unsigned: bool,
+    pub si: bool,
+    pub memory_limit: u64,
+    /// Reuse and keep downloads in the asset cache
+    pub cache: bool,
+    pub chmod_dirs: bool,
+    pub keep_quarantine: bool,
+    /// Refuse destinations other users can tamper with, instead of warning
+    pub paranoid: bool,
+    pub ownership: Option<(u32, u32)>,
+    pub mtime: Option<SystemTime>,
+    /// Set modes of installed files to 0755 (any execute bit) or 0644, and of directories to 0755
+    pub normalize_modes: bool,
+    /// Hooks of the manifest entry, recorded with the install
+    pub hooks: hooks::ToolHooks,
+    /// Record the tool as held, so that `update --all` leaves it at this version
+    pub hold: bool,
+    /// Scope whose data directory holds versioned installs
+    pub scope: Scope,
+}
+
+impl Default for InstallOptions {
+    fn default() -> Self {
+        Self {
+            tag: None,
+            version_req: None,
+            tag_pattern: None,
+            channel: None,
+            latest_by_version: false,
+            prerelease: false,
+            os: env::consts::OS.to_string(),
+            arch: env::consts::ARCH.to_string(),
+            first: false,
+            prompt_timeout: None,
+            fallback_previous: 0,
+            notes: false,
+            confirm: false,
+            exclude: None,
+            asset_pattern: None,
+            bin_name: None,
+            destination: PathBuf::from("."),
+            no_decompress: false,
+            extract_all: false,
+            extract_dir: None,
+            strip_components: 0,
+            member: None,
+            bin_pattern: None,
+            bins: Vec::new(),
+            strict_libc: false,
+            strict_format: false,
+            prefer: None,
+            previous_asset: None,
+            locked: None,
+            checksum: None,
+            checksum_file: None,
+            checksum_asset: None,
+            layout: Layout::Flat,
+            extractors: BTreeMap::new(),
+            minisign_key: None,
+            gpg_key: None,
+            require_signature: false,
+            allow_unsigned: false,
+            si: false,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            cache: true,
+            chmod_dirs: true,
+            keep_quarantine: false,
+            paranoid: false,
+            ownership: None,
+            mtime: None,
+            normalize_modes: false,
+            hooks: hooks::ToolHooks::default(),
+            hold: false,
+            scope: Scope::User,
+        }
+    }
+}
+
+/// Result of a successful install
+pub struct Installed {
+    pub tag: String,
+    /// Publication time of the release, unknown for local files
+    pub published_at: Option<String>,
+    pub asset: Asset,
+    pub bin_name: String,
+    /// Files and directories written by the install
+    pub files: Vec<PathBuf>,
+    /// SHA-256 of the downloaded asset
+    pub sha256: String,
+}
+
+/// Resolve a release, select the matching asset, then download and place the executable
+pub fn install(agent: &Agent, repo: &str, opts: &InstallOptions) -> Result<Installed> {
+    // 1. Fetch release info (specific tag or latest)
+    let started = Instant::now();
+    let release = resolve_release(agent, repo, opts)?;
+    timings::record("resolve", started, 0);
+    if release.prerelease {
+        eprintln!("Selected version: {} (prerelease)", release.tag_name);
+    } else {
+        eprintln!("Selected version: {}", release.tag_name);
+    }
+    if opts.notes {
+        notes::print(&release);
+    }
+    if opts.confirm && !notes::confirm(&format!("{} {}", repo, release.tag_name))? {
+        return Err(anyhow!("Installation of {} cancelled", repo));
+    }
+
+    // 2. Select the asset best matching the host or explicit platform, unless one is pinned
+    let asset = match &opts.locked {
+        Some(locked) => locked_asset(&release, locked)?,
+        None => select_asset(&release.assets, opts)?,
+    };
+    eprintln!("Selected asset: {}", asset.name);
+    if opts.os == "linux" && env::consts::OS == "linux" {
+        host::check_libc(&asset.name, opts.strict_libc)?;
+    }
+
+    // 3. Download and place the binary
+    let bin_name = opts
+        .bin_name
+        .clone()
+        .unwrap_or_else(|| default_bin_name(repo, &asset.name));
+    let started = Instant::now();
+    let expected = expectations(agent, &release, &asset, opts)?;
+    timings::record("verify", started, 0);
+    let fetch = || download_asset(agent, &asset, opts);
+    let (files, sha256) =
+        install_asset(fetch, &asset, &bin_name, &release.tag_name, opts, &expected)?;
+    let bin_name = installed_bin_name(bin_name, &files);
+
+    Ok(Installed {
+        tag: release.tag_name,
+        published_at: release.published_at,
+        asset,
+        bin_name,
+        files,
+        sha256,
+    })
+}
+
+/// Executable name of a tool from `repo` installed without --bin-name
+pub fn default_bin_name(repo: &str, asset_name: &str) -> String {
+    let name = repo.split('/').next_back().unwrap_or("app");
+    // Keep the extension so desktop integrations (e.g. appimaged) recognize the file
+    if is_appimage(asset_name) {
+        format!("{}.AppImage", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// The name an install is recorded under: a wildcard name is replaced by the normalized name of
+/// the file it matched
+pub fn installed_bin_name(bin_name: String, files: &[PathBuf]) -> String {
+    match files.last().and_then(|f| f.file_name()) {
+        Some(installed) if is_wildcard(&bin_name) => {
+            let installed = installed.to_string_lossy();
+            installed
+                .strip_suffix(".exe")
+                .unwrap_or(&installed)
+                .to_string()
+        }
+        _ => bin_name,
+    }
+}
+
+/// What the download of `asset` has to match: the digests given, locked and published for it,
+/// and the signatures made with the configured keys
+pub fn expectations(
+    agent: &Agent,
+    release: &Release,
+    asset: &Asset,
+    opts: &InstallOptions,
+) -> Result<verify::Expectations> {
+    let mut expected = verify::Expectations::default();
+    expected.digests.extend(opts.checksum.iter().cloned());
+    if let Some(locked) = &opts.locked {
+        expected
+            .digests
+            .push(verify::ExpectedDigest::new(&locked.sha256, "the lockfile"));
+    }
+    if let Some(path) = &opts.checksum_file {
+        expected
+            .digests
+            .push(verify::digest_from_file(path, &asset.name)?);
+    }
+    if let Some(pattern) = &opts.checksum_asset {
+        expected.digests.push(verify::digest_from_release(
+            agent,
+            &release.assets,
+            asset,
+            pattern,
+        )?);
+    }
+    expected.digests.extend(verify::api_digest(asset));
+    expected
+        .digests
+        .extend(verify::published_digest(agent, &release.assets, asset)?);
+    if let Some(key) = &opts.minisign_key {
+        expected.minisign = minisign::check(agent, &release.assets, asset, key)?;
+    }
+    if let Some(key) = &opts.gpg_key {
+        expected.gpg = pgp::check(agent, &release.assets, asset, key)?;
+    }
+    if expected.minisign.is_none() && expected.gpg.is_none() {
+        if opts.require_signature {
+            return Err(anyhow!(
+                "No signature of {} can be verified (--require-signature)",
+                asset.name
+            ));
+        }
+        if opts.minisign_key.is_some() || opts.gpg_key.is_some() {
+            // A pinned key means a signature is expected; a missing one may be an attack
+            if !opts.allow_unsigned {
+                return Err(anyhow!(
+                    "The release publishes no signature of {} for the configured key; pass --allow-unsigned to install it unverified",
+                    asset.name
+                ));
+            }
+            gha::warning(&format!(
+                "The release publishes no signature of {}; installing it unverified",
+                asset.name
+            ));
+        }
+    }
+    Ok(expected)
+}
+
+/// Fetch an asset with `fetch` and place its contents, returning the written paths and the
+/// asset's SHA-256
+pub fn install_asset(
+    fetch: impl FnOnce() -> Result<DownloadSource>,
+    asset: &Asset,
+    bin_name: &str,
+    tag: &str,
+    opts: &InstallOptions,
+    expected: &verify::Expectations,
+) -> Result<(Vec<PathBuf>, String)> {
+    guard::check_destination(&opts.destination, opts.paranoid)?;
+    // A version that is already present is only selected again
+    let version_dir = match opts.layout {
+        Layout::Versioned if is_wildcard(bin_name) => {
+            return Err(anyhow!(
+                "--layout versioned needs a --bin-name without wildcards, to name the tool's directory"
+            ));
+        }
+        Layout::Versioned => Some(versions::version_dir(opts.scope, bin_name, tag)?),
+        _ => None,
+    };
+    if let Some(dir) = &version_dir
+        && let Some((files, sha256)) = versions::reusable(dir, &asset.name, expected)
+    {
+        eprintln!("Using {} already present in {:?}", tag, dir);
+        let links = versions::activate(dir, &opts.destination, &files)?;
+        return Ok(([files, links].concat(), sha256));
+    }
+    let started = Instant::now();
+    let source = fetch()?;
+    timings::record("download", started, asset.size);
+
+    let started = Instant::now();
+    let sha256 = source.sha256()?;
+    expected.check(&asset.name, &source, &sha256)?;
+    timings::record("verify", started, asset.size);
+
+    // The FHS layout places executables in bin/ and companion files in share/ under the prefix
+    let exe_dir = match (&version_dir, opts.layout) {
+        (Some(dir), _) => dir.clone(),
+        (None, Layout::Fhs) if !opts.extract_all => opts.destination.join("bin"),
+        _ => opts.destination.clone(),
+    };
+    let created_dirs = create_dest_dir(&exe_dir)?;
+    let plan = if opts.extract_all {
+        let dir = match &opts.extract_dir {
+            Some(subdir) => opts.destination.join(subdir),
+            None => opts.destination.clone(),
+        };
+        ExtractPlan::new(dir, bin_name, Selection::All).strip_components(opts.strip_components)
+    } else if let Some(member) = &opts.member {
+        let pattern = glob::Pattern::new(member)
+            .map_err(|e| anyhow!("Invalid --member pattern '{}': {}", member, e))?;
+        ExtractPlan::new(exe_dir.clone(), bin_name, Selection::Member(pattern))
+    } else if opts.bins == ["all"] {
+        ExtractPlan::new(exe_dir.clone(), bin_name, Selection::Executables)
+    } else if !opts.bins.is_empty() {
+        let names = opts
+            .bins
+            .iter()
+            .map(|name| {
+                if cfg!(windows) && !name.ends_with(".exe") {
+                    format!("{}.exe", name)
+                } else {
+                    name.clone()
+                }
+            })
+            .collect();
+        ExtractPlan::new(exe_dir.clone(), bin_name, Selection::Bins(names))
+    } else if is_wildcard(bin_name) {
+        let pattern = glob::Pattern::new(bin_name)
+            .map_err(|e| anyhow!("Invalid --bin-name pattern '{}': {}", bin_name, e))?;
+        ExtractPlan::new(exe_dir.clone(), bin_name, Selection::Wildcard(pattern))
+    } else if let Some(bin_pattern) = &opts.bin_pattern {
+        let pattern = glob::Pattern::new(bin_pattern)
+            .map_err(|e| anyhow!("Invalid --bin-pattern '{}': {}", bin_pattern, e))?;
+        ExtractPlan::new(exe_dir.clone(), bin_name, Selection::Pattern(pattern))
+    } else {
+        ExtractPlan::new(exe_dir.clone(), bin_name, Selection::Binary)
+    };
+    let plan = match opts.layout {
+        Layout::Fhs if !opts.extract_all => plan.companions(opts.destination.join("share")),
+        _ => plan,
+    };
+    let started = Instant::now();
+    let files = extract_and_save(
+        source,
+        &asset.name,
+        &plan,
+        asset.content_type.as_deref(),
+        opts.no_decompress,
+        &opts.extractors,
+    )?;
+    timings::record("extract", started, asset.size);
+
+    // Asset names can mislead the selection; the executable headers don't
+    let started = Instant::now();
+    if plan.is_executable_selection() && !opts.no_decompress {
+        for file in files.iter().filter(|f| !plan.is_companion(f)) {
+            if let Err(e) = binfmt::check_format(file, &opts.os, &opts.arch, opts.strict_format) {
+                for file in &files {
+                    let _ = fs::remove_file(file);
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    finalize_install(&created_dirs, &files, opts)?;
+    if cfg!(target_os = "macos") && !opts.keep_quarantine {
+        clear_quarantine(&files);
+    }
+    let files = match &version_dir {
+        Some(dir) => {
+            versions::record(dir, asset, &sha256, &files)?;
+            let links = versions::activate(dir, &opts.destination, &files)?;
+            [files, links].concat()
+        }
+        None => files,
+    };
+    timings::record("install", started, 0);
+    Ok((files, sha256))
+}
+
+/// Build the receipt recorded in the state file for an install
+pub fn receipt_for(repo: &str, installed: &Installed, opts: &InstallOptions) -> Result<Receipt> {
+    Ok(Receipt {
+        repo: repo.to_string(),
+        tag: installed.tag.clone(),
+        published_at: installed.published_at.clone(),
+        asset: installed.asset.name.clone(),
+        url: installed.asset.browser_download_url.clone(),
+        size: installed.asset.size,
+        sha256: installed.sha256.clone(),
+        bin_name: installed.bin_name.clone(),
+        destination: std::path::absolute(&opts.destination)?,
+        extract_all: opts.extract_all,
+        extract_dir: opts.extract_dir.clone(),
+        strip_components: opts.strip_components,
+        member: opts.member.clone(),
+        // Reinstalling under the normalized name keeps matching the recorded wildcard
+        bin_pattern: opts
+            .bin_pattern
+            .clone()
+            .or_else(|| opts.bin_name.clone().filter(|name| is_wildcard(name))),
+        bins: opts.bins.clone(),
+        layout: opts.layout,
+        // --prerelease stands for the channel of every prerelease
+        channel: opts.channel.or(opts.prerelease.then_some(Channel::Pre)),
+        os: Some(opts.os.clone()),
+        arch: Some(opts.arch.clone()),
+        exclude: opts.exclude.clone(),
+        asset_pattern: opts.asset_pattern.clone(),
+        prefer: opts.prefer,
+        first: opts.first,
+        checksum_asset: opts.checksum_asset.clone(),
+        ownership: opts.ownership,
+        no_chmod_dirs: !opts.chmod_dirs,
+        normalize_modes: opts.normalize_modes,
+        files: installed
+            .files
+            .iter()
+            .map(std::path::absolute)
+            .collect::<io::Result<_>>()?,
+        digests: file_digests(&installed.files)?,
+        hooks: opts.hooks.clone(),
+        hold: opts.hold,
+        installed_at: state::now(),
+        previous: None,
+    })
+}
+
+fn parse_ownership(input: &str) -> Result<(u32, u32)> {
+    let (uid, gid) = input
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Invalid ownership '{}'. Expected UID:GID", input))?;
+    let parse = |s: &str| {
+        s.trim()
+            .parse::<u32>()
+            .map_err(|_| anyhow!("Invalid ownership '{}'. Expected numeric UID:GID", input))
+    };
+    Ok((parse(uid)?, parse(gid)?))
+}
+
+pub fn extract_and_save(
+    source: DownloadSource,
+    filename: &str,
+    plan: &ExtractPlan,
+    content_type: Option<&str>,
+    no_decompress: bool,
+    extractors: &BTreeMap<String, String>,
+) -> Result<Vec<PathBuf>> {
+    let dest_dir = plan.dest_dir.as_path();
+    if no_decompress {
+        // Save using the original asset name (do not rename or extract)
+        let out_path = save_raw(source, filename, dest_dir)?;
+        eprintln!("Saved raw asset to {:?}", out_path);
+        return Ok(vec![out_path]);
+    }
+
+    // AppImages are self-mounting executables and must not be unpacked
+    if is_appimage(filename) {
+        let name = match plan.selection {
+            Selection::Wildcard(_) => format!("{}.AppImage", plan.raw_name(filename)?),
+            _ => plan.bin_name.clone(),
+        };
+        return Ok(vec![save_raw(source, &name, dest_dir)?]);
+    }
+
+    // The file name decides; the declared content type only helps when the name says nothing.
+    // It describes the download, so it no longer applies once that is decompressed.
+    let media_type = content_type.map(media_type);
+    let (source, filename, media_type) = match Compression::detect(filename) {
+        Some((compression, inner_name)) => (decompress(source, compression)?, inner_name, None),
+        None => match Compression::from_content_type(media_type.as_deref().unwrap_or(""), &source)?
+        {
+            Some(compression) => (decompress(source, compression)?, filename.to_string(), None),
+            None => (source, filename.to_string(), media_type),
+        },
+    };
+
+    let installed = match extract::Format::detect(&filename, media_type.as_deref(), &source)? {
+        Some(format) => {
+            extract::extractor_for(format, extractors)?.extract(format, source, plan)?
+        }
+        // A plain or single-file compressed executable
+        None => {
+            if let Some(media_type) = media_type.as_deref().filter(|t| is_document(t)) {
+                return Err(anyhow!(
+                    "{} is uploaded as {}, not an executable or archive; pick another asset",
+                    filename,
+                    media_type
+                ));
+            }
+            vec![save_raw(source, &plan.raw_name(&filename)?, dest_dir)?]
+        }
+    };
+
+    if matches!(plan.selection, Selection::All) {
+        eprintln!("Extracted {} entries to {:?}", installed.len(), dest_dir);
+    }
+    Ok(installed)
+}
+
+/// Create the parent directories of `path`, returning the ones that were created
+pub fn create_parent_dirs(path: &Path) -> Result<Vec<PathBuf>> {
+    match path.parent() {
+        Some(parent) => create_dest_dir(parent),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Remove the `com.apple.quarantine` attribute so Gatekeeper doesn't block the installed tools
+fn clear_quarantine(paths: &[PathBuf]) {
+    use std::process::{Command, Stdio};
+
+    if paths.is_empty() {
+        return;
+    }
+    // xattr complains about files without the attribute, which is the usual case
+    let _ = Command::new("xattr")
+        .args(["-d", "com.apple.quarantine"])
+        .args(paths)
+        .stderr(Stdio::null())
+        .status();
+}
+
+fn save_raw(source: DownloadSource, target_bin_name: &str, dest_dir: &Path) -> Result<PathBuf> {
+    let out_path = dest_dir.join(target_bin_name);
+    match source {
+        DownloadSource::Memory(bytes) => {
+            fs::write(&out_path, bytes)?;
+        }
+        DownloadSource::Disk(temp_file) => {
+            fs::copy(temp_file.path(), &out_path)?;
+        }
+    }
+    #[cfg(unix)]
+    set_permissions(&out_path)?;
+    Ok(out_path)
+}
+
+/// Create `dir` and any missing parents, returning the directories that were created
+pub fn create_dest_dir(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut missing = Vec::new();
+    let mut current = Some(dir);
+    while let Some(path) = current {
+        if path.as_os_str().is_empty() || path.exists() {
+            break;
+        }
+        missing.push(path.to_path_buf());
+        current = path.parent();
+    }
+    fs::create_dir_all(dir)?;
+    missing.reverse();
+    Ok(missing)
+}
+
+/// Apply directory permissions, ownership and timestamps (useful in container image builds)
+fn finalize_install(
+    created_dirs: &[PathBuf],
+    installed: &[PathBuf],
+    opts: &InstallOptions,
+) -> Result<()> {
+    let (ownership, mtime) = (opts.ownership, opts.mtime);
+    #[cfg(unix)]
+    {
+        if opts.chmod_dirs {
+            for dir in created_dirs {
+                set_permissions(dir)?;
+            }
+        }
+        if opts.normalize_modes {
+            for path in installed {
+                normalize_mode(path)?;
+            }
+        }
+        if let Some((uid, gid)) = ownership {
+            for path in created_dirs.iter().chain(installed) {
+                std::os::unix::fs::lchown(path, Some(uid), Some(gid))?;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        if ownership.is_some() {
+            return Err(anyhow!("--dest-ownership is only supported on Unix"));
+        }
+    }
+
+    if let Some(mtime) = mtime {
+        // Deepest paths first, so that touching entries doesn't disturb their parents afterwards
+        for path in installed.iter().rev().chain(created_dirs.iter().rev()) {
+            let file_type = fs::symlink_metadata(path)?.file_type();
+            if file_type.is_file() {
+                File::options()
+                    .write(true)
+                    .open(path)?
+                    .set_modified(mtime)?;
+            } else if cfg!(unix) && file_type.is_dir() {
+                // Directories can only be opened for timestamp updates on Unix
+                File::open(path)?.set_modified(mtime)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Set the mode of an installed file to 0755 when any execute bit is set and 0644 otherwise, so
+/// that neither the archive nor the umask decide it; directories get 0755, symlinks are left as is
+#[cfg(unix)]
+fn normalize_mode(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let metadata = fs::symlink_metadata(path)?;
+    let mode = if metadata.is_dir() {
+        0o755
+    } else if !metadata.is_file() {
+        return Ok(());
+    } else if metadata.permissions().mode() & 0o111 != 0 {
+        0o755
+    } else {
+        0o644
+    };
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn set_permissions(path: &Path) -> Result<()> {
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = fs::metadata(path)?.permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(path, perms)?;
+    }
+    Ok(())
+}
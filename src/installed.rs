@@ -0,0 +1,147 @@
+use std::{
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use anyhow::{Result, anyhow};
+
+use crate::{
+    index,
+    install::file_sha256,
+    state::{Scope, State},
+    table::print_table,
+};
+
+/// Print a table of the tools installed in `scope`, from the index of their receipts
+pub fn list_installed(scope: Scope) -> Result<()> {
+    let entries = index::load(scope)?;
+    if entries.is_empty() {
+        eprintln!("No tools installed by grd");
+        return Ok(());
+    }
+
+    let rows: Vec<Vec<String>> = entries
+        .into_iter()
+        .map(|entry| {
+            let at = UNIX_EPOCH + Duration::from_secs(entry.installed_at);
+            vec![
+                entry.name,
+                entry.repo,
+                entry.tag,
+                entry.destination.display().to_string(),
+                humantime::format_rfc3339_seconds(at).to_string(),
+            ]
+        })
+        .collect();
+    print_table(&["NAME", "REPO", "TAG", "DESTINATION", "INSTALLED"], &rows);
+    Ok(())
+}
+
+/// Print where the tool `name` (or the tool that installed the executable `name`) is, where it
+/// came from and its digests, looking in `scope` or else in both scopes
+pub fn which(name: &str, scope: Option<Scope>) -> Result<()> {
+    let scopes = match scope {
+        Some(scope) => vec![scope],
+        None => vec![Scope::User, Scope::System],
+    };
+    let is_exe = |path: &Path| {
+        path.file_name().is_some_and(|file| {
+            file == name || (cfg!(windows) && file.to_string_lossy() == format!("{}.exe", name))
+        })
+    };
+    for scope in scopes {
+        let entries = index::load(scope)?;
+        let Some(entry) = entries.iter().find(|e| e.name == name).or_else(|| {
+            entries
+                .iter()
+                .find(|e| e.files.iter().any(|(f, _)| is_exe(f)))
+        }) else {
+            continue;
+        };
+        // The executable asked for, else the one named like the tool, else the first file written
+        let (path, digest) = entry
+            .files
+            .iter()
+            .find(|(f, _)| is_exe(f))
+            .or_else(|| {
+                entry.files.iter().find(|(f, _)| {
+                    f.file_stem()
+                        .is_some_and(|stem| *stem == *entry.bin_name.as_str())
+                })
+            })
+            .or_else(|| entry.files.iter().find(|(_, digest)| digest.is_some()))
+            .map(|(f, digest)| (f.clone(), digest.as_ref()))
+            .unwrap_or_else(|| (entry.destination.join(&entry.bin_name), None));
+        let at = UNIX_EPOCH + Duration::from_secs(entry.installed_at);
+        println!("{}", path.display());
+        println!("  tool:       {}", entry.name);
+        let scope = match scope {
+            Scope::User => "user",
+            Scope::System => "system",
+        };
+        println!("  scope:      {}", scope);
+        println!("  repo:       {}", entry.repo);
+        println!("  tag:        {}", entry.tag);
+        println!("  asset:      {} (sha256 {})", entry.asset, entry.sha256);
+        if let Some(digest) = digest {
+            println!("  sha256:     {}", digest);
+        }
+        println!("  installed:  {}", humantime::format_rfc3339_seconds(at));
+        return Ok(());
+    }
+    Err(anyhow!("'{}' is not installed by grd", name))
+}
+
+/// Check the files installed for `name` (or every tool) against the digests in their receipts
+pub fn verify_installed(name: Option<&str>, scope: Scope) -> Result<()> {
+    let state = State::load(scope)?;
+    let names: Vec<&String> = match name {
+        Some(name) => vec![
+            state
+                .tools
+                .get_key_value(name)
+                .ok_or_else(|| anyhow!("'{}' is not installed by grd", name))?
+                .0,
+        ],
+        None => state.tools.keys().collect(),
+    };
+    if names.is_empty() {
+        eprintln!("No tools installed by grd");
+        return Ok(());
+    }
+
+    let mut problems = 0;
+    for name in names {
+        let receipt = &state.tools[name];
+        if receipt.digests.is_empty() {
+            eprintln!(
+                "{}: no file digests recorded (installed by an older grd); reinstall it to record them",
+                name
+            );
+            continue;
+        }
+        let mut changed = 0;
+        for (path, expected) in &receipt.digests {
+            let status = match file_sha256(path) {
+                Ok(actual) if actual == *expected => continue,
+                Ok(_) => "modified",
+                Err(_) if !path.exists() => "missing",
+                Err(_) => "unreadable",
+            };
+            println!("{:<10} {}  {}", status, name, path.display());
+            changed += 1;
+        }
+        if changed == 0 {
+            println!("{:<10} {}  {} file(s)", "ok", name, receipt.digests.len());
+        }
+        problems += changed;
+    }
+
+    if problems > 0 {
+        return Err(anyhow!(
+            "{} installed file(s) no longer match their receipts",
+            problems
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,95 @@
+//! Installs from local files, for assets obtained through other channels than GitHub
+
+use std::{env, fs, path::Path};
+
+use anyhow::{Context, Result, anyhow};
+use tempfile::NamedTempFile;
+
+use crate::{
+    download::DownloadSource,
+    host,
+    install::{InstallOptions, Installed, default_bin_name, install_asset, installed_bin_name},
+    release::Asset,
+    select::is_appimage,
+    verify,
+};
+
+/// Tag recorded for a local file installed without --tag
+pub const TAG: &str = "local";
+
+/// Select, extract and place the executable of the archive (or executable) at `path` as an
+/// install of a release asset would, without network access
+///
+/// `repo`, when known, names the executable by default; otherwise it is named after the first
+/// word of the file name (`tool` for `tool-1.0-linux-amd64.tar.gz`).
+pub fn install(path: &Path, repo: Option<&str>, opts: &InstallOptions) -> Result<Installed> {
+    let path = std::path::absolute(path)?;
+    let metadata = fs::metadata(&path).with_context(|| format!("Failed to read {:?}", path))?;
+    if !metadata.is_file() {
+        return Err(anyhow!("{:?} is not a file", path));
+    }
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{:?} has no file name", path))?
+        .to_string_lossy()
+        .to_string();
+    let asset = Asset {
+        name: name.clone(),
+        browser_download_url: format!("file://{}", path.display()),
+        size: metadata.len(),
+        digest: None,
+        content_type: None,
+        download_count: 0,
+    };
+    let tag = opts.tag.clone().unwrap_or_else(|| TAG.to_string());
+    eprintln!("Installing from {:?}", path);
+    if opts.os == "linux" && env::consts::OS == "linux" {
+        host::check_libc(&asset.name, opts.strict_libc)?;
+    }
+
+    let bin_name = opts.bin_name.clone().unwrap_or_else(|| match repo {
+        Some(repo) => default_bin_name(repo, &name),
+        None => file_bin_name(&name),
+    });
+    let mut expected = verify::Expectations::default();
+    expected.digests.extend(opts.checksum.iter().cloned());
+    if let Some(checksum_file) = &opts.checksum_file {
+        expected
+            .digests
+            .push(verify::digest_from_file(checksum_file, &name)?);
+    }
+    let fetch = || read(&path, opts.memory_limit);
+    let (files, sha256) = install_asset(fetch, &asset, &bin_name, &tag, opts, &expected)?;
+    Ok(Installed {
+        tag,
+        published_at: None,
+        bin_name: installed_bin_name(bin_name, &files),
+        asset,
+        files,
+        sha256,
+    })
+}
+
+/// The contents of `path`, in memory up to `memory_limit` bytes and in a temporary copy beyond,
+/// so that extraction can't touch the original
+pub fn read(path: &Path, memory_limit: u64) -> Result<DownloadSource> {
+    if fs::metadata(path)?.len() <= memory_limit {
+        return Ok(DownloadSource::Memory(fs::read(path)?));
+    }
+    let temp_file = NamedTempFile::new()?;
+    fs::copy(path, temp_file.path()).with_context(|| format!("Failed to copy {:?}", path))?;
+    Ok(DownloadSource::Disk(temp_file))
+}
+
+/// Executable name for a file of no known repository: the first word of its name
+fn file_bin_name(file_name: &str) -> String {
+    let word = file_name
+        .split(['-', '_', '.'])
+        .find(|word| !word.is_empty())
+        .unwrap_or("app");
+    if is_appimage(file_name) {
+        format!("{}.AppImage", word)
+    } else {
+        word.to_string()
+    }
+}
@@ -0,0 +1,312 @@
+//! Lockfiles pinning the exact release asset and digest of every manifest tool, per platform
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use ureq::Agent;
+
+use crate::{
+    config::Config,
+    download::download_asset,
+    manifest::Manifest,
+    resolve::resolve_release,
+    select::{parse_platform, resolve_platform, select_asset},
+    token, verify,
+};
+
+/// Schema version written to lockfiles
+const LOCK_VERSION: u32 = 1;
+
+/// `grd.lock`: the release and asset each tool of a manifest resolved to
+///
+/// ```toml
+/// version = 1
+///
+/// [tools.rg]
+/// repo = "BurntSushi/ripgrep"
+/// tag = "14.1.0"
+///
+/// [tools.rg.assets.linux-x86_64]
+/// name = "ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz"
+/// url = "https://github.com/BurntSushi/ripgrep/releases/download/14.1.0/..."
+/// sha256 = "..."
+/// ```
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Lockfile {
+    pub version: u32,
+    #[serde(default)]
+    pub tools: BTreeMap<String, LockedTool>,
+    /// File the lockfile was loaded from, for messages
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LockedTool {
+    pub repo: String,
+    pub tag: String,
+    /// Pinned asset for each platform, keyed by `os-arch`
+    pub assets: BTreeMap<String, LockedAsset>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LockedAsset {
+    pub name: String,
+    pub url: String,
+    pub sha256: String,
+}
+
+impl Lockfile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read lockfile {:?}", path))?;
+        let mut lockfile: Lockfile = toml::from_str(&text)
+            .with_context(|| format!("Failed to parse lockfile {:?}", path))?;
+        if lockfile.version > LOCK_VERSION {
+            return Err(anyhow!(
+                "Lockfile {:?} was written by a newer grd (schema version {})",
+                path,
+                lockfile.version
+            ));
+        }
+        lockfile.path = path.to_path_buf();
+        Ok(lockfile)
+    }
+
+    /// The tag and asset pinned for `repo` on `os-arch`; `bin_name` picks among several tools
+    /// locked from the same repository
+    pub fn pin(
+        &self,
+        repo: &str,
+        bin_name: Option<&str>,
+        os: &str,
+        arch: &str,
+    ) -> Result<(String, LockedAsset)> {
+        let candidates: Vec<(&String, &LockedTool)> = self
+            .tools
+            .iter()
+            .filter(|(name, tool)| {
+                tool.repo.eq_ignore_ascii_case(repo) && bin_name.is_none_or(|b| *name == b)
+            })
+            .collect();
+        let (name, tool) = match candidates[..] {
+            [one] => one,
+            [] => return Err(anyhow!("{} is not locked in {:?}", repo, self.path)),
+            _ => {
+                let names: Vec<&str> = candidates.iter().map(|(n, _)| n.as_str()).collect();
+                return Err(anyhow!(
+                    "Several tools of {} are locked in {:?} ({}); pick one with --bin-name",
+                    repo,
+                    self.path,
+                    names.join(", ")
+                ));
+            }
+        };
+        let platform = format!("{}-{}", os, arch);
+        let asset = tool.assets.get(&platform).ok_or_else(|| {
+            anyhow!(
+                "{:?} pins no asset of {} for {}; run `grd lock --platform {}`",
+                self.path,
+                name,
+                platform,
+                platform
+            )
+        })?;
+        Ok((tool.tag.clone(), asset.clone()))
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let text = format!(
+            "# Generated by `grd lock`; do not edit by hand\n{}",
+            toml::to_string_pretty(self)?
+        );
+        let tmp = path.with_extension("lock.tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Lockfile belonging to a manifest (`grd.toml` -> `grd.lock`, `.grd-tools` -> `.grd-tools.lock`)
+pub fn path_for(manifest: &Path) -> PathBuf {
+    manifest.with_extension("lock")
+}
+
+/// Resolve every tool of a manifest to a release asset for each platform and write its lockfile
+///
+/// Platforms already in the lockfile stay locked, in addition to `platforms`; a new lockfile
+/// without `platforms` is locked for the host. Held tools keep their locked entry.
+pub fn lock(agent: &Agent, manifest_path: &Path, platforms: &[String]) -> Result<()> {
+    let manifest = Manifest::load(manifest_path)?;
+    let config = Config::load()?;
+    token::preflight(agent, &manifest.repos())?;
+    let path = path_for(manifest_path);
+    let existing = path.exists().then(|| Lockfile::load(&path)).transpose()?;
+
+    let locked_platforms = existing
+        .iter()
+        .flat_map(|l| l.tools.values())
+        .flat_map(|t| t.assets.keys());
+    let mut platforms: BTreeSet<(String, String)> = platforms
+        .iter()
+        .chain(locked_platforms)
+        .map(|p| parse_platform(p))
+        .collect::<Result<_>>()?;
+    if platforms.is_empty() {
+        platforms.insert(resolve_platform(None, None)?);
+    }
+
+    let mut tools = BTreeMap::new();
+    for (name, spec) in &manifest.tools {
+        let previous = existing
+            .as_ref()
+            .and_then(|l| l.tools.get(name))
+            .filter(|t| t.repo == spec.repo);
+        if spec.hold
+            && let Some(previous) = previous
+        {
+            eprintln!("==> {} held at {}", name, previous.tag);
+            tools.insert(name.clone(), previous.clone());
+            continue;
+        }
+
+        let mut opts = spec.install_options(name, &config)?;
+        let release = resolve_release(agent, &spec.repo, &opts)?;
+        let mut assets = BTreeMap::new();
+        for (os, arch) in &platforms {
+            opts.os = os.clone();
+            opts.arch = arch.clone();
+            let platform = format!("{}-{}", os, arch);
+            let asset = select_asset(&release.assets, &opts)
+                .with_context(|| format!("Failed to lock {} for {}", name, platform))?;
+            // Reuse the digest of an unchanged pin instead of downloading the asset again
+            let pinned = previous
+                .filter(|p| p.tag == release.tag_name)
+                .and_then(|p| p.assets.get(&platform))
+                .filter(|a| a.url == asset.browser_download_url);
+            let sha256 = match (pinned, verify::api_digest(&asset)) {
+                (_, Some(digest)) => digest.sha256,
+                (Some(pinned), None) => pinned.sha256.clone(),
+                (None, None) => download_asset(agent, &asset, &opts)?.sha256()?,
+            };
+            assets.insert(
+                platform,
+                LockedAsset {
+                    name: asset.name,
+                    url: asset.browser_download_url,
+                    sha256,
+                },
+            );
+        }
+        eprintln!("==> {} locked at {}", name, release.tag_name);
+        tools.insert(
+            name.clone(),
+            LockedTool {
+                repo: spec.repo.clone(),
+                tag: release.tag_name,
+                assets,
+            },
+        );
+    }
+
+    let lockfile = Lockfile {
+        version: LOCK_VERSION,
+        tools,
+        path: path.clone(),
+    };
+    lockfile.save(&path)?;
+    eprintln!(
+        "Locked {} tool(s) for {} platform(s) in {:?}",
+        lockfile.tools.len(),
+        platforms.len(),
+        path
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lockfile() -> Lockfile {
+        toml::from_str(
+            r#"
+            version = 1
+
+            [tools.rg]
+            repo = "BurntSushi/ripgrep"
+            tag = "14.1.0"
+
+            [tools.rg.assets.linux-x86_64]
+            name = "ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz"
+            url = "https://example.com/rg.tar.gz"
+            sha256 = "00"
+
+            [tools.fd]
+            repo = "sharkdp/fd"
+            tag = "v10.1.0"
+            assets = {}
+
+            [tools.fdfind]
+            repo = "sharkdp/fd"
+            tag = "v9.0.0"
+
+            [tools.fdfind.assets.linux-x86_64]
+            name = "fd-v9.0.0-x86_64-unknown-linux-gnu.tar.gz"
+            url = "https://example.com/fd.tar.gz"
+            sha256 = "11"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn pins_the_tag_and_asset_of_the_platform() {
+        let (tag, asset) = lockfile()
+            .pin("burntsushi/ripgrep", None, "linux", "x86_64")
+            .unwrap();
+        assert_eq!(tag, "14.1.0");
+        assert_eq!(
+            asset.name,
+            "ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz"
+        );
+    }
+
+    #[test]
+    fn fails_for_platforms_and_repositories_not_locked() {
+        let lockfile = lockfile();
+        let err = lockfile
+            .pin("BurntSushi/ripgrep", None, "macos", "aarch64")
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("grd lock --platform macos-aarch64")
+        );
+        assert!(
+            lockfile
+                .pin("owner/other", None, "linux", "x86_64")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn tools_of_the_same_repository_are_picked_by_name() {
+        let lockfile = lockfile();
+        let err = lockfile
+            .pin("sharkdp/fd", None, "linux", "x86_64")
+            .unwrap_err();
+        assert!(err.to_string().contains("fd, fdfind"));
+        let (tag, _) = lockfile
+            .pin("sharkdp/fd", Some("fdfind"), "linux", "x86_64")
+            .unwrap();
+        assert_eq!(tag, "v9.0.0");
+    }
+}
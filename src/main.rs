@@ -1,38 +1,161 @@
-use std::{
-    env,
-    fs::{self, File},
-    io::{self, Cursor, Read, Seek, Write},
-    path::{Path, PathBuf},
-};
+use std::{env, path::PathBuf, time::Duration};
 
 use anyhow::{Result, anyhow};
-use clap::Parser;
-use flate2::read::GzDecoder;
-use indicatif::{ProgressBar, ProgressStyle};
-use serde::Deserialize;
-use tempfile::NamedTempFile;
-use ureq::Agent;
-use zip::ZipArchive;
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    channel::Channel,
+    config::Config,
+    install::{DEFAULT_MEMORY_LIMIT, InstallOptions, install_command},
+    resolve::{ReleaseFilter, list_releases, parse_date},
+    select::{Prefer, print_platforms, resolve_platform},
+    state::Scope,
+    units::parse_size,
+};
+
+mod alias;
+mod asset_cache;
+mod bench;
+mod binfmt;
+mod bundle;
+mod cache;
+mod cache_key;
+mod channel;
+mod check_assets;
+mod complete;
+mod config;
+mod config_check;
+mod config_edit;
+mod definitions;
+mod doctor;
+mod download;
+mod exec;
+mod extract;
+mod gha;
+mod guard;
+mod history;
+mod hooks;
+mod host;
+mod http;
+mod index;
+mod info;
+mod install;
+mod installed;
+mod local;
+mod lockfile;
+mod manifest;
+mod minisign;
+mod mirror;
+mod notes;
+mod outdated;
+mod pgp;
+mod plan;
+mod progress;
+mod release;
+mod release_files;
+mod resolve;
+mod rollback;
+mod schema;
+mod select;
+mod self_update;
+mod shims;
+mod source;
+mod state;
+mod sync;
+mod table;
+mod timings;
+mod token;
+mod tool_versions;
+mod uninstall;
+mod units;
+mod update;
+mod url;
+mod user_path;
+mod verify;
+mod version;
+mod versions;
+mod wrapper;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about = "GitHub Release Downloader")]
+#[command(
+    author,
+    version,
+    about = "GitHub Release Downloader",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    /// GitHub repository (e.g., owner/repo)
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// `grd owner/repo [OPTIONS]` is short for `grd install owner/repo [OPTIONS]`
+    #[command(flatten)]
+    install: InstallArgs,
+
+    /// Emit GitHub Actions annotations (::error::, ::warning::, ::notice::) for failures and installs
+    #[arg(long, global = true)]
+    gha: bool,
+
+    /// Print how long each phase (resolve, download, verify, extract, install) took
+    #[arg(long, global = true)]
+    timings: bool,
+}
+
+/// Options of the install command
+#[derive(clap::Args, Debug)]
+#[command(group(clap::ArgGroup::new("whole_archive").args(["extract_all", "source"])))]
+struct InstallArgs {
+    /// GitHub repository (e.g., owner/repo), or a tool alias (e.g., rg)
+    #[arg(required_unless_present_any = ["list_platforms", "from_file"])]
     repo: Option<String>,
 
-    /// Version to download (e.g., v1.2.3). If omitted, uses latest
+    /// Version to download (e.g., v1.2.3), or a semantic version range picking the highest
+    /// matching release (e.g., '^1.4', '>=0.10, <0.12'). If omitted, uses latest
     #[arg(short, long)]
     tag: Option<String>,
 
-    /// List available release versions
-    #[arg(short, long)]
+    /// Regular expression the whole tag has to match, for tags that aren't semantic versions
+    /// (e.g., 'v1\.2\..*', 'release-2024.*'); the newest matching release by publication wins
+    #[arg(long, conflicts_with = "tag", value_name = "REGEX")]
+    tag_pattern: Option<String>,
+
+    /// Consider prereleases when resolving the latest release
+    #[arg(long)]
+    prerelease: bool,
+
+    /// Release channel the latest release is taken from; tags are classified by their
+    /// conventions (-rc, -beta, nightly-YYYYMMDD), and a channel includes the more stable ones
+    #[arg(long, value_enum, conflicts_with_all = ["tag", "prerelease"])]
+    channel: Option<Channel>,
+
+    /// Resolve the latest release as the highest semantic version instead of GitHub's latest,
+    /// which is the most recent one even when it patches an older series
+    #[arg(long, conflicts_with_all = ["tag", "tag_pattern", "fallback_previous"])]
+    latest_by_version: bool,
+
+    /// List available release versions (same as `grd releases`)
+    #[arg(short, long, hide = true)]
     list: bool,
 
-    /// Destination directory
-    #[arg(short, long, default_value = ".")]
-    destination: PathBuf,
+    /// Destination directory (defaults to the scope's bin directory, or "." without --scope)
+    #[arg(short, long)]
+    destination: Option<PathBuf>,
+
+    /// Install into a writable directory already on PATH (~/.local/bin, ~/bin or ~/.cargo/bin)
+    /// instead of "."
+    #[arg(long, conflicts_with_all = ["destination", "scope"])]
+    install: bool,
+
+    /// On Windows, add the destination to the user PATH if it isn't on PATH yet
+    #[arg(long)]
+    add_to_path: bool,
+
+    /// Install for the current user or machine-wide; receipts are kept in that scope's data directory
+    #[arg(long, value_enum)]
+    scope: Option<Scope>,
 
-    /// Executable file name (defaults to repository name if not specified)
+    /// Executable file name (defaults to repository name); a glob like 'kube*' searches the archive and installs under the normalized match
     #[arg(short, long)]
     bin_name: Option<String>,
 
@@ -40,6 +163,22 @@ struct Args {
     #[arg(long)]
     first: bool,
 
+    /// Select the first matching asset when the prompt is left unanswered this long (e.g. 30s)
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    prompt_timeout: Option<Duration>,
+
+    /// When the latest release has no asset for the platform, try up to this many older releases
+    #[arg(long, value_name = "N", default_value_t = 0, conflicts_with_all = ["tag", "tag_pattern"])]
+    fallback_previous: usize,
+
+    /// Print the release notes of the selected version before downloading it
+    #[arg(long)]
+    notes: bool,
+
+    /// Print the release notes and ask before downloading the selected version
+    #[arg(long)]
+    confirm: bool,
+
     /// Comma-separated list of words to exclude from asset matching
     #[arg(long)]
     exclude: Option<String>,
@@ -48,10 +187,82 @@ struct Args {
     #[arg(long = "no-decompress")]
     no_decompress: bool,
 
+    /// Extract the whole archive instead of only the executable
+    #[arg(long, conflicts_with = "no_decompress")]
+    extract_all: bool,
+
+    /// Download the source code of the tag (or of the latest release) instead of an asset, and
+    /// extract it into <repo>-<tag> in the destination (or --extract-dir); nothing is recorded
+    #[arg(
+        long,
+        conflicts_with_all = ["tag_pattern", "channel", "latest_by_version", "fallback_previous", "extract_all", "no_decompress", "layout", "install", "scope", "locked"]
+    )]
+    source: bool,
+
+    /// Download every asset of the release into the destination as is, without extracting or
+    /// recording anything (e.g., to mirror a release or grab completions and checksums)
+    #[arg(
+        long,
+        conflicts_with_all = ["source", "extract_all", "no_decompress", "layout", "member", "bin_pattern", "bins", "locked", "fallback_previous"]
+    )]
+    all_assets: bool,
+
+    /// Install from a local archive or executable instead of a release asset, without network
+    /// access; with a repository, the install is recorded under it (and --tag, or 'local')
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["tag_pattern", "prerelease", "channel", "latest_by_version", "fallback_previous", "source", "all_assets", "locked", "notes", "confirm"]
+    )]
+    from_file: Option<PathBuf>,
+
+    /// Only download the assets whose name matches this glob (with --all-assets)
+    #[arg(long, requires = "all_assets", value_name = "GLOB")]
+    asset_filter: Option<glob::Pattern>,
+
+    /// Subdirectory of the destination to extract the whole archive (or the source code) into
+    #[arg(long, requires = "whole_archive", value_name = "DIR")]
+    extract_dir: Option<PathBuf>,
+
+    /// Directory layout: 'flat' puts everything in the destination, 'fhs' treats it as a prefix
+    /// with bin/, share/man/ and share/completions/, 'versioned' keeps each version in the data
+    /// directory and links the selected one into the destination
+    #[arg(long, value_enum, default_value_t = Layout::Flat, conflicts_with = "extract_all")]
+    layout: Layout,
+
+    /// Remove this many leading path components from extracted entries (like tar)
+    #[arg(long, requires = "extract_all", value_name = "N", default_value_t = 0)]
+    strip_components: usize,
+
+    /// Path inside the archive of the file to install (exact or glob, e.g. 'release/tool-cli')
+    #[arg(long, conflicts_with_all = ["extract_all", "no_decompress"], value_name = "PATH")]
+    member: Option<String>,
+
+    /// Glob for the executable's name inside the archive, for versioned names (e.g. 'tool-*')
+    #[arg(long, conflicts_with_all = ["extract_all", "no_decompress", "member"], value_name = "GLOB")]
+    bin_pattern: Option<String>,
+
+    /// Install several executables from the archive under their own names; 'all' installs every executable
+    #[arg(
+        long = "bin",
+        value_name = "NAME",
+        value_delimiter = ',',
+        conflicts_with_all = ["bin_name", "extract_all", "no_decompress", "member", "bin_pattern"]
+    )]
+    bins: Vec<String>,
+
     /// Memory limit in bytes; downloads larger than this use temp files
-    #[arg(short = 'm', long = "memory-limit", default_value = "104857600")]
+    #[arg(short = 'm', long = "memory-limit", default_value_t = DEFAULT_MEMORY_LIMIT)]
     memory_limit: u64,
 
+    /// Download the asset even when the asset cache has it, and don't cache it
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Show sizes in decimal units (kB, MB, GB) instead of binary ones (KiB, MiB, GiB)
+    #[arg(long)]
+    si: bool,
+
     /// Target OS (windows, macos, linux, auto-detect if omitted)
     #[arg(long)]
     os: Option<String>,
@@ -60,386 +271,947 @@ struct Args {
     #[arg(long)]
     arch: Option<String>,
 
-    /// List supported platform combinations
+    /// Fail instead of warning when the asset targets a different libc (glibc/musl) than the host
+    #[arg(long)]
+    strict_libc: bool,
+
+    /// Fail instead of warning when an installed executable is built for another OS or architecture
     #[arg(long)]
+    strict_format: bool,
+
+    /// Choose portable packages or installers when a release ships both, instead of prompting
+    #[arg(long, value_enum)]
+    prefer: Option<Prefer>,
+
+    /// List supported platform combinations (same as `grd list --platforms`)
+    #[arg(long, hide = true)]
     list_platforms: bool,
-}
 
-#[derive(Deserialize, Debug)]
-struct Release {
-    tag_name: String,
-    assets: Vec<Asset>,
-}
+    /// Change ownership of installed files and created directories (UID:GID, Unix only)
+    #[arg(long, value_name = "UID:GID")]
+    dest_ownership: Option<String>,
 
-#[derive(Deserialize, Debug, Clone)]
-struct Asset {
-    name: String,
-    browser_download_url: String,
-    size: u64,
-}
+    /// Do not set permissions of newly created destination directories to 0755
+    #[arg(long, conflicts_with = "reproducible")]
+    no_chmod_dirs: bool,
 
-enum DownloadSource {
-    Memory(Vec<u8>),
-    Disk(NamedTempFile),
-}
+    /// Keep the com.apple.quarantine attribute on installed files (macOS only)
+    #[arg(long)]
+    keep_quarantine: bool,
 
-trait ReadSeek: Read + Seek {}
-impl<T: Read + Seek + ?Sized> ReadSeek for T {}
+    /// Refuse to install into a destination other users can tamper with (owned by them,
+    /// world-writable, or behind their symlinks) instead of warning
+    #[arg(long)]
+    paranoid: bool,
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+    /// Set modification times of installed files to this Unix timestamp
+    #[arg(long, env = "SOURCE_DATE_EPOCH", value_name = "SECONDS")]
+    source_date_epoch: Option<u64>,
 
-    let ua = format!("lucidfrontier45/grd-{}", env!("CARGO_PKG_VERSION"));
-    let agent: Agent = Agent::config_builder().user_agent(&ua).build().into();
+    /// Expected digest of the downloaded asset (sha256:<hex>); installation fails on mismatch
+    #[arg(long, value_name = "ALGO:HEX")]
+    checksum: Option<String>,
 
-    // If the --list flag is present
-    if args.list {
-        let repo = args
-            .repo
-            .as_ref()
-            .ok_or_else(|| anyhow!("--list requires a repository"))?;
-        return list_releases(&agent, repo);
-    }
+    /// Local checksum file (sha256sum format) listing the expected digest of the asset
+    #[arg(long, value_name = "FILE")]
+    checksum_file: Option<PathBuf>,
 
-    let repo = args.repo.ok_or_else(|| anyhow!("Repository is required"))?;
-
-    // 1. Fetch release info (specific tag or latest)
-    let release = fetch_release_info(&agent, &repo, args.tag.as_deref())?;
-    println!("Selected version: {}", release.tag_name);
-
-    // 2. Select the asset best matching the host or explicit platform
-    let os = args
-        .os
-        .as_ref()
-        .map(|s| normalize_os(s))
-        .transpose()?
-        .unwrap_or_else(|| env::consts::OS.to_string());
-    let arch = args
-        .arch
-        .as_ref()
-        .map(|s| normalize_arch(s))
-        .transpose()?
-        .unwrap_or_else(|| env::consts::ARCH.to_string());
-
-    if args.os.is_none() && args.arch.is_none() {
-        println!("Detected platform: {}-{}", os, arch);
-    } else {
-        println!("Using platform: {}-{}", os, arch);
-    }
+    /// Minisign public key (base64, or a minisign.pub file) the asset's .minisig has to verify against
+    #[arg(long, value_name = "PUBKEY")]
+    minisign_key: Option<String>,
 
-    let asset = select_asset(
-        &release.assets,
-        &os,
-        &arch,
-        args.first,
-        args.exclude.as_deref(),
-    )?;
-    println!("Selected asset: {}", asset.name);
-
-    // 3. Download and place the binary
-    let bin_name = args
-        .bin_name
-        .unwrap_or_else(|| repo.split('/').next_back().unwrap_or("app").to_string());
-
-    let source = download_asset(&agent, &asset, args.memory_limit)?;
-
-    extract_and_save(
-        source,
-        &asset.name,
-        &bin_name,
-        &args.destination,
-        args.no_decompress,
-    )?;
-
-    println!(
-        "Successfully installed '{}' to {:?}",
-        bin_name, args.destination
-    );
-    Ok(())
-}
+    /// OpenPGP public key file the asset's detached .asc/.sig signature has to verify against
+    #[arg(long, value_name = "FILE")]
+    gpg_key: Option<PathBuf>,
 
-/// List releases
-fn list_releases(agent: &Agent, repo: &str) -> Result<()> {
-    let url = format!("https://api.github.com/repos/{}/releases", repo);
-    let mut response = agent.get(&url).call()?;
-    let releases: Vec<Release> = response.body_mut().read_json()?;
+    /// Fail unless a minisign or PGP signature of the asset is verified
+    #[arg(long)]
+    require_signature: bool,
 
-    println!("Available releases for {}:", repo);
-    for rel in releases {
-        println!("  - {}", rel.tag_name);
-    }
-    Ok(())
+    /// Install with a warning when a key is given but the release publishes no signature
+    #[arg(long, conflicts_with = "require_signature")]
+    allow_unsigned: bool,
+
+    /// Print SHA-256 digests of the downloaded asset and of each installed file
+    #[arg(long)]
+    print_digest: bool,
+
+    /// Print the installed release and files as JSON (see `grd schema install`)
+    #[arg(long, conflicts_with = "print_digest")]
+    json: bool,
+
+    /// Normalize timestamps (to --source-date-epoch or 0) and permissions (0755 for directories and
+    /// executables, 0644 for other files) for byte-identical installs
+    #[arg(long)]
+    reproducible: bool,
+
+    /// Install the release and asset pinned in the lockfile, failing if the release drifted from it
+    #[arg(long, conflicts_with_all = ["fallback_previous", "list"])]
+    locked: bool,
+
+    /// Lockfile used with --locked
+    #[arg(
+        long,
+        value_name = "FILE",
+        default_value = "grd.lock",
+        requires = "locked"
+    )]
+    lockfile: PathBuf,
 }
 
-/// Fetch release information for a given tag or the latest release
-fn fetch_release_info(agent: &Agent, repo: &str, tag: Option<&str>) -> Result<Release> {
-    let url = match tag {
-        Some(t) => format!("https://api.github.com/repos/{}/releases/tags/{}", repo, t),
-        None => format!("https://api.github.com/repos/{}/releases/latest", repo),
-    };
-
-    let mut response = agent.get(&url).call()?;
-    if !response.status().is_success() {
-        return Err(anyhow!(
-            "Failed to fetch release info: {}",
-            response.status()
-        ));
-    }
-    Ok(response.body_mut().read_json()?)
+/// Release and asset selection options shared by commands that resolve an asset
+#[derive(clap::Args, Debug)]
+struct SelectArgs {
+    /// Version to resolve (e.g., v1.2.3), or a semantic version range (e.g., '^1.4'). If
+    /// omitted, uses latest
+    #[arg(short, long)]
+    tag: Option<String>,
+
+    /// Regular expression the whole tag has to match; the newest matching release wins
+    #[arg(long, conflicts_with = "tag", value_name = "REGEX")]
+    tag_pattern: Option<String>,
+
+    /// Consider prereleases when resolving the latest release
+    #[arg(long)]
+    prerelease: bool,
+
+    /// Release channel the latest release is taken from; tags are classified by their
+    /// conventions (-rc, -beta, nightly-YYYYMMDD), and a channel includes the more stable ones
+    #[arg(long, value_enum, conflicts_with_all = ["tag", "prerelease"])]
+    channel: Option<Channel>,
+
+    /// Resolve the latest release as the highest semantic version instead of GitHub's latest,
+    /// which is the most recent one even when it patches an older series
+    #[arg(long, conflicts_with_all = ["tag", "tag_pattern", "fallback_previous"])]
+    latest_by_version: bool,
+
+    /// Target OS (windows, macos, linux, auto-detect if omitted)
+    #[arg(long)]
+    os: Option<String>,
+
+    /// Target architecture (x86_64, aarch64, auto-detect if omitted)
+    #[arg(long)]
+    arch: Option<String>,
+
+    /// Always select the first matching asset without prompting
+    #[arg(long)]
+    first: bool,
+
+    /// Select the first matching asset when the prompt is left unanswered this long (e.g. 30s)
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    prompt_timeout: Option<Duration>,
+
+    /// When the latest release has no asset for the platform, try up to this many older releases
+    #[arg(long, value_name = "N", default_value_t = 0, conflicts_with_all = ["tag", "tag_pattern"])]
+    fallback_previous: usize,
+
+    /// Comma-separated list of words to exclude from asset matching
+    #[arg(long)]
+    exclude: Option<String>,
+
+    /// Choose portable packages or installers when a release ships both
+    #[arg(long, value_enum)]
+    prefer: Option<Prefer>,
 }
 
-fn format_size(bytes: u64) -> String {
-    if bytes < 1024 {
-        format!("{} B", bytes)
-    } else if bytes < 1024 * 1024 {
-        format!("{:.1} KB", bytes as f64 / 1024.0)
-    } else {
-        format!("{:.1} MB", bytes as f64 / 1024.0 / 1024.0)
+impl SelectArgs {
+    /// The repository of `owner/repo@tag`, taking the tag as `--tag`
+    fn split_tag<'a>(&mut self, repo: &'a str) -> Result<&'a str> {
+        let Some((repo, tag)) = repo.split_once('@') else {
+            return Ok(repo);
+        };
+        if self.tag.is_some() || self.tag_pattern.is_some() {
+            return Err(anyhow!(
+                "Give the release either as {}@{} or with --tag",
+                repo,
+                tag
+            ));
+        }
+        self.tag = Some(tag.to_string());
+        Ok(repo)
     }
-}
 
-fn normalize_os(input: &str) -> Result<String> {
-    let normalized = input.to_lowercase();
-    match normalized.as_str() {
-        "windows" | "macos" | "linux" => Ok(normalized),
-        _ => Err(anyhow!(
-            "Invalid OS '{}'. Supported: windows, macos, linux",
-            input
-        )),
+    fn into_options(self) -> Result<InstallOptions> {
+        let (os, arch) = resolve_platform(self.os.as_deref(), self.arch.as_deref())?;
+        let version_req = self
+            .tag
+            .as_deref()
+            .map(version::parse_range)
+            .transpose()?
+            .flatten();
+        Ok(InstallOptions {
+            tag: self.tag.filter(|_| version_req.is_none()),
+            version_req,
+            tag_pattern: self
+                .tag_pattern
+                .as_deref()
+                .map(version::parse_tag_pattern)
+                .transpose()?,
+            prerelease: self.prerelease,
+            channel: self.channel,
+            latest_by_version: self.latest_by_version,
+            os,
+            arch,
+            first: self.first,
+            prompt_timeout: self.prompt_timeout,
+            fallback_previous: self.fallback_previous,
+            exclude: self.exclude,
+            prefer: self.prefer,
+            ..InstallOptions::default()
+        })
     }
 }
 
-fn normalize_arch(input: &str) -> Result<String> {
-    let normalized = input.to_lowercase();
-    match normalized.as_str() {
-        "x86_64" | "amd64" | "x64" => Ok("x86_64".to_string()),
-        "aarch64" | "arm64" => Ok("aarch64".to_string()),
-        _ => Err(anyhow!(
-            "Invalid architecture '{}'. Supported: x86_64 (aliases: amd64, x64), aarch64 (alias: arm64)",
-            input
-        )),
-    }
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Download a release asset and install its executable (the default command)
+    Install(Box<InstallArgs>),
+    /// List the assets of a release, the tools installed by grd, or the supported platforms
+    List {
+        /// GitHub repository (e.g., owner/repo), or a tool alias (e.g., rg)
+        #[arg(required_unless_present_any = ["platforms", "installed"])]
+        repo: Option<String>,
+
+        /// Release to list (e.g., v1.2.3). If omitted, uses latest
+        #[arg(short, long)]
+        tag: Option<String>,
+
+        /// List the supported platform combinations instead
+        #[arg(long, conflicts_with_all = ["tag", "installed"])]
+        platforms: bool,
+
+        /// List the tools installed by grd, with their repositories, tags and destinations
+        #[arg(long, conflicts_with_all = ["repo", "tag"])]
+        installed: bool,
+
+        /// Scope to list installed tools of
+        #[arg(long, value_enum, default_value_t, requires = "installed")]
+        scope: Scope,
+
+        /// Show sizes in decimal units (kB, MB, GB) instead of binary ones (KiB, MiB, GiB)
+        #[arg(long)]
+        si: bool,
+
+        /// Print the release and its assets as JSON (see `grd schema release`)
+        #[arg(long, conflicts_with_all = ["platforms", "installed"])]
+        json: bool,
+    },
+    /// Show a release, who published it, and its assets with their sizes, download counts and
+    /// digests, marking those that match the platform
+    Info {
+        /// GitHub repository (e.g., owner/repo), or a tool alias (e.g., rg); append @tag to
+        /// show a given release (e.g., owner/repo@v1.2.3)
+        repo: String,
+
+        /// Show sizes in decimal units (kB, MB, GB) instead of binary ones (KiB, MiB, GiB)
+        #[arg(long)]
+        si: bool,
+
+        /// Print the release and its assets as JSON (see `grd schema release`)
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// List every asset of a release with its size and content type, marking those that match the
+    /// platform, to find out why an asset isn't selected
+    Assets {
+        /// GitHub repository (e.g., owner/repo), or a tool alias (e.g., rg); append @tag to
+        /// list the assets of a given release (e.g., owner/repo@v1.2.3)
+        repo: String,
+
+        /// Show sizes in decimal units (kB, MB, GB) instead of binary ones (KiB, MiB, GiB)
+        #[arg(long)]
+        si: bool,
+
+        /// Print the release and its assets as JSON (see `grd schema release`)
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// List the release versions of a repository
+    Releases {
+        /// GitHub repository (e.g., owner/repo), or a tool alias (e.g., rg)
+        repo: String,
+
+        /// Only list releases whose tag matches this glob (e.g., 'v2.*')
+        #[arg(long, value_name = "GLOB")]
+        filter: Option<glob::Pattern>,
+
+        /// Only list releases published on or after this date (YYYY-MM-DD)
+        #[arg(long, value_name = "DATE", value_parser = parse_date)]
+        since: Option<String>,
+
+        /// Number of releases to list, newest first
+        #[arg(long, value_name = "N", default_value_t = 30)]
+        limit: usize,
+
+        /// List every release, however many pages that takes
+        #[arg(long, conflicts_with = "limit")]
+        all: bool,
+
+        /// Print the releases and their assets as JSON (see `grd schema releases`)
+        #[arg(long)]
+        json: bool,
+    },
+    /// Install every tool of a manifest into a relocatable bundle directory
+    BundleDir {
+        /// Manifest file listing the tools (TOML)
+        manifest: PathBuf,
+
+        /// Bundle directory to create (bin/, share/ and receipts/ are placed inside)
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Pack the assets of a manifest's tools into a single archive, and install from it offline
+    Bundle {
+        #[command(subcommand)]
+        command: BundleCommand,
+    },
+    /// Manage the cache of downloaded assets, `grd run` releases and completion tags
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// Copy releases and their assets into a directory that, served over HTTP, stands in for
+    /// GitHub in air-gapped networks (point GRD_MIRROR at it)
+    Mirror {
+        /// GitHub repositories (e.g., owner/repo), or tool aliases (e.g., rg)
+        #[arg(required = true)]
+        repos: Vec<String>,
+
+        /// Directory of the mirror, created or updated
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Only copy releases whose tag is in this semantic version range (e.g., '>=1.4')
+        #[arg(long, value_name = "RANGE")]
+        versions: Option<semver::VersionReq>,
+
+        /// Only copy releases whose tag matches this glob (e.g., 'v2.*')
+        #[arg(long, value_name = "GLOB")]
+        filter: Option<glob::Pattern>,
+
+        /// Number of the newest matching releases to copy
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+
+        /// Only copy the assets whose name matches this glob (e.g., '*linux*')
+        #[arg(long, value_name = "GLOB")]
+        asset_filter: Option<glob::Pattern>,
+
+        /// Show sizes in decimal units (kB, MB, GB) instead of binary ones (KiB, MiB, GiB)
+        #[arg(long)]
+        si: bool,
+    },
+    /// Install or update every tool of a manifest, e.g. to bootstrap a development environment
+    Sync {
+        /// Manifest or tool-version file listing the tools; defaults to the nearest grd.toml, .grd-tools or .tool-versions in the current directory or its parents, up to the repository root
+        manifest: Option<PathBuf>,
+
+        /// Scope to install into; tools without a destination go to its bin directory
+        #[arg(long, value_enum, default_value_t)]
+        scope: Scope,
+
+        /// Install the releases and assets pinned in the manifest's lockfile (grd.toml -> grd.lock)
+        #[arg(long)]
+        locked: bool,
+    },
+    /// Pin the release asset and digest of every manifest tool in a lockfile (grd.toml -> grd.lock)
+    Lock {
+        /// Manifest or tool-version file listing the tools; discovered like for `sync` by default
+        manifest: Option<PathBuf>,
+
+        /// Platforms to lock (OS-ARCH, comma-separated) besides those already locked; a new lockfile defaults to the host
+        #[arg(long, value_delimiter = ',')]
+        platform: Vec<String>,
+    },
+    /// Reinstall tools installed by grd whose repository has a newer release
+    Update {
+        /// Tool name (the installed executable name)
+        #[arg(required_unless_present = "all")]
+        name: Option<String>,
+
+        /// Update every installed tool
+        #[arg(long, conflicts_with = "name")]
+        all: bool,
+
+        /// Comma-separated list of tools to leave at their installed version
+        #[arg(long, value_delimiter = ',', conflicts_with = "name")]
+        except: Vec<String>,
+
+        /// Print the release notes of every version between the installed and the new one
+        #[arg(long)]
+        changelog: bool,
+
+        /// Scope the tools were installed in
+        #[arg(long, value_enum, default_value_t)]
+        scope: Scope,
+    },
+    /// List the tool aliases accepted in place of repositories, built in and from the config file
+    Aliases,
+    /// Manage the shared tool definitions (aliases and asset rules for tricky repositories)
+    Definitions {
+        #[command(subcommand)]
+        command: DefinitionsCommand,
+    },
+    /// List the tools installed by grd that are behind the latest release of their repository
+    Outdated {
+        /// Print the outdated tools as JSON, for dashboards and scripts
+        #[arg(long)]
+        json: bool,
+
+        /// Scope the tools were installed in
+        #[arg(long, value_enum, default_value_t)]
+        scope: Scope,
+    },
+    /// Show where a tool installed by grd is, which repository and tag it came from, and its digests
+    ///
+    /// Exits with an error if grd doesn't manage it.
+    Which {
+        /// Tool name, or the name of an executable it installed
+        name: String,
+
+        /// Scope to look in (both by default)
+        #[arg(long, value_enum)]
+        scope: Option<Scope>,
+    },
+    /// Check that the files installed by grd still match the digests recorded when installing them
+    Verify {
+        /// Tool name (the installed executable name). If omitted, checks every installed tool
+        name: Option<String>,
+
+        /// Scope the tools were installed in
+        #[arg(long, value_enum, default_value_t)]
+        scope: Scope,
+    },
+    /// Find receipts that no longer match the filesystem: tools whose files are gone, missing files and broken symlinks
+    Doctor {
+        /// Drop orphaned receipts, forget missing files and remove broken symlinks
+        #[arg(long)]
+        fix: bool,
+
+        /// Scope to check
+        #[arg(long, value_enum, default_value_t)]
+        scope: Scope,
+    },
+    /// Remove a tool installed by grd: its executables, extracted files and receipt
+    Uninstall {
+        /// Tool name (the installed executable name)
+        name: String,
+
+        /// Only print the files that would be removed
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Scope the tool was installed in
+        #[arg(long, value_enum, default_value_t)]
+        scope: Scope,
+    },
+    /// Select another version of a tool installed with `--layout versioned`, without downloading
+    /// it again
+    ///
+    /// Without a tag, lists the versions present.
+    Switch {
+        /// Tool name (the installed executable name)
+        name: String,
+
+        /// Version to select, as its release tag
+        tag: Option<String>,
+
+        /// Scope the tool was installed in
+        #[arg(long, value_enum, default_value_t)]
+        scope: Scope,
+    },
+    /// Reinstall the version of a tool that was installed before the current one (one level; a
+    /// second rollback returns to the current version)
+    Rollback {
+        /// Tool name (the installed executable name)
+        name: String,
+
+        /// Scope the tool was installed in
+        #[arg(long, value_enum, default_value_t)]
+        scope: Scope,
+    },
+    /// Print the download URL of the asset that would be installed, without downloading it
+    Url {
+        /// GitHub repository (e.g., owner/repo), or a tool alias (e.g., rg)
+        repo: String,
+
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Print a stable cache key (repository, resolved tag and asset digest) for CI caches
+    CacheKey {
+        /// GitHub repository (e.g., owner/repo), or a tool alias (e.g., rg)
+        repo: String,
+
+        /// Semver requirement the tag has to satisfy (e.g., '^1.2'); the highest match is used
+        #[arg(long, conflicts_with = "tag", value_name = "REQ")]
+        version_req: Option<semver::VersionReq>,
+
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Show when each version of a tool was installed, and from which asset
+    History {
+        /// Tool name (the installed executable name)
+        name: String,
+
+        /// Scope the tool was installed in
+        #[arg(long, value_enum, default_value_t)]
+        scope: Scope,
+    },
+    /// Fail unless a release has an asset for every expected platform (a post-release CI gate)
+    CheckAssets {
+        /// GitHub repository (e.g., owner/repo), or a tool alias (e.g., rg)
+        repo: String,
+
+        /// Comma-separated platforms the release must cover (e.g., linux-x86_64,macos-aarch64)
+        #[arg(long, value_delimiter = ',', required = true, value_name = "OS-ARCH")]
+        expect: Vec<String>,
+
+        /// Release to check (e.g., v1.2.3). If omitted, uses latest
+        #[arg(short, long)]
+        tag: Option<String>,
+    },
+    /// Time single-stream, temp-file and segmented downloads of the asset that would be installed
+    Bench {
+        /// GitHub repository (e.g., owner/repo), or a tool alias (e.g., rg)
+        repo: String,
+
+        /// Number of parallel range requests for the segmented download
+        #[arg(long, default_value_t = 4)]
+        connections: usize,
+
+        /// Show sizes in decimal units (kB, MB, GB) instead of binary ones (KiB, MiB, GiB)
+        #[arg(long)]
+        si: bool,
+
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Replace this grd executable with the latest release (or another one), verified like any install
+    SelfUpdate {
+        /// Release to install instead of the latest (e.g., v1.2.3), also to downgrade
+        #[arg(short, long)]
+        tag: Option<String>,
+
+        /// Only print whether a newer release is available
+        #[arg(long, conflicts_with = "tag")]
+        check: bool,
+    },
+    /// Print the JSON Schema of a command's --json output
+    Schema {
+        /// Command whose output to describe
+        #[arg(value_enum)]
+        output: schema::Output,
+    },
+    /// Run a release's executable with the given arguments, without installing it
+    ///
+    /// The release is unpacked into the cache on first use and reused afterwards; grd exits with
+    /// the executable's exit code.
+    Run {
+        /// GitHub repository (e.g., owner/repo), or a tool alias (e.g., rg)
+        repo: String,
+
+        /// Executable to run from the release (defaults to the alias or repository name)
+        #[arg(long)]
+        bin_name: Option<String>,
+
+        #[command(flatten)]
+        select: SelectArgs,
+
+        /// Arguments passed to the executable, after `--`
+        #[arg(last = true)]
+        args: Vec<std::ffi::OsString>,
+    },
+    /// Write shims for the executables of tools installed with `--layout versioned`, running the
+    /// version pinned by the nearest `.grd-tools` or `.tool-versions` file
+    ///
+    /// The shims are kept up to date from then on; put their directory first on PATH.
+    Shims {
+        /// Scope whose tools get shims
+        #[arg(long, value_enum, default_value_t)]
+        scope: Scope,
+    },
+    /// Run an executable through its shim
+    #[command(name = "__shim", hide = true)]
+    Shim {
+        #[arg(long, value_enum, default_value_t)]
+        scope: Scope,
+
+        tool: String,
+
+        exe: String,
+
+        #[arg(last = true)]
+        args: Vec<std::ffi::OsString>,
+    },
+    /// Print the tags of a repository's recent releases, for shell completion of --tag
+    #[command(name = "__complete-tags", hide = true)]
+    CompleteTags {
+        /// GitHub repository (e.g., owner/repo), or a tool alias (e.g., rg)
+        repo: String,
+    },
+    /// Inspect or change the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
 }
 
-fn select_asset(
-    assets: &[Asset],
-    os: &str,
-    arch: &str,
-    first: bool,
-    exclude: Option<&str>,
-) -> Result<Asset> {
-    let blacklist: Vec<String> = exclude.map_or_else(Vec::new, |s| {
-        s.split(',').map(|w| w.trim().to_lowercase()).collect()
-    });
-
-    let matches: Vec<&Asset> = assets
-        .iter()
-        .filter(|a| {
-            let name = a.name.to_lowercase();
-            let os_match = match os {
-                "windows" => {
-                    name.contains("windows")
-                        || name.contains("win64")
-                        || name.contains("pc-windows")
-                }
-                "macos" => {
-                    name.contains("apple-darwin")
-                        || name.contains("macos")
-                        || name.contains("darwin")
-                }
-                "linux" => name.contains("linux") || name.contains("unknown-linux"),
-                _ => false,
-            };
-            let arch_match = match arch {
-                "x86_64" => {
-                    name.contains("x86_64") || name.contains("amd64") || name.contains("x64")
-                }
-                "aarch64" => name.contains("aarch64") || name.contains("arm64"),
-                _ => false,
-            };
-            os_match && arch_match && !blacklist.iter().any(|b| name.contains(b))
-        })
-        .collect();
-
-    match matches.len() {
-        0 => Err(anyhow!("No matching asset found for {}-{}", os, arch)),
-        1 => Ok(matches[0].clone()),
-        _ => {
-            if first {
-                Ok(matches[0].clone())
-            } else {
-                println!("Multiple assets found. Select one:");
-                for (i, asset) in matches.iter().enumerate() {
-                    println!("{}. {} ({})", i + 1, asset.name, format_size(asset.size));
-                }
-                loop {
-                    print!("Enter choice (1-{}): ", matches.len());
-                    io::stdout().flush().unwrap();
-                    let mut input = String::new();
-                    io::stdin()
-                        .read_line(&mut input)
-                        .map_err(|_| anyhow!("Failed to read input"))?;
-                    match input.trim().parse::<usize>() {
-                        Ok(n) if n >= 1 && n <= matches.len() => return Ok(matches[n - 1].clone()),
-                        _ => println!(
-                            "Invalid choice. Enter a number between 1 and {}.",
-                            matches.len()
-                        ),
-                    }
-                }
-            }
-        }
-    }
+#[derive(Subcommand, Debug)]
+enum DefinitionsCommand {
+    /// Clone or fast-forward the definitions repository set as definitions_repo in the config file
+    Update,
+    /// List the repositories that have definitions, with the file each comes from
+    List,
 }
 
-fn download_asset(agent: &Agent, asset: &Asset, memory_threshold: u64) -> Result<DownloadSource> {
-    println!("Downloading...");
-    let pb = ProgressBar::new(asset.size);
-    pb.set_style(
-        ProgressStyle::with_template(
-            "[{elapsed_precise}] {bar:40.cyan/blue} {bytes}/{total_bytes} ({eta})",
-        )
-        .unwrap()
-        .progress_chars("#>–"),
-    );
-    let mut response = agent.get(&asset.browser_download_url).call()?;
-    let mut reader = response.body_mut().as_reader();
-    let source = if asset.size > memory_threshold {
-        println!("Using temp file due to size > {} bytes", memory_threshold);
-        let mut temp_file = NamedTempFile::new()?;
-        let writer = |buf: &[u8]| temp_file.write_all(buf);
-        download_with_progress(&mut reader, &pb, writer)?;
-        DownloadSource::Disk(temp_file)
-    } else {
-        let mut bytes = Vec::new();
-        let writer = |buf: &[u8]| {
-            bytes.extend_from_slice(buf);
-            Ok(())
-        };
-        download_with_progress(&mut reader, &pb, writer)?;
-        DownloadSource::Memory(bytes)
-    };
-    pb.finish_with_message("Downloaded");
-    Ok(source)
+#[derive(Subcommand, Debug)]
+enum BundleCommand {
+    /// Download and verify the assets of every manifest tool into a bundle archive (.tar.gz)
+    Create {
+        /// Manifest or tool-version file listing the tools; discovered like for `sync` by default
+        manifest: Option<PathBuf>,
+
+        /// Bundle archive to write
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Platforms to bundle assets for (OS-ARCH, comma-separated); defaults to the host
+        #[arg(long, value_delimiter = ',')]
+        platform: Vec<String>,
+
+        /// Bundle the releases and assets pinned in the manifest's lockfile
+        #[arg(long)]
+        locked: bool,
+    },
+    /// Install every tool of a bundle archive, without network access
+    Install {
+        /// Bundle archive written by `grd bundle create`
+        bundle: PathBuf,
+
+        /// Scope to install into; tools without a destination go to its bin directory
+        #[arg(long, value_enum, default_value_t)]
+        scope: Scope,
+
+        /// Run the hooks of the bundled manifest, which are skipped by default
+        #[arg(long)]
+        run_hooks: bool,
+    },
 }
 
-fn download_with_progress<R: Read, F>(reader: &mut R, pb: &ProgressBar, mut writer: F) -> Result<()>
-where
-    F: FnMut(&[u8]) -> io::Result<()>,
-{
-    let mut buf = [0; 8192];
-    loop {
-        let n = reader.read(&mut buf)?;
-        if n == 0 {
-            break;
-        }
-        writer(&buf[..n])?;
-        pb.inc(n as u64);
-    }
-    Ok(())
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// Remove cached assets, run objects and completion tags unused for a while, or beyond a size
+    Prune {
+        /// Remove what wasn't used for this long (e.g., 30d, 12h)
+        #[arg(
+            long,
+            value_name = "DURATION",
+            value_parser = humantime::parse_duration,
+            required_unless_present = "max_size"
+        )]
+        older_than: Option<Duration>,
+
+        /// Then remove the least recently used entries until the cache fits (e.g., 2G, 500MiB)
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_size: Option<u64>,
+    },
+    /// Print the cache directory
+    Dir,
+    /// Remove everything from the cache
+    Clear,
 }
 
-fn extract_and_save(
-    source: DownloadSource,
-    filename: &str,
-    bin_name: &str,
-    dest_dir: &Path,
-    no_decompress: bool,
-) -> Result<()> {
-    fs::create_dir_all(dest_dir)?;
-
-    if no_decompress {
-        // Save using the original asset name (do not rename or extract)
-        save_raw(source, filename, dest_dir)?;
-        println!("Saved raw asset to {:?}", dest_dir.join(filename));
-        return Ok(());
-    }
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Validate the config file and manifests, reporting unknown keys, bad globs and other mistakes
+    Check {
+        /// Manifest files to validate as well
+        manifests: Vec<PathBuf>,
 
-    let target_bin_name = if cfg!(windows) {
-        format!("{}.exe", bin_name)
-    } else {
-        bin_name.to_string()
-    };
-
-    if filename.ends_with(".zip") {
-        extract_zip(source, &target_bin_name, dest_dir)
-    } else if filename.ends_with(".tar.gz") || filename.ends_with(".tgz") {
-        extract_tar_gz(source, &target_bin_name, dest_dir)
-    } else {
-        save_raw(source, &target_bin_name, dest_dir)
-    }
+        /// Also check that every repository mentioned exists on GitHub
+        #[arg(long)]
+        online: bool,
+    },
+    /// Print a value (e.g. 'minisign_keys.owner/repo'), or every entry of a section
+    Get {
+        /// Config key: <section>.<entry> or <section>
+        key: String,
+    },
+    /// Set a value (e.g. 'gpg_keys.owner/repo /etc/grd/keys/owner.asc')
+    Set {
+        /// Config key: <section>.<entry>
+        key: String,
+
+        /// Value to store
+        value: String,
+    },
+    /// Remove a value, or a whole section
+    Unset {
+        /// Config key: <section>.<entry> or <section>
+        key: String,
+    },
+    /// Open the config file in $VISUAL or $EDITOR
+    Edit,
 }
 
-fn extract_zip(source: DownloadSource, target_bin_name: &str, dest_dir: &Path) -> Result<()> {
-    let rdr: Box<dyn ReadSeek> = match source {
-        DownloadSource::Memory(bytes) => Box::new(Cursor::new(bytes)),
-        DownloadSource::Disk(temp_file) => Box::new(File::open(temp_file.path())?),
-    };
-    let target_bin_name: &str = target_bin_name;
-    let mut archive = ZipArchive::new(rdr)?;
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        if file.name().ends_with(target_bin_name) {
-            let out_path = dest_dir.join(target_bin_name);
-            let mut outfile = File::create(&out_path)?;
-            io::copy(&mut file, &mut outfile)?;
-            #[cfg(unix)]
-            set_permissions(&out_path)?;
-            return Ok(());
-        }
-    }
-    Err(anyhow!(
-        "Executable '{}' not found in archive",
-        target_bin_name
-    ))
+/// How installed files are arranged under the destination
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// Executables directly in the destination
+    #[default]
+    Flat,
+    /// Executables in `bin/`, man pages in `share/man/` and completions in `share/completions/`
+    Fhs,
+    /// Each version in `tools/<name>/<version>/` of the data directory, with a `current` symlink
+    /// selecting the one the destination links to
+    Versioned,
 }
 
-fn extract_tar_gz(source: DownloadSource, target_bin_name: &str, dest_dir: &Path) -> Result<()> {
-    let rdr: Box<dyn Read> = match source {
-        DownloadSource::Memory(bytes) => Box::new(Cursor::new(bytes)),
-        DownloadSource::Disk(temp_file) => Box::new(File::open(temp_file.path())?),
-    };
-    let target_bin_name: &str = target_bin_name;
-    let mut archive = tar::Archive::new(GzDecoder::new(rdr));
-    for entry in archive.entries()? {
-        let mut file = entry?;
-        let path = file.path()?.to_path_buf();
-        if path.to_string_lossy().ends_with(target_bin_name) {
-            let out_path = dest_dir.join(target_bin_name);
-            file.unpack(&out_path)?;
-            #[cfg(unix)]
-            set_permissions(&out_path)?;
-            return Ok(());
-        }
+fn main() -> Result<()> {
+    let args = Args::parse();
+    if args.gha {
+        gha::enable();
+    }
+    if args.timings {
+        timings::enable();
+    }
+    let result = run(args);
+    timings::report();
+    if let Err(e) = &result {
+        gha::error(&format!("{:#}", e));
     }
-    Err(anyhow!(
-        "Executable '{}' not found in archive",
-        target_bin_name
-    ))
+    result
 }
 
-fn save_raw(source: DownloadSource, target_bin_name: &str, dest_dir: &Path) -> Result<()> {
-    let out_path = dest_dir.join(target_bin_name);
-    match source {
-        DownloadSource::Memory(bytes) => {
-            fs::write(&out_path, bytes)?;
+fn run(args: Args) -> Result<()> {
+    let agent = http::build_agent();
+
+    let command = args
+        .command
+        .unwrap_or_else(|| Command::Install(Box::new(args.install)));
+    match command {
+        Command::Install(args) => install_command(&agent, *args),
+        Command::List {
+            installed: true,
+            scope,
+            ..
+        } => installed::list_installed(scope),
+        Command::List {
+            repo: Some(repo),
+            tag,
+            platforms: false,
+            si,
+            json,
+            ..
+        } => info::list_assets(&agent, &alias::resolve(&repo)?, tag.as_deref(), si, json),
+        Command::List { .. } => {
+            print_platforms();
+            Ok(())
         }
-        DownloadSource::Disk(temp_file) => {
-            fs::copy(temp_file.path(), &out_path)?;
+        Command::Info {
+            repo,
+            si,
+            json,
+            mut select,
+        } => {
+            let repo = select.split_tag(&repo)?;
+            let opts = InstallOptions {
+                si,
+                ..select.into_options()?
+            };
+            info::info(&agent, &alias::resolve(repo)?, &opts, json)
         }
+        Command::Assets {
+            repo,
+            si,
+            json,
+            mut select,
+        } => {
+            let repo = alias::resolve(select.split_tag(&repo)?)?;
+            let mut opts = InstallOptions {
+                si,
+                ..select.into_options()?
+            };
+            // Selection goes by the repository's asset glob too
+            Config::load()?.repo(&repo).apply(&mut opts)?;
+            info::assets(&agent, &repo, &opts, json)
+        }
+        Command::Releases {
+            repo,
+            filter,
+            since,
+            limit,
+            all,
+            json,
+        } => {
+            let filter = ReleaseFilter {
+                tag: filter,
+                since,
+                limit: (!all).then_some(limit),
+            };
+            list_releases(&agent, &alias::resolve(&repo)?, &filter, json)
+        }
+        Command::BundleDir { manifest, output } => bundle::bundle_dir(&agent, &manifest, &output),
+        Command::Bundle { command } => match command {
+            BundleCommand::Create {
+                manifest,
+                output,
+                platform,
+                locked,
+            } => {
+                let manifest = match manifest {
+                    Some(path) => path,
+                    None => manifest::discover(&env::current_dir()?)?,
+                };
+                bundle::create(&agent, &manifest, &output, &platform, locked)
+            }
+            BundleCommand::Install {
+                bundle,
+                scope,
+                run_hooks,
+            } => bundle::install_bundle(&bundle, scope, run_hooks),
+        },
+        Command::Cache { command } => match command {
+            CacheCommand::Prune {
+                older_than,
+                max_size,
+            } => cache::prune(older_than, max_size),
+            CacheCommand::Dir => cache::dir(),
+            CacheCommand::Clear => cache::clear(),
+        },
+        Command::Mirror {
+            repos,
+            output,
+            versions,
+            filter,
+            limit,
+            asset_filter,
+            si,
+        } => {
+            let repos = repos
+                .iter()
+                .map(|repo| alias::resolve(repo))
+                .collect::<Result<Vec<_>>>()?;
+            let filter = mirror::Filter {
+                versions,
+                tags: filter,
+                limit,
+                assets: asset_filter,
+            };
+            mirror::mirror(&agent, &repos, &output, &filter, si)
+        }
+        Command::Sync {
+            manifest,
+            scope,
+            locked,
+        } => {
+            let manifest = match manifest {
+                Some(path) => path,
+                None => manifest::discover(&env::current_dir()?)?,
+            };
+            sync::sync(&agent, &manifest, scope, locked)
+        }
+        Command::Lock { manifest, platform } => {
+            let manifest = match manifest {
+                Some(path) => path,
+                None => manifest::discover(&env::current_dir()?)?,
+            };
+            lockfile::lock(&agent, &manifest, &platform)
+        }
+        Command::Update {
+            name,
+            except,
+            changelog,
+            scope,
+            ..
+        } => update::update(&agent, name.as_deref(), &except, scope, changelog),
+        Command::Outdated { json, scope } => outdated::outdated(&agent, scope, json),
+        Command::Aliases => alias::list(),
+        Command::Definitions { command } => match command {
+            DefinitionsCommand::Update => definitions::update(),
+            DefinitionsCommand::List => definitions::list(),
+        },
+        Command::Which { name, scope } => installed::which(&name, scope),
+        Command::Verify { name, scope } => installed::verify_installed(name.as_deref(), scope),
+        Command::Doctor { fix, scope } => doctor::doctor(scope, fix),
+        Command::Uninstall {
+            name,
+            dry_run,
+            scope,
+        } => uninstall::uninstall(&name, scope, dry_run),
+        Command::Switch { name, tag, scope } => versions::switch(&name, tag.as_deref(), scope),
+        Command::Rollback { name, scope } => rollback::rollback(&agent, &name, scope),
+        Command::History { name, scope } => history::history(&name, scope),
+        Command::Url { repo, select } => {
+            url::url(&agent, &alias::resolve(&repo)?, &select.into_options()?)
+        }
+        Command::CacheKey {
+            repo,
+            version_req,
+            select,
+        } => cache_key::cache_key(
+            &agent,
+            &alias::resolve(&repo)?,
+            version_req.as_ref(),
+            &select.into_options()?,
+        ),
+        Command::CheckAssets { repo, expect, tag } => {
+            check_assets::check_assets(&agent, &alias::resolve(&repo)?, tag.as_deref(), &expect)
+        }
+        Command::Bench {
+            repo,
+            connections,
+            si,
+            select,
+        } => {
+            let opts = InstallOptions {
+                si,
+                ..select.into_options()?
+            };
+            bench::bench(&agent, &alias::resolve(&repo)?, connections, &opts)
+        }
+        Command::SelfUpdate { tag, check } => {
+            self_update::self_update(&agent, tag.as_deref(), check)
+        }
+        Command::Schema { output } => schema::print(output),
+        Command::Run {
+            repo,
+            bin_name,
+            select,
+            args,
+        } => {
+            let resolved = alias::resolve(&repo)?;
+            let opts = InstallOptions {
+                bin_name: bin_name.or((repo != resolved).then_some(repo)),
+                ..select.into_options()?
+            };
+            exec::run(&agent, &resolved, opts, &args)
+        }
+        Command::Shims { scope } => shims::create(scope),
+        Command::Shim {
+            scope,
+            tool,
+            exe,
+            args,
+        } => shims::dispatch(&tool, &exe, scope, &args),
+        Command::CompleteTags { repo } => complete::tags(&agent, &alias::resolve(&repo)?),
+        Command::Config { command } => match command {
+            ConfigCommand::Check { manifests, online } => {
+                config_check::check(&agent, &manifests, online)
+            }
+            ConfigCommand::Get { key } => config_edit::get(&key),
+            ConfigCommand::Set { key, value } => config_edit::set(&key, &value),
+            ConfigCommand::Unset { key } => config_edit::unset(&key),
+            ConfigCommand::Edit => config_edit::edit(),
+        },
     }
-    #[cfg(unix)]
-    set_permissions(&out_path)?;
-    Ok(())
-}
-
-#[cfg(unix)]
-fn set_permissions(path: &Path) -> Result<()> {
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = fs::metadata(path)?.permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(path, perms)?;
-    }
-    Ok(())
 }
@@ -0,0 +1,222 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+use semver::VersionReq;
+use serde::Deserialize;
+
+use crate::{
+    channel::Channel, config::Config, hooks::ToolHooks, install::InstallOptions, select::Prefer,
+    tool_versions, verify,
+};
+
+/// Default manifest file name
+pub const FILE_NAME: &str = "grd.toml";
+
+/// A declarative list of tools to install, keyed by tool name
+///
+/// ```toml
+/// [tools.rg]
+/// repo = "BurntSushi/ripgrep"
+/// version = "^14"
+/// post_update = ["systemctl --user restart rg-server"]
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    pub tools: BTreeMap<String, ToolSpec>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ToolSpec {
+    /// GitHub repository (e.g., owner/repo)
+    pub repo: String,
+
+    /// Version to install. If omitted, uses latest
+    pub tag: Option<String>,
+
+    /// Semantic version requirement the installed release has to satisfy (e.g. `^14`)
+    pub version: Option<String>,
+
+    /// Release channel tracked when no tag is pinned (defaults to the repository's configured
+    /// channel, or stable)
+    pub channel: Option<Channel>,
+
+    /// Executable file name (defaults to the tool name)
+    pub bin_name: Option<String>,
+
+    /// Directory `grd sync` installs the tool into, relative to the manifest (defaults to the
+    /// scope's bin directory)
+    pub destination: Option<PathBuf>,
+
+    /// Path inside the archive of the file to install (exact or glob)
+    pub member: Option<String>,
+
+    /// Glob for the executable's name inside the archive
+    pub bin_pattern: Option<String>,
+
+    /// Several executables to install under their own names (`["all"]` for every executable)
+    #[serde(default)]
+    pub bins: Vec<String>,
+
+    /// Comma-separated list of words to exclude from asset matching
+    pub exclude: Option<String>,
+
+    /// Choose portable packages or installers when a release ships both
+    pub prefer: Option<Prefer>,
+
+    /// Expected digest of the asset (`sha256:<hex>`)
+    pub checksum: Option<String>,
+
+    /// Always select the first matching asset without prompting
+    #[serde(default)]
+    pub first: bool,
+
+    /// Keep the currently installed version when refreshing, without dropping the tag/channel
+    #[serde(default)]
+    pub hold: bool,
+
+    /// Commands run in the manifest's directory before the tool is installed or replaced
+    #[serde(default)]
+    pub pre_install: Vec<String>,
+
+    /// Commands run after the tool is installed for the first time
+    #[serde(default)]
+    pub post_install: Vec<String>,
+
+    /// Commands run after another version replaced the installed one, also by `grd update`
+    #[serde(default)]
+    pub post_update: Vec<String>,
+}
+
+impl ToolSpec {
+    /// The parsed `version` requirement, which excludes a `tag`
+    pub fn version_req(&self) -> Result<Option<VersionReq>> {
+        let Some(version) = &self.version else {
+            return Ok(None);
+        };
+        if self.tag.is_some() {
+            return Err(anyhow!("tag and version are mutually exclusive"));
+        }
+        VersionReq::parse(version)
+            .map(Some)
+            .map_err(|e| anyhow!("Invalid version requirement '{}': {}", version, e))
+    }
+
+    /// The entry's hooks, run in `dir`
+    pub fn hooks(&self, dir: &Path) -> ToolHooks {
+        ToolHooks {
+            pre_install: self.pre_install.clone(),
+            post_install: self.post_install.clone(),
+            post_update: self.post_update.clone(),
+            dir: Some(dir.to_path_buf()),
+        }
+    }
+
+    /// Install options for the tool `name`, with the keys configured for its repository
+    pub fn install_options(&self, name: &str, config: &Config) -> Result<InstallOptions> {
+        let mut opts = InstallOptions {
+            tag: self.tag.clone(),
+            version_req: self.version_req()?,
+            channel: self.channel,
+            first: self.first,
+            exclude: self.exclude.clone(),
+            bin_name: self.bin_name.clone(),
+            member: self.member.clone(),
+            bin_pattern: self.bin_pattern.clone(),
+            bins: self.bins.clone(),
+            prefer: self.prefer,
+            checksum: self
+                .checksum
+                .as_deref()
+                .map(verify::parse_checksum)
+                .transpose()?,
+            minisign_key: config.minisign_keys.get(&self.repo).cloned(),
+            gpg_key: config.gpg_keys.get(&self.repo).cloned(),
+            extractors: config.extractors.clone(),
+            paranoid: config.paranoid,
+            hold: self.hold,
+            ..InstallOptions::default()
+        };
+        config.repo(&self.repo).apply(&mut opts)?;
+        opts.bin_name.get_or_insert_with(|| name.to_string());
+        Ok(opts)
+    }
+}
+
+impl Manifest {
+    /// Load a TOML manifest, or a tool-version file (`.grd-tools`, `.tool-versions`)
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest {:?}", path))?;
+        if tool_versions::is_tool_versions(path) {
+            return tool_versions::parse(&text, path);
+        }
+        toml::from_str(&text).with_context(|| format!("Failed to parse manifest {:?}", path))
+    }
+
+    /// Every repository the manifest installs from, once
+    pub fn repos(&self) -> Vec<&str> {
+        let repos: BTreeSet<&str> = self.tools.values().map(|t| t.repo.as_str()).collect();
+        repos.into_iter().collect()
+    }
+}
+
+/// The nearest manifest or tool-version file in `dir` or its ancestors, up to the root of the
+/// Git repository `dir` is in
+///
+/// Within a directory, `grd.toml` wins over `.grd-tools`, which wins over `.tool-versions`.
+/// Manifests outside the repository are not picked up, since their hooks would run unasked.
+pub fn discover(dir: &Path) -> Result<PathBuf> {
+    for ancestor in dir.ancestors() {
+        for name in [FILE_NAME].into_iter().chain(tool_versions::FILE_NAMES) {
+            let path = ancestor.join(name);
+            if path.is_file() {
+                return Ok(path);
+            }
+        }
+        // `.git` is a file in worktrees and submodules
+        if ancestor.join(".git").exists() {
+            break;
+        }
+    }
+    Err(anyhow!(
+        "No {}, {} or {} found in {:?} or its parent directories up to the repository root",
+        FILE_NAME,
+        tool_versions::FILE_NAMES[0],
+        tool_versions::FILE_NAMES[1],
+        dir
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn discovery_stops_at_the_repository_root() {
+        let root = tempfile::tempdir().unwrap();
+        let repo = root.path().join("repo");
+        let nested = repo.join("crates/cli");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(&nested).unwrap();
+        // Above the repository, so never used from inside it
+        fs::write(root.path().join(FILE_NAME), "").unwrap();
+        assert!(discover(&nested).is_err());
+
+        fs::write(repo.join(".tool-versions"), "").unwrap();
+        assert_eq!(discover(&nested).unwrap(), repo.join(".tool-versions"));
+        fs::write(repo.join(FILE_NAME), "").unwrap();
+        assert_eq!(discover(&nested).unwrap(), repo.join(FILE_NAME));
+        fs::write(nested.join(".grd-tools"), "").unwrap();
+        assert_eq!(discover(&nested).unwrap(), nested.join(".grd-tools"));
+        assert_eq!(discover(root.path()).unwrap(), root.path().join(FILE_NAME));
+    }
+}
@@ -0,0 +1,126 @@
+//! Verification of minisign signatures, unless grd is built without the `minisign` feature
+
+#[cfg(feature = "minisign")]
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::Path,
+};
+
+use anyhow::{Result, anyhow};
+#[cfg(feature = "minisign")]
+use minisign_verify::{PublicKey, Signature};
+use ureq::Agent;
+
+#[cfg(feature = "minisign")]
+use crate::verify;
+use crate::{download::DownloadSource, release::Asset};
+
+/// A minisign signature of the asset and the public key it has to be made with
+#[cfg(feature = "minisign")]
+pub struct MinisignCheck {
+    key: PublicKey,
+    signature: Signature,
+    origin: String,
+}
+
+#[cfg(feature = "minisign")]
+impl MinisignCheck {
+    pub fn check(&self, asset_name: &str, source: &DownloadSource) -> Result<()> {
+        let result = match self.key.verify_stream(&self.signature) {
+            Ok(mut verifier) => {
+                match source {
+                    DownloadSource::Memory(bytes) => verifier.update(bytes),
+                    DownloadSource::Disk(temp_file) => {
+                        let mut file = File::open(temp_file.path())?;
+                        let mut buf = vec![0; 64 * 1024];
+                        loop {
+                            let n = file.read(&mut buf)?;
+                            if n == 0 {
+                                break;
+                            }
+                            verifier.update(&buf[..n]);
+                        }
+                    }
+                }
+                verifier.finalize()
+            }
+            // Signatures of old minisign versions sign the whole file rather than its hash
+            Err(minisign_verify::Error::UnsupportedLegacyMode) => {
+                let bytes = match source {
+                    DownloadSource::Memory(bytes) => bytes.clone(),
+                    DownloadSource::Disk(temp_file) => fs::read(temp_file.path())?,
+                };
+                self.key.verify(&bytes, &self.signature, true)
+            }
+            Err(e) => Err(e),
+        };
+        result.map_err(|e| anyhow!("Minisign signature check failed for {}: {}", asset_name, e))?;
+        eprintln!("Verified minisign signature {}", self.origin);
+        Ok(())
+    }
+}
+
+/// Fetch the `<asset>.minisig` signature published next to the asset, to be checked with `key`
+///
+/// `key` is a base64 public key, or the path of a `minisign.pub` file.
+#[cfg(feature = "minisign")]
+pub fn check(
+    agent: &Agent,
+    assets: &[Asset],
+    asset: &Asset,
+    key: &str,
+) -> Result<Option<MinisignCheck>> {
+    let key = parse_key(key)?;
+
+    let sig_name = format!("{}.minisig", asset.name);
+    let Some(sig_asset) = assets.iter().find(|a| a.name == sig_name) else {
+        return Ok(None);
+    };
+    let text = String::from_utf8(verify::fetch(agent, sig_asset)?)
+        .map_err(|_| anyhow!("Invalid signature {}", sig_name))?;
+    let signature =
+        Signature::decode(&text).map_err(|e| anyhow!("Invalid signature {}: {}", sig_name, e))?;
+    Ok(Some(MinisignCheck {
+        key,
+        signature,
+        origin: sig_name,
+    }))
+}
+
+/// Parse a minisign public key given as base64, or as the path of a `minisign.pub` file
+#[cfg(feature = "minisign")]
+pub fn parse_key(key: &str) -> Result<PublicKey> {
+    if Path::new(key).is_file() {
+        PublicKey::from_file(key)
+    } else {
+        PublicKey::from_base64(key.trim())
+    }
+    .map_err(|e| anyhow!("Invalid minisign public key: {}", e))
+}
+
+/// Stand-in for a check that can't be made without the `minisign` feature
+#[cfg(not(feature = "minisign"))]
+pub enum MinisignCheck {}
+
+#[cfg(not(feature = "minisign"))]
+impl MinisignCheck {
+    pub fn check(&self, _: &str, _: &DownloadSource) -> Result<()> {
+        match *self {}
+    }
+}
+
+#[cfg(not(feature = "minisign"))]
+pub fn check(_: &Agent, _: &[Asset], _: &Asset, _: &str) -> Result<Option<MinisignCheck>> {
+    Err(disabled())
+}
+
+#[cfg(not(feature = "minisign"))]
+pub fn parse_key(_: &str) -> Result<()> {
+    Err(disabled())
+}
+
+#[cfg(not(feature = "minisign"))]
+fn disabled() -> anyhow::Error {
+    anyhow!("This grd is built without minisign support (the `minisign` feature)")
+}
@@ -0,0 +1,361 @@
+//! Mirrors of repositories' releases, for air-gapped networks
+//!
+//! `grd mirror` lays releases out as the GitHub API paths grd requests, so that a plain static
+//! file server can serve the directory:
+//!
+//! ```text
+//! <dir>/index.json                                   what was mirrored, with asset digests
+//! <dir>/repos/<owner>/<repo>/releases/page/<n>       release list, 100 per page
+//! <dir>/repos/<owner>/<repo>/releases/latest         latest stable release
+//! <dir>/repos/<owner>/<repo>/releases/tags/<tag>     each release
+//! <dir>/download/<owner>/<repo>/<tag>/<asset>        the assets
+//! ```
+//!
+//! With `GRD_MIRROR` set to the URL the directory is served at, grd sends its requests for
+//! `api.github.com` and for release downloads from `github.com` there instead.
+
+use std::{collections::BTreeMap, env, fs, path::Path};
+
+use anyhow::{Context, Result, anyhow};
+use semver::VersionReq;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ureq::{
+    Agent, Body, SendBody,
+    http::{Request, Response, Uri},
+    middleware::MiddlewareNext,
+};
+
+use crate::{
+    install::{InstallOptions, file_sha256},
+    release::Release,
+    release_files, state,
+    units::format_size,
+    version,
+};
+
+/// Releases per page of the mirrored release list, the page size grd asks the API for
+const PER_PAGE: usize = 100;
+
+/// Which releases `grd mirror` copies
+pub struct Filter {
+    /// Semantic version range the tags have to be in
+    pub versions: Option<VersionReq>,
+    /// Glob the tags have to match
+    pub tags: Option<glob::Pattern>,
+    /// Number of the newest matching releases copied at most
+    pub limit: Option<usize>,
+    /// Glob the names of the copied assets have to match
+    pub assets: Option<glob::Pattern>,
+}
+
+/// `index.json` of a mirror
+#[derive(Serialize, Deserialize, Default)]
+struct Index {
+    repos: BTreeMap<String, MirroredRepo>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MirroredRepo {
+    /// Unix time of the last `grd mirror` of the repository
+    mirrored_at: u64,
+    releases: Vec<MirroredRelease>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MirroredRelease {
+    tag: String,
+    published_at: Option<String>,
+    prerelease: bool,
+    assets: Vec<MirroredAsset>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MirroredAsset {
+    name: String,
+    size: u64,
+    sha256: String,
+}
+
+/// Copy the releases of `repos` that `filter` picks, with their assets, into `dir`
+///
+/// Assets already present with the expected size are kept, so running it again only fetches
+/// what is new. The release list of each repository is replaced by the releases `filter` now picks.
+pub fn mirror(
+    agent: &Agent,
+    repos: &[String],
+    dir: &Path,
+    filter: &Filter,
+    si: bool,
+) -> Result<()> {
+    let index_path = dir.join("index.json");
+    let empty = dir
+        .read_dir()
+        .map_or(true, |mut entries| entries.next().is_none());
+    if !index_path.is_file() && !empty {
+        return Err(anyhow!(
+            "{:?} is neither empty nor a grd mirror (it has no index.json)",
+            dir
+        ));
+    }
+    let mut index: Index = match fs::read_to_string(&index_path) {
+        Ok(text) => serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse {:?}", index_path))?,
+        Err(_) => Index::default(),
+    };
+    // The mirror keeps its own copy of every asset
+    let opts = InstallOptions {
+        si,
+        cache: false,
+        ..Default::default()
+    };
+    for repo in repos {
+        let (releases, raw) = select(agent, repo, filter)?;
+        eprintln!("==> {}: {} release(s)", repo, releases.len());
+        let api = dir.join("repos").join(repo).join("releases");
+        let mut mirrored = Vec::with_capacity(releases.len());
+        for (release, raw) in releases.iter().zip(&raw) {
+            let downloads = dir.join("download").join(repo).join(&release.tag_name);
+            fs::create_dir_all(&downloads)
+                .with_context(|| format!("Failed to create {:?}", downloads))?;
+            let mut assets = Vec::new();
+            for asset in &release.assets {
+                let path = downloads.join(&asset.name);
+                let present = fs::metadata(&path).is_ok_and(|m| m.len() == asset.size);
+                if !present {
+                    eprintln!(
+                        "{} {} ({})",
+                        release.tag_name,
+                        asset.name,
+                        format_size(asset.size, si)
+                    );
+                    release_files::save(agent, asset, &downloads, &opts)?;
+                }
+                assets.push(MirroredAsset {
+                    name: asset.name.clone(),
+                    size: asset.size,
+                    sha256: file_sha256(&path)?,
+                });
+            }
+            write_json(&api.join("tags").join(&release.tag_name), raw)?;
+            mirrored.push(MirroredRelease {
+                tag: release.tag_name.clone(),
+                published_at: release.published_at.clone(),
+                prerelease: release.prerelease,
+                assets,
+            });
+        }
+
+        // Pages are rewritten from scratch, so that releases no longer selected disappear
+        let _ = fs::remove_dir_all(api.join("page"));
+        for (i, page) in raw.chunks(PER_PAGE).enumerate() {
+            write_json(&api.join("page").join((i + 1).to_string()), &page)?;
+        }
+        if raw.is_empty() {
+            write_json(&api.join("page").join("1"), &Vec::<Value>::new())?;
+        }
+        match releases.iter().position(|r| !r.prerelease) {
+            Some(latest) => write_json(&api.join("latest"), &raw[latest])?,
+            None => {
+                let _ = fs::remove_file(api.join("latest"));
+            }
+        }
+        index.repos.insert(
+            repo.clone(),
+            MirroredRepo {
+                mirrored_at: state::now(),
+                releases: mirrored,
+            },
+        );
+    }
+    write_json(&index_path, &index)?;
+    eprintln!(
+        "Mirrored {} repository(ies) into {:?}; serve it and set GRD_MIRROR to its URL",
+        repos.len(),
+        dir
+    );
+    Ok(())
+}
+
+/// The releases of `repo` that `filter` picks, newest first, parsed and as the API returned
+/// them; drafts are left out, and only the selected assets are kept
+fn select(agent: &Agent, repo: &str, filter: &Filter) -> Result<(Vec<Release>, Vec<Value>)> {
+    let mut releases = Vec::new();
+    let mut raw = Vec::new();
+    'pages: for page in 1.. {
+        let url = format!(
+            "https://api.github.com/repos/{}/releases?per_page={}&page={}",
+            repo, PER_PAGE, page
+        );
+        let mut response = agent.get(&url).call()?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Failed to fetch releases of {}: {}",
+                repo,
+                response.status()
+            ));
+        }
+        let batch: Vec<Value> = response.body_mut().read_json()?;
+        let last = batch.len() < PER_PAGE;
+        for mut value in batch {
+            if filter.limit.is_some_and(|limit| releases.len() >= limit) {
+                break 'pages;
+            }
+            let mut release: Release = serde_json::from_value(value.clone())?;
+            let in_range = match &filter.versions {
+                Some(req) => version::parse_tag(&release.tag_name).is_some_and(|v| req.matches(&v)),
+                None => true,
+            };
+            let matches = filter
+                .tags
+                .as_ref()
+                .is_none_or(|tags| tags.matches(&release.tag_name));
+            if release.draft || !in_range || !matches {
+                continue;
+            }
+            if let Some(assets) = &filter.assets {
+                release.assets.retain(|asset| assets.matches(&asset.name));
+                if let Some(Value::Array(list)) = value.get_mut("assets") {
+                    list.retain(|asset| {
+                        asset
+                            .get("name")
+                            .and_then(Value::as_str)
+                            .is_some_and(|name| assets.matches(name))
+                    });
+                }
+            }
+            releases.push(release);
+            raw.push(value);
+        }
+        if last {
+            break;
+        }
+    }
+    Ok((releases, raw))
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {:?}", parent))?;
+    }
+    fs::write(path, serde_json::to_string_pretty(value)?)
+        .with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// URL of the mirror grd reads releases from, from `GRD_MIRROR`
+pub fn base() -> Option<String> {
+    env::var("GRD_MIRROR")
+        .ok()
+        .map(|url| url.trim().trim_end_matches('/').to_string())
+        .filter(|url| !url.is_empty())
+}
+
+/// Middleware sending API requests and GitHub release downloads to the mirror at `base`
+pub fn redirect(
+    base: String,
+) -> impl Fn(Request<SendBody>, MiddlewareNext) -> Result<Response<Body>, ureq::Error>
++ Send
++ Sync
++ 'static {
+    move |mut request: Request<SendBody>, next: MiddlewareNext| {
+        if let Some(path) = mirrored_path(request.uri())
+            && let Ok(uri) = format!("{}{}", base, path).parse::<Uri>()
+        {
+            *request.uri_mut() = uri;
+        }
+        next.handle(request)
+    }
+}
+
+/// Path under the mirror of a request to GitHub, if it is one
+fn mirrored_path(uri: &Uri) -> Option<String> {
+    let path = uri.path();
+    match uri.host()? {
+        "api.github.com" if path.ends_with("/releases") => {
+            // Static files can't be paged by query, so pages are files of their own
+            let page = uri
+                .query()
+                .unwrap_or_default()
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("page="))
+                .unwrap_or("1");
+            Some(format!("{}/page/{}", path, page))
+        }
+        "api.github.com" => Some(path.to_string()),
+        "github.com" => {
+            // /<owner>/<repo>/releases/download/<tag>/<asset>
+            let (repo, rest) = path.split_once("/releases/download/")?;
+            Some(format!("/download{}/{}", repo, rest))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mirrored(url: &str) -> Option<String> {
+        mirrored_path(&url.parse::<Uri>().unwrap())
+    }
+
+    #[test]
+    fn release_lists_are_paged_into_files() {
+        assert_eq!(
+            mirrored("https://api.github.com/repos/owner/tool/releases?per_page=100&page=3")
+                .as_deref(),
+            Some("/repos/owner/tool/releases/page/3")
+        );
+        assert_eq!(
+            mirrored("https://api.github.com/repos/owner/tool/releases?per_page=100").as_deref(),
+            Some("/repos/owner/tool/releases/page/1")
+        );
+        assert_eq!(
+            mirrored("https://api.github.com/repos/owner/tool/releases").as_deref(),
+            Some("/repos/owner/tool/releases/page/1")
+        );
+    }
+
+    #[test]
+    fn releases_keep_their_api_paths() {
+        assert_eq!(
+            mirrored("https://api.github.com/repos/owner/tool/releases/latest").as_deref(),
+            Some("/repos/owner/tool/releases/latest")
+        );
+        assert_eq!(
+            mirrored("https://api.github.com/repos/owner/tool/releases/tags/v1.2.3").as_deref(),
+            Some("/repos/owner/tool/releases/tags/v1.2.3")
+        );
+    }
+
+    #[test]
+    fn downloads_move_under_download() {
+        assert_eq!(
+            mirrored(
+                "https://github.com/owner/tool/releases/download/v1.2.3/tool-linux-x86_64.tar.gz"
+            )
+            .as_deref(),
+            Some("/download/owner/tool/v1.2.3/tool-linux-x86_64.tar.gz")
+        );
+    }
+
+    #[test]
+    fn other_requests_are_left_alone() {
+        assert_eq!(
+            mirrored("https://github.com/owner/tool/archive/v1.2.3.tar.gz"),
+            None
+        );
+        assert_eq!(
+            mirrored("https://objects.githubusercontent.com/github-production-release-asset/1"),
+            None
+        );
+        assert_eq!(
+            mirrored("https://example.com/owner/tool/releases/download/v1/a"),
+            None
+        );
+        assert_eq!(
+            mirrored("https://api.github.com.evil.example/repos/a/b/releases/latest"),
+            None
+        );
+    }
+}
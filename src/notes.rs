@@ -0,0 +1,148 @@
+//! Release notes: the Markdown body of a release, shown in the terminal
+
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::LazyLock,
+};
+
+use anyhow::{Result, anyhow};
+use regex_lite::Regex;
+use ureq::Agent;
+
+use crate::{
+    release::Release,
+    version::{self, Scan},
+};
+
+static COMMENTS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<!--.*?-->").expect("valid regex"));
+static IMAGES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"!\[[^\]]*\]\([^)]*\)").expect("valid regex"));
+static LINKS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[([^\]]+)\]\(([^)\s]+)\)").expect("valid regex"));
+static EMPHASIS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\*\*([^*]+)\*\*|__([^_]+)__").expect("valid regex"));
+
+/// Print the notes of `release` to stderr, or say that it has none
+pub fn print(release: &Release) {
+    match release
+        .body
+        .as_deref()
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+    {
+        Some(body) => {
+            eprintln!("Release notes of {}:", release.tag_name);
+            eprintln!();
+            for line in render(body).lines() {
+                eprintln!("  {}", line);
+            }
+            eprintln!();
+        }
+        None => eprintln!("{} has no release notes", release.tag_name),
+    }
+}
+
+/// Print the notes of every release after `from` up to and including `to`, newest first
+///
+/// With semantic version tags, releases of other series published in between (a `1.x` patch
+/// while updating within `2.x`) are left out.
+pub fn changelog(agent: &Agent, repo: &str, from: &str, to: &str) -> Result<()> {
+    let range = version::parse_tag(from).zip(version::parse_tag(to));
+    let mut reached = false;
+    let releases = version::scan_releases(agent, repo, None, |release| {
+        if release.tag_name == from {
+            return Scan::Stop;
+        }
+        reached |= release.tag_name == to;
+        let between = match (&range, version::parse_tag(&release.tag_name)) {
+            (Some((from, to)), Some(version)) => *from < version && version <= *to,
+            (Some(_), None) => false,
+            (None, _) => reached,
+        };
+        if between && !release.draft {
+            Scan::Take
+        } else {
+            Scan::Skip
+        }
+    })?;
+    eprintln!(
+        "Changes in {} from {} to {} ({} release(s)):",
+        repo,
+        from,
+        to,
+        releases.len()
+    );
+    eprintln!();
+    for release in &releases {
+        print(release);
+    }
+    Ok(())
+}
+
+/// Ask whether to go on with `what`; an interactive answer is required
+pub fn confirm(what: &str) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "stdin is not a terminal to confirm {}; drop --confirm to proceed without asking",
+            what
+        ));
+    }
+    eprint!("Proceed with {}? [y/N] ", what);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
+
+/// Plain text for GitHub-flavored Markdown: headings underlined, emphasis, HTML comments and
+/// images dropped, and links followed by their URL
+pub fn render(markdown: &str) -> String {
+    let text = markdown.replace("\r\n", "\n");
+    let text = COMMENTS.replace_all(&text, "");
+    let text = IMAGES.replace_all(&text, "");
+    let text = LINKS.replace_all(&text, |caps: &regex_lite::Captures| {
+        if caps[1] == caps[2] {
+            caps[1].to_string()
+        } else {
+            format!("{} ({})", &caps[1], &caps[2])
+        }
+    });
+    let text = EMPHASIS.replace_all(&text, "$1$2");
+
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let heading = line.trim_start_matches('#');
+        if heading.len() < line.len() && (heading.is_empty() || heading.starts_with(' ')) {
+            let heading = heading.trim();
+            lines.push(heading.to_string());
+            lines.push("-".repeat(heading.chars().count()));
+        } else {
+            lines.push(line.trim_end().to_string());
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_underlines_headings_and_keeps_link_urls() {
+        let markdown = "## What's Changed\r\n<!-- hidden -->\r\n* **Fix** [#12](https://x/12) ![img](a.png)\r\n";
+        assert_eq!(
+            render(markdown),
+            "What's Changed\n--------------\n\n* Fix #12 (https://x/12)"
+        );
+    }
+
+    #[test]
+    fn render_shows_bare_links_once() {
+        assert_eq!(render("[https://x](https://x)"), "https://x");
+        assert_eq!(render("__bold__ #hashtag"), "bold #hashtag");
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use ureq::Agent;
+
+use crate::{
+    config::Config,
+    index,
+    state::Scope,
+    table::print_table,
+    update::{self, is_newer},
+};
+
+/// A recorded install behind the latest release of its repository, as printed by `--json` (see
+/// `grd schema outdated`)
+#[derive(Serialize)]
+struct Outdated<'a> {
+    name: &'a str,
+    repo: &'a str,
+    installed: &'a str,
+    latest: String,
+}
+
+/// Print the tools installed in `scope` that have a newer release upstream, as a table or JSON
+pub fn outdated(agent: &Agent, scope: Scope, json: bool) -> Result<()> {
+    let entries = index::load(scope)?;
+    if entries.is_empty() && !json {
+        eprintln!("No tools installed by grd");
+        return Ok(());
+    }
+    let config = Config::load()?;
+    let mut behind = Vec::new();
+    for entry in &entries {
+        let latest =
+            update::latest(agent, &entry.repo, &config.repo(&entry.repo), entry.channel)
+                .with_context(|| format!("Failed to check {} for a newer release", entry.name))?;
+        if is_newer(&latest, &entry.tag, entry.published_at.as_deref()) {
+            behind.push(Outdated {
+                name: &entry.name,
+                repo: &entry.repo,
+                installed: &entry.tag,
+                latest: latest.tag_name,
+            });
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&behind)?);
+        return Ok(());
+    }
+    if behind.is_empty() {
+        eprintln!("All {} installed tool(s) are up to date", entries.len());
+        return Ok(());
+    }
+    let rows: Vec<Vec<String>> = behind
+        .into_iter()
+        .map(|o| {
+            vec![
+                o.name.to_string(),
+                o.repo.to_string(),
+                o.installed.to_string(),
+                o.latest,
+            ]
+        })
+        .collect();
+    print_table(&["NAME", "REPO", "INSTALLED", "LATEST"], &rows);
+    Ok(())
+}
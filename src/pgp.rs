@@ -0,0 +1,418 @@
+//! Verification of detached OpenPGP signatures with rPGP, unless grd is built without the `pgp`
+//! feature
+//!
+//! A signature only counts when made by the primary key or a subkey of the trusted key file that
+//! a valid self-signature allows to sign, and that is neither expired nor revoked.
+
+use std::path::Path;
+#[cfg(feature = "pgp")]
+use std::{
+    fs,
+    time::{Duration, SystemTime},
+};
+
+#[cfg(feature = "pgp")]
+use ::pgp::{
+    composed::{Deserializable, DetachedSignature, SignedPublicKey, SignedPublicSubKey},
+    crypto::hash::HashAlgorithm,
+    packet::{Signature, SignatureType},
+    types::{KeyDetails, PublicParams, Tag},
+};
+use anyhow::{Result, anyhow};
+#[cfg(feature = "pgp")]
+use rsa::traits::PublicKeyParts;
+use ureq::Agent;
+
+#[cfg(feature = "pgp")]
+use crate::verify;
+use crate::{download::DownloadSource, release::Asset};
+
+/// Smallest RSA modulus accepted, in bits
+#[cfg(feature = "pgp")]
+const MIN_RSA_BITS: usize = 2048;
+
+/// A detached OpenPGP signature of the asset and the public keys it has to be made with
+#[cfg(feature = "pgp")]
+pub struct PgpCheck {
+    keys: Vec<SignedPublicKey>,
+    signatures: Vec<Signature>,
+    origin: String,
+}
+
+#[cfg(feature = "pgp")]
+impl PgpCheck {
+    pub fn check(&self, asset_name: &str, source: &DownloadSource) -> Result<()> {
+        let now = SystemTime::now();
+        let mut reason = anyhow!("not made by the key");
+        for signature in &self.signatures {
+            for key in &self.keys {
+                match verify_signature(key, signature, source, now) {
+                    Ok(()) => {
+                        eprintln!("Verified PGP signature {}", self.origin);
+                        return Ok(());
+                    }
+                    Err(e) => reason = e,
+                }
+            }
+        }
+        Err(anyhow!(
+            "PGP signature check failed for {}: {} is not a valid signature ({})",
+            asset_name,
+            self.origin,
+            reason
+        ))
+    }
+}
+
+/// Fetch the detached `<asset>.asc` (or `.sig`) signature published next to the asset, to be
+/// checked with the public key file `key` (armored or binary)
+#[cfg(feature = "pgp")]
+pub fn check(
+    agent: &Agent,
+    assets: &[Asset],
+    asset: &Asset,
+    key: &Path,
+) -> Result<Option<PgpCheck>> {
+    let keys = read_key(key)?;
+    let Some(sig_asset) = [".asc", ".sig"].iter().find_map(|ext| {
+        let name = format!("{}{}", asset.name, ext);
+        assets.iter().find(|a| a.name == name)
+    }) else {
+        return Ok(None);
+    };
+    let signatures = parse_signatures(&verify::fetch(agent, sig_asset)?)
+        .map_err(|e| anyhow!("Invalid signature {}: {}", sig_asset.name, e))?;
+    Ok(Some(PgpCheck {
+        keys,
+        signatures,
+        origin: sig_asset.name.clone(),
+    }))
+}
+
+/// The certificates of an OpenPGP public key file, armored or binary
+#[cfg(feature = "pgp")]
+pub fn read_key(path: &Path) -> Result<Vec<SignedPublicKey>> {
+    let data =
+        fs::read(path).map_err(|e| anyhow!("Failed to read PGP public key {:?}: {}", path, e))?;
+    parse_keys(&data).map_err(|e| anyhow!("Invalid PGP public key {:?}: {}", path, e))
+}
+
+#[cfg(feature = "pgp")]
+fn parse_keys(data: &[u8]) -> Result<Vec<SignedPublicKey>> {
+    let (keys, _) = SignedPublicKey::from_reader_many(data)?;
+    let keys = keys.collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err(anyhow!("no public key found"));
+    }
+    Ok(keys)
+}
+
+/// The signatures of binary documents in `data`, with a SHA-2 or SHA-3 hash
+#[cfg(feature = "pgp")]
+fn parse_signatures(data: &[u8]) -> Result<Vec<Signature>> {
+    let (signatures, _) = DetachedSignature::from_reader_many(data)?;
+    let mut usable = Vec::new();
+    let mut unusable = None;
+    for signature in signatures {
+        let signature = signature?.signature;
+        let reason = match (signature.typ(), signature.hash_alg()) {
+            (Some(SignatureType::Binary), Some(hash)) if strong_hash(hash) => {
+                usable.push(signature);
+                continue;
+            }
+            (Some(SignatureType::Binary), Some(hash)) => {
+                anyhow!("{} signatures are not accepted", hash)
+            }
+            (typ, _) => anyhow!(
+                "unsupported signature type {:?}, only signatures of binary documents are",
+                typ
+            ),
+        };
+        unusable.get_or_insert(reason);
+    }
+    if usable.is_empty() {
+        return Err(unusable.unwrap_or_else(|| anyhow!("no signature found")));
+    }
+    Ok(usable)
+}
+
+#[cfg(feature = "pgp")]
+fn strong_hash(hash: HashAlgorithm) -> bool {
+    matches!(
+        hash,
+        HashAlgorithm::Sha224
+            | HashAlgorithm::Sha256
+            | HashAlgorithm::Sha384
+            | HashAlgorithm::Sha512
+            | HashAlgorithm::Sha3_256
+            | HashAlgorithm::Sha3_512
+    )
+}
+
+/// Check `signature` of `source` with the key of `cert` that issued it, if it may sign at `now`
+#[cfg(feature = "pgp")]
+fn verify_signature(
+    cert: &SignedPublicKey,
+    signature: &Signature,
+    source: &DownloadSource,
+    now: SystemTime,
+) -> Result<()> {
+    let primary = &cert.primary_key;
+    if issued_by(signature, primary) {
+        let self_signature = primary_self_signature(cert, now)?;
+        if !self_signature.key_flags().sign() {
+            return Err(anyhow!("key {} may not sign", primary.fingerprint()));
+        }
+        check_strength(primary)?;
+        return signature
+            .verify(primary, source.reader()?)
+            .map_err(|e| anyhow!("{}", e));
+    }
+    let subkey = cert
+        .public_subkeys
+        .iter()
+        .find(|subkey| issued_by(signature, &subkey.key))
+        .ok_or_else(|| anyhow!("not made by the key"))?;
+    primary_self_signature(cert, now)?;
+    check_subkey(cert, subkey, now)?;
+    check_strength(&subkey.key)?;
+    signature
+        .verify(&subkey.key, source.reader()?)
+        .map_err(|e| anyhow!("{}", e))
+}
+
+/// Whether `signature` names `key` as its issuer, or names none
+#[cfg(feature = "pgp")]
+fn issued_by(signature: &Signature, key: &impl KeyDetails) -> bool {
+    let (ids, fingerprints) = (signature.issuer_key_id(), signature.issuer_fingerprint());
+    (ids.is_empty() && fingerprints.is_empty())
+        || ids.contains(&&key.legacy_key_id())
+        || fingerprints.contains(&&key.fingerprint())
+}
+
+/// The newest valid self-signature of the primary key, failing if it is revoked or expired
+#[cfg(feature = "pgp")]
+fn primary_self_signature(cert: &SignedPublicKey, now: SystemTime) -> Result<&Signature> {
+    let primary = &cert.primary_key;
+    if cert
+        .details
+        .revocation_signatures
+        .iter()
+        .any(|sig| sig.verify_key(primary).is_ok())
+    {
+        return Err(anyhow!("key {} is revoked", primary.fingerprint()));
+    }
+    let certifications = cert.details.users.iter().flat_map(|user| {
+        user.signatures.iter().filter(move |sig| {
+            sig.typ() != Some(SignatureType::CertRevocation)
+                && sig
+                    .verify_certification(primary, Tag::UserId, &user.id)
+                    .is_ok()
+        })
+    });
+    let direct = cert
+        .details
+        .direct_signatures
+        .iter()
+        .filter(|sig| sig.typ() == Some(SignatureType::Key) && sig.verify_key(primary).is_ok());
+    let self_signature = certifications
+        .chain(direct)
+        .max_by_key(|sig| sig.created())
+        .ok_or_else(|| anyhow!("key {} has no valid self-signature", primary.fingerprint()))?;
+    check_expiry(primary, self_signature, now)?;
+    Ok(self_signature)
+}
+
+/// Check that `subkey` is bound to the primary key for signing, and neither revoked nor expired
+#[cfg(feature = "pgp")]
+fn check_subkey(
+    cert: &SignedPublicKey,
+    subkey: &SignedPublicSubKey,
+    now: SystemTime,
+) -> Result<()> {
+    let primary = &cert.primary_key;
+    let fingerprint = subkey.key.fingerprint();
+    let (revocations, bindings): (Vec<_>, Vec<_>) = subkey
+        .signatures
+        .iter()
+        .filter(|sig| sig.verify_subkey_binding(primary, &subkey.key).is_ok())
+        .partition(|sig| sig.typ() == Some(SignatureType::SubkeyRevocation));
+    if !revocations.is_empty() {
+        return Err(anyhow!("subkey {} is revoked", fingerprint));
+    }
+    let binding = bindings
+        .into_iter()
+        .max_by_key(|sig| sig.created())
+        .ok_or_else(|| anyhow!("subkey {} is not bound to its key", fingerprint))?;
+    // A signing subkey has to prove it belongs to the primary key by signing it back
+    let backed = binding.embedded_signature().is_some_and(|back| {
+        back.verify_primary_key_binding(&subkey.key, primary)
+            .is_ok()
+    });
+    if !binding.key_flags().sign() || !backed {
+        return Err(anyhow!("subkey {} may not sign", fingerprint));
+    }
+    check_expiry(&subkey.key, binding, now)
+}
+
+/// Fail if the key expiration time `self_signature` gives `key` is past
+#[cfg(feature = "pgp")]
+fn check_expiry(key: &impl KeyDetails, self_signature: &Signature, now: SystemTime) -> Result<()> {
+    // A zero expiration time means that the key doesn't expire
+    if let Some(validity) = self_signature.key_expiration_time().map(Duration::from)
+        && !validity.is_zero()
+        && SystemTime::from(key.created_at()) + validity <= now
+    {
+        return Err(anyhow!("key {} has expired", key.fingerprint()));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "pgp")]
+fn check_strength(key: &impl KeyDetails) -> Result<()> {
+    if let PublicParams::RSA(params) = key.public_params()
+        && params.key.n().bits() < MIN_RSA_BITS
+    {
+        return Err(anyhow!(
+            "key {} is too weak, RSA keys need at least {} bits",
+            key.fingerprint(),
+            MIN_RSA_BITS
+        ));
+    }
+    Ok(())
+}
+
+/// Stand-in for a check that can't be made without the `pgp` feature
+#[cfg(not(feature = "pgp"))]
+pub enum PgpCheck {}
+
+#[cfg(not(feature = "pgp"))]
+impl PgpCheck {
+    pub fn check(&self, _: &str, _: &DownloadSource) -> Result<()> {
+        match *self {}
+    }
+}
+
+#[cfg(not(feature = "pgp"))]
+pub fn check(_: &Agent, _: &[Asset], _: &Asset, _: &Path) -> Result<Option<PgpCheck>> {
+    Err(disabled())
+}
+
+#[cfg(not(feature = "pgp"))]
+fn disabled() -> anyhow::Error {
+    anyhow!("This grd is built without OpenPGP support (the `pgp` feature)")
+}
+
+#[cfg(all(test, feature = "pgp"))]
+mod tests {
+    use ::pgp::ser::Serialize;
+
+    use super::*;
+
+    const DATA: &[u8] = include_bytes!("testdata/pgp/data");
+    const RSA_KEY: &[u8] = include_bytes!("testdata/pgp/rsa.asc");
+    const ED25519_KEY: &[u8] = include_bytes!("testdata/pgp/ed.asc");
+
+    fn check(key: &[u8], signature: &[u8], data: &[u8]) -> Result<()> {
+        let check = PgpCheck {
+            keys: parse_keys(key).unwrap(),
+            signatures: parse_signatures(signature).unwrap(),
+            origin: "data.asc".to_string(),
+        };
+        check.check("data", &DownloadSource::Memory(data.to_vec()))
+    }
+
+    fn verifies(key: &[u8], signature: &[u8], data: &[u8]) -> bool {
+        check(key, signature, data).is_ok()
+    }
+
+    fn rejection(key: &[u8], signature: &[u8]) -> String {
+        check(key, signature, DATA).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn verifies_rsa_signatures() {
+        let signature = include_bytes!("testdata/pgp/rsa.sig.asc");
+        assert!(verifies(RSA_KEY, signature, DATA));
+        assert!(!verifies(RSA_KEY, signature, b"hello grd!\n"));
+        assert!(!verifies(ED25519_KEY, signature, DATA));
+    }
+
+    #[test]
+    fn verifies_signatures_by_subkeys() {
+        // Made by the signing subkey, with SHA-512
+        let signature = include_bytes!("testdata/pgp/rsasub.sig.asc");
+        assert!(verifies(RSA_KEY, signature, DATA));
+    }
+
+    #[test]
+    fn verifies_binary_ed25519_signatures() {
+        let signature = include_bytes!("testdata/pgp/ed.sig");
+        assert!(verifies(ED25519_KEY, signature, DATA));
+        assert!(!verifies(ED25519_KEY, signature, b""));
+        assert!(!verifies(RSA_KEY, signature, DATA));
+    }
+
+    #[test]
+    fn verifies_binary_keys() {
+        let key = parse_keys(ED25519_KEY).unwrap()[0].to_bytes().unwrap();
+        assert!(verifies(&key, include_bytes!("testdata/pgp/ed.sig"), DATA));
+    }
+
+    #[test]
+    fn rejects_expired_keys() {
+        // Made in 2020, by a key that expired two days after its creation
+        let reason = rejection(
+            include_bytes!("testdata/pgp/expired.asc"),
+            include_bytes!("testdata/pgp/expired.sig"),
+        );
+        assert!(reason.contains("has expired"), "{}", reason);
+    }
+
+    #[test]
+    fn rejects_revoked_keys() {
+        let reason = rejection(
+            include_bytes!("testdata/pgp/revoked.asc"),
+            include_bytes!("testdata/pgp/revoked.sig"),
+        );
+        assert!(reason.contains("is revoked"), "{}", reason);
+    }
+
+    #[test]
+    fn rejects_keys_not_allowed_to_sign() {
+        // Made before the key's usage was changed to certification only
+        let reason = rejection(
+            include_bytes!("testdata/pgp/certify.asc"),
+            include_bytes!("testdata/pgp/certify.sig"),
+        );
+        assert!(reason.contains("may not sign"), "{}", reason);
+    }
+
+    #[test]
+    fn rejects_weak_rsa_keys() {
+        let reason = rejection(
+            include_bytes!("testdata/pgp/weak.asc"),
+            include_bytes!("testdata/pgp/weak.sig"),
+        );
+        assert!(reason.contains("too weak"), "{}", reason);
+    }
+
+    #[test]
+    fn rejects_unsupported_signatures() {
+        let sha1 = parse_signatures(include_bytes!("testdata/pgp/ed-sha1.asc"));
+        assert!(sha1.is_err_and(|e| e.to_string().contains("SHA1")));
+        let text = parse_signatures(include_bytes!("testdata/pgp/ed-text.asc"));
+        assert!(text.is_err_and(|e| e.to_string().contains("type")));
+    }
+
+    #[test]
+    fn rejects_corrupt_keys() {
+        let mut key = RSA_KEY.to_vec();
+        // A flipped base64 character in the modulus of the primary key
+        let i = key.windows(2).position(|w| w == b"\n\n").unwrap() + 100;
+        key[i] = if key[i] == b'A' { b'B' } else { b'A' };
+        let signature = include_bytes!("testdata/pgp/rsa.sig.asc");
+        assert!(parse_keys(&key).is_err() || !verifies(&key, signature, DATA));
+        assert!(parse_keys(b"not a key").is_err());
+    }
+}
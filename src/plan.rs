@@ -0,0 +1,451 @@
+//! What to take from an archive and where to put it, with the checks keeping every write inside
+//! the destination
+
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Result, anyhow};
+
+/// Which archive entries to install and where
+pub struct ExtractPlan {
+    pub dest_dir: PathBuf,
+    /// Executable name as requested
+    pub bin_name: String,
+    /// Executable name as searched in archives (with `.exe` on Windows)
+    pub exe_name: String,
+    pub selection: Selection,
+    /// Number of leading path components removed from extracted entries
+    pub strip_components: usize,
+    /// Directory receiving man pages and shell completions found next to the executables
+    pub companions: Option<PathBuf>,
+}
+
+pub enum Selection {
+    /// The single executable whose path ends with the executable name
+    Binary,
+    /// The single entry whose path matches a pattern, installed as the executable name
+    Member(glob::Pattern),
+    /// The single entry whose trailing path components match a pattern, installed as the executable name
+    Pattern(glob::Pattern),
+    /// The single file whose name matches a wildcard executable name, installed under its
+    /// normalized name
+    Wildcard(glob::Pattern),
+    /// Every file with one of these names, each installed under its own name
+    Bins(Vec<String>),
+    /// Every executable file, each installed under its own name
+    Executables,
+    /// Every entry, keeping the archive's directory structure
+    All,
+}
+
+impl ExtractPlan {
+    pub fn new(dest_dir: PathBuf, bin_name: &str, selection: Selection) -> Self {
+        let exe_name = if cfg!(windows) {
+            format!("{}.exe", bin_name)
+        } else {
+            bin_name.to_string()
+        };
+        Self {
+            dest_dir,
+            bin_name: bin_name.to_string(),
+            exe_name,
+            selection,
+            strip_components: 0,
+            companions: None,
+        }
+    }
+
+    pub fn companions(mut self, share_dir: PathBuf) -> Self {
+        self.companions = Some(share_dir);
+        self
+    }
+
+    /// Whether `out_path` is a man page or completion rather than an executable
+    pub fn is_companion(&self, out_path: &Path) -> bool {
+        self.companions
+            .as_ref()
+            .is_some_and(|dir| out_path.starts_with(dir))
+    }
+
+    pub fn strip_components(mut self, n: usize) -> Self {
+        self.strip_components = n;
+        self
+    }
+
+    /// Where to write an archive entry, or `None` to skip it
+    pub fn target(&self, entry_path: &str) -> Option<PathBuf> {
+        if let Some(share_dir) = &self.companions
+            && let Some(relative) = companion_path(entry_path)
+        {
+            return Some(share_dir.join(relative));
+        }
+        match self.selection {
+            Selection::Binary => entry_path
+                .ends_with(self.exe_name.as_str())
+                .then(|| self.dest_dir.join(&self.exe_name)),
+            Selection::Member(ref pattern) => {
+                let entry_path = entry_path.trim_start_matches("./");
+                pattern
+                    .matches_with(entry_path, GLOB_OPTIONS)
+                    .then(|| self.dest_dir.join(&self.exe_name))
+            }
+            Selection::Pattern(ref pattern) => {
+                matches_trailing(pattern, entry_path).then(|| self.dest_dir.join(&self.exe_name))
+            }
+            Selection::Wildcard(ref pattern) => {
+                let name = entry_file_name(entry_path)?;
+                pattern
+                    .matches(name)
+                    .then(|| self.dest_dir.join(normalized_exe_name(name)))
+            }
+            Selection::Bins(ref names) => {
+                let name = entry_file_name(entry_path)?;
+                names
+                    .iter()
+                    .any(|n| n == name)
+                    .then(|| self.dest_dir.join(name))
+            }
+            Selection::Executables => entry_file_name(entry_path).map(|n| self.dest_dir.join(n)),
+            Selection::All => {
+                let relative = relative_entry_path(entry_path)?;
+                let stripped: PathBuf = relative.components().skip(self.strip_components).collect();
+                // Entries consisting only of stripped components (e.g. the top directory) are skipped
+                (!stripped.as_os_str().is_empty()).then(|| self.dest_dir.join(stripped))
+            }
+        }
+    }
+
+    /// Reject entries that would be written outside the destination in whole-archive mode
+    ///
+    /// Selected executables are always written to a fixed name, so only whole archives need this.
+    pub fn check_entry(&self, entry_path: &str) -> Result<()> {
+        if matches!(self.selection, Selection::All) && !is_safe_entry_path(entry_path) {
+            return Err(anyhow!(
+                "Refusing to extract '{}': it points outside the destination",
+                entry_path
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reject symlinks at `out_path` whose target resolves outside the destination, since later
+    /// entries could be written through them
+    pub fn check_link(&self, out_path: &Path, link_target: &Path) -> Result<()> {
+        let parent = out_path
+            .parent()
+            .and_then(|p| p.strip_prefix(&self.dest_dir).ok())
+            .ok_or_else(|| anyhow!("Invalid link path {:?}", out_path))?;
+        let mut depth = parent.components().count();
+        for component in link_target.components() {
+            match component {
+                Component::Normal(_) => depth += 1,
+                Component::CurDir => {}
+                Component::ParentDir if depth > 0 => depth -= 1,
+                _ => {
+                    return Err(anyhow!(
+                        "Refusing to extract symlink {:?} -> {:?}: it points outside the destination",
+                        out_path,
+                        link_target
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject writing `out_path` in whole-archive mode when a directory on the way resolves
+    /// outside the destination, like a symlink an earlier entry left, and replace a symlink at
+    /// `out_path` itself instead of writing through it
+    ///
+    /// `check_link` only sees each link on its own; chained links (`a -> .`, then `a/b -> ..`)
+    /// are caught here, on the actual file system.
+    pub fn check_write(&self, out_path: &Path) -> Result<()> {
+        if !matches!(self.selection, Selection::All) {
+            return Ok(());
+        }
+        self.check_parents(out_path)?;
+        if fs::symlink_metadata(out_path).is_ok_and(|m| m.is_symlink()) {
+            fs::remove_file(out_path)?;
+        }
+        Ok(())
+    }
+
+    /// Reject `out_path` in whole-archive mode when one of its existing parent directories
+    /// resolves outside the destination
+    pub fn check_parents(&self, out_path: &Path) -> Result<()> {
+        if !matches!(self.selection, Selection::All) {
+            return Ok(());
+        }
+        let relative = out_path
+            .strip_prefix(&self.dest_dir)
+            .map_err(|_| anyhow!("Invalid entry path {:?}", out_path))?;
+        // Nothing can be in the way before the destination exists
+        let Ok(root) = fs::canonicalize(&self.dest_dir) else {
+            return Ok(());
+        };
+        let mut path = self.dest_dir.clone();
+        let mut parents = relative.components();
+        parents.next_back();
+        for component in parents {
+            path.push(component);
+            let inside = match fs::canonicalize(&path) {
+                Ok(resolved) => resolved.starts_with(&root),
+                // A dangling symlink would be followed when creating the directory
+                Err(_) => fs::symlink_metadata(&path).is_err(),
+            };
+            if !inside {
+                return Err(anyhow!(
+                    "Refusing to extract {:?}: {:?} leads outside the destination",
+                    out_path,
+                    path
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Name to save a non-archive asset under; wildcard names have to match the asset itself
+    pub fn raw_name(&self, filename: &str) -> Result<String> {
+        match self.selection {
+            Selection::Wildcard(ref pattern) if pattern.matches(filename) => {
+                Ok(normalized_exe_name(filename))
+            }
+            Selection::Wildcard(ref pattern) => Err(anyhow!(
+                "Asset '{}' does not match '{}'",
+                filename,
+                pattern.as_str()
+            )),
+            _ => Ok(self.exe_name.clone()),
+        }
+    }
+
+    /// Whether extraction stops at the first selected entry
+    pub fn is_single(&self) -> bool {
+        self.companions.is_none()
+            && matches!(
+                self.selection,
+                Selection::Binary
+                    | Selection::Member(_)
+                    | Selection::Pattern(_)
+                    | Selection::Wildcard(_)
+            )
+    }
+
+    /// Whether selected entries are executables rather than a whole archive tree
+    pub fn is_executable_selection(&self) -> bool {
+        !matches!(self.selection, Selection::All)
+    }
+
+    /// Whether to pass over a selected entry: executables have to be regular files, of which
+    /// only the first with a given name is installed
+    pub fn rejects(
+        &self,
+        out_path: &Path,
+        is_file: bool,
+        executable: bool,
+        installed: &[PathBuf],
+    ) -> bool {
+        if !self.is_executable_selection() {
+            return false;
+        }
+        !is_file
+            || installed.iter().any(|p| p == out_path)
+            || (matches!(self.selection, Selection::Executables)
+                && !executable
+                && !self.is_companion(out_path))
+    }
+
+    /// Fail when nothing (or not every requested executable) was selected from the archive
+    pub fn check_found(&self, installed: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+        if let Selection::Bins(ref names) = self.selection {
+            let missing: Vec<&str> = names
+                .iter()
+                .filter(|n| !installed.contains(&self.dest_dir.join(n)))
+                .map(String::as_str)
+                .collect();
+            if !missing.is_empty() {
+                return Err(anyhow!(
+                    "Executable(s) not found in archive: {}",
+                    missing.join(", ")
+                ));
+            }
+        }
+        // Companion files alone don't make an install
+        if installed.iter().any(|p| !self.is_companion(p)) {
+            return Ok(installed);
+        }
+        match self.selection {
+            Selection::Binary => Err(anyhow!(
+                "Executable '{}' not found in archive",
+                self.exe_name
+            )),
+            Selection::Member(ref pattern)
+            | Selection::Pattern(ref pattern)
+            | Selection::Wildcard(ref pattern) => {
+                Err(anyhow!("No archive member matches '{}'", pattern.as_str()))
+            }
+            Selection::Bins(_) | Selection::Executables => {
+                Err(anyhow!("No executables found in archive"))
+            }
+            Selection::All => Err(anyhow!("No entries found in archive")),
+        }
+    }
+}
+
+/// Globs never match across '/', so `*` stays within one path component
+const GLOB_OPTIONS: glob::MatchOptions = glob::MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Whether some trailing components of a file entry's path match `pattern`, like `Path::ends_with`
+fn matches_trailing(pattern: &glob::Pattern, entry_path: &str) -> bool {
+    if entry_path.ends_with('/') {
+        return false;
+    }
+    let components: Vec<&str> = entry_path.split('/').filter(|c| !c.is_empty()).collect();
+    (0..components.len()).any(|i| pattern.matches_with(&components[i..].join("/"), GLOB_OPTIONS))
+}
+
+/// Where a man page or shell completion entry goes under `share/`, or `None` for other entries
+///
+/// Man pages are `name.N` (optionally gzipped) files, sorted into `man/manN/`; completions are
+/// `.bash`/`.fish`/`.zsh` files or anything in a `complete`/`completions`/`autocomplete` directory.
+fn companion_path(entry_path: &str) -> Option<PathBuf> {
+    let name = entry_file_name(entry_path)?;
+    let in_completion_dir = entry_path.split('/').rev().skip(1).any(|dir| {
+        matches!(
+            dir.to_lowercase().as_str(),
+            "complete" | "completion" | "completions" | "autocomplete"
+        )
+    });
+    let (stem, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+    if in_completion_dir || matches!(ext, "bash" | "fish" | "zsh") {
+        return Some(Path::new("completions").join(name));
+    }
+
+    let (stem, section) = match ext {
+        "gz" => stem.rsplit_once('.')?,
+        _ => (stem, ext),
+    };
+    let mut chars = section.chars();
+    let digit = chars.next().filter(|c| ('1'..='9').contains(c))?;
+    // Versioned names like tool-1.2 or libtool.so.1 are not man pages
+    let is_man = section.len() <= 3
+        && chars.all(|c| c.is_ascii_lowercase())
+        && !stem.is_empty()
+        && !stem.ends_with(|c: char| c.is_ascii_digit())
+        && !stem.ends_with(".so");
+    is_man.then(|| Path::new("man").join(format!("man{}", digit)).join(name))
+}
+
+/// Whether an executable name is a glob to search archives with
+pub fn is_wildcard(name: &str) -> bool {
+    name.contains(['*', '?', '['])
+}
+
+/// Executable name without version, platform and `.exe` suffixes (re-added on Windows),
+/// e.g. `kubectl-v1.30.0-linux-amd64` -> `kubectl`
+fn normalized_exe_name(file_name: &str) -> String {
+    const PLATFORM_WORDS: &[&str] = &[
+        "linux", "darwin", "macos", "osx", "apple", "windows", "win32", "win64", "x86", "x64",
+        "amd64", "arm64", "aarch64", "i386", "i686", "unknown", "musl", "gnu",
+    ];
+    let name = file_name
+        .strip_suffix(".exe")
+        .or_else(|| file_name.strip_suffix(".EXE"))
+        .unwrap_or(file_name);
+    let lower = name.to_lowercase();
+    let cut = name
+        .char_indices()
+        .filter(|&(i, c)| i > 0 && matches!(c, '-' | '_' | '.'))
+        .map(|(i, _)| i)
+        .find(|&i| {
+            let rest = &lower[i + 1..];
+            let word = rest.split(['-', '_', '.']).next().unwrap_or_default();
+            rest.trim_start_matches('v')
+                .starts_with(|c: char| c.is_ascii_digit())
+                || PLATFORM_WORDS.contains(&word)
+        });
+    let name = cut.map_or(name, |i| &name[..i]);
+    if cfg!(windows) {
+        format!("{}.exe", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Last component of a file entry's path, or `None` for directory entries
+fn entry_file_name(entry_path: &str) -> Option<&str> {
+    entry_path
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+}
+
+/// Whether an archive entry looks executable, from its Unix mode or its `.exe` extension
+pub fn is_executable(entry_path: &str, mode: Option<u32>) -> bool {
+    mode.is_some_and(|mode| mode & 0o111 != 0) || entry_path.to_lowercase().ends_with(".exe")
+}
+
+/// Whether an entry path stays below the extraction root (no absolute paths, prefixes or `..`)
+fn is_safe_entry_path(entry_path: &str) -> bool {
+    Path::new(entry_path)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// Relative path of an archive entry, or `None` if it is absolute or climbs out with `..`
+fn relative_entry_path(entry_path: &str) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in Path::new(entry_path).components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!relative.as_os_str().is_empty()).then_some(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_paths_stay_below_the_root() {
+        assert!(is_safe_entry_path("tool-1.0/bin/tool"));
+        assert!(is_safe_entry_path("./tool"));
+        assert!(!is_safe_entry_path("../tool"));
+        assert!(!is_safe_entry_path("tool/../../tool"));
+        assert!(!is_safe_entry_path("/usr/bin/tool"));
+    }
+
+    #[test]
+    fn relative_entry_paths() {
+        assert_eq!(
+            relative_entry_path("./tool-1.0/bin/tool"),
+            Some(PathBuf::from("tool-1.0/bin/tool"))
+        );
+        assert_eq!(relative_entry_path("./"), None);
+        assert_eq!(relative_entry_path("a/../../b"), None);
+        assert_eq!(relative_entry_path("/etc/passwd"), None);
+    }
+
+    #[test]
+    fn links_may_not_point_outside_the_destination() {
+        let plan = ExtractPlan::new(PathBuf::from("/dest"), "tool", Selection::All);
+        let link = Path::new("/dest/lib/tool");
+        assert!(plan.check_link(link, Path::new("../bin/tool")).is_ok());
+        assert!(plan.check_link(link, Path::new("./tool.1")).is_ok());
+        assert!(plan.check_link(link, Path::new("../../tool")).is_err());
+        assert!(plan.check_link(link, Path::new("/etc/passwd")).is_err());
+        assert!(
+            plan.check_link(Path::new("/dest/tool"), Path::new(".."))
+                .is_err()
+        );
+    }
+}
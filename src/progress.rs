@@ -0,0 +1,50 @@
+//! Download progress bars, drawn with indicatif unless grd is built without the `progress`
+//! feature
+
+#[cfg(feature = "progress")]
+pub use indicatif::ProgressBar;
+
+/// Progress bar of a download of `size` bytes, in decimal units with `si`
+#[cfg(feature = "progress")]
+pub fn download_bar(size: u64, si: bool) -> ProgressBar {
+    use indicatif::ProgressStyle;
+
+    let pb = ProgressBar::new(size);
+    let template = if si {
+        "[{elapsed_precise}] {bar:40.cyan/blue} {decimal_bytes}/{decimal_total_bytes} ({eta}) {msg}"
+    } else {
+        "[{elapsed_precise}] {bar:40.cyan/blue} {binary_bytes}/{binary_total_bytes} ({eta}) {msg}"
+    };
+    pb.set_style(
+        ProgressStyle::with_template(template)
+            .expect("valid constant template")
+            .progress_chars("#>–"),
+    );
+    pb
+}
+
+/// Stand-in drawing nothing, with the methods of indicatif's bar that grd uses
+#[cfg(not(feature = "progress"))]
+pub struct ProgressBar;
+
+#[cfg(not(feature = "progress"))]
+impl ProgressBar {
+    pub fn hidden() -> Self {
+        ProgressBar
+    }
+
+    pub fn set_message(&self, _: impl Into<String>) {}
+
+    pub fn inc(&self, _: u64) {}
+
+    pub fn reset(&self) {}
+
+    pub fn finish_with_message(&self, _: &'static str) {}
+
+    pub fn abandon(&self) {}
+}
+
+#[cfg(not(feature = "progress"))]
+pub fn download_bar(_: u64, _: bool) -> ProgressBar {
+    ProgressBar
+}
@@ -0,0 +1,62 @@
+//! Releases and assets as the GitHub API describes them
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct Release {
+    pub tag_name: String,
+    pub assets: Vec<Asset>,
+    /// Source code archive of the tag, for releases shipping no assets
+    #[serde(default)]
+    pub tarball_url: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub published_at: Option<String>,
+    #[serde(default)]
+    pub html_url: Option<String>,
+    /// Release notes, in Markdown
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Account that published the release
+    #[serde(default)]
+    pub author: Option<Author>,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Author {
+    pub login: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+    pub size: u64,
+    /// Digest computed by GitHub (`sha256:<hex>`), missing for assets uploaded before it was added
+    #[serde(default)]
+    pub digest: Option<String>,
+    /// Media type declared by the uploader, often just `application/octet-stream`
+    #[serde(default)]
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub download_count: u64,
+}
+
+/// The lowercase media type of a `Content-Type` value, without parameters
+pub fn media_type(content_type: &str) -> String {
+    let essence = content_type.split(';').next().unwrap_or_default();
+    essence.trim().to_ascii_lowercase()
+}
+
+/// Whether a media type is a document or image, which can't be an executable
+pub fn is_document(media_type: &str) -> bool {
+    matches!(
+        media_type,
+        "text/html" | "application/json" | "application/xml" | "text/xml" | "application/pdf"
+    ) || media_type.starts_with("image/")
+}
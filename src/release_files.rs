@@ -0,0 +1,78 @@
+//! Release assets saved as they are, without selecting or extracting anything
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+use ureq::Agent;
+
+use crate::{
+    download::download_asset, install::InstallOptions, release::Asset, resolve::resolve_release,
+    units::format_size, verify,
+};
+
+/// Download every asset of the release `opts` selects whose name matches `filter` into the
+/// destination, under their own names
+pub fn download_all(
+    agent: &Agent,
+    repo: &str,
+    opts: &InstallOptions,
+    filter: Option<&glob::Pattern>,
+) -> Result<()> {
+    let release = resolve_release(agent, repo, opts)?;
+    let assets: Vec<&Asset> = release
+        .assets
+        .iter()
+        .filter(|asset| filter.is_none_or(|filter| filter.matches(&asset.name)))
+        .collect();
+    if assets.is_empty() {
+        return Err(anyhow!(
+            "No asset of {} {} matches the filter",
+            repo,
+            release.tag_name
+        ));
+    }
+    let total: u64 = assets.iter().map(|asset| asset.size).sum();
+    eprintln!(
+        "Downloading {} asset(s) of {} {} ({})",
+        assets.len(),
+        repo,
+        release.tag_name,
+        format_size(total, opts.si)
+    );
+    fs::create_dir_all(&opts.destination)
+        .with_context(|| format!("Failed to create {:?}", opts.destination))?;
+    for asset in assets {
+        eprintln!("==> {}", asset.name);
+        let path = save(agent, asset, &opts.destination, opts)?;
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+/// Download `asset` into `dir` under its own name, checking the digest GitHub computed for it
+pub fn save(agent: &Agent, asset: &Asset, dir: &Path, opts: &InstallOptions) -> Result<PathBuf> {
+    let source = download_asset(agent, asset, opts)?;
+    let sha256 = source.sha256()?;
+    let expected = verify::Expectations {
+        digests: verify::api_digest(asset).into_iter().collect(),
+        ..Default::default()
+    };
+    expected.check(&asset.name, &source, &sha256)?;
+
+    check_name(&asset.name)?;
+    let path = dir.join(&asset.name);
+    source.write_to(&path)?;
+    Ok(path)
+}
+
+/// Fail for an asset name that can't be used as a file name of its own
+pub fn check_name(name: &str) -> Result<()> {
+    // Asset names come from the release, so don't let one point elsewhere
+    if name.contains(['/', '\\']) || name == ".." {
+        return Err(anyhow!("Refusing to save an asset named {:?}", name));
+    }
+    Ok(())
+}
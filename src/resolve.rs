@@ -0,0 +1,284 @@
+//! Resolving the release to install: by tag, version range, channel or pattern
+
+use anyhow::{Result, anyhow};
+use ureq::Agent;
+
+use crate::{
+    channel::{self, Channel},
+    info,
+    install::InstallOptions,
+    lockfile,
+    release::{Asset, Release},
+    select::matching_assets,
+    table::print_table,
+    token, version,
+};
+
+/// Which releases `grd releases` lists
+pub struct ReleaseFilter {
+    /// Glob the tag has to match
+    pub tag: Option<glob::Pattern>,
+    /// Earliest publication date, as YYYY-MM-DD
+    pub since: Option<String>,
+    /// Number of releases listed at most
+    pub limit: Option<usize>,
+}
+
+impl Default for ReleaseFilter {
+    fn default() -> Self {
+        ReleaseFilter {
+            tag: None,
+            since: None,
+            limit: Some(30),
+        }
+    }
+}
+
+/// Validate a YYYY-MM-DD date
+pub fn parse_date(date: &str) -> Result<String> {
+    humantime::parse_rfc3339_weak(&format!("{}T00:00:00", date))
+        .map_err(|_| anyhow!("Invalid date '{}', expected YYYY-MM-DD", date))?;
+    Ok(date.to_string())
+}
+
+/// List the newest releases `filter` selects, as a table or JSON
+pub fn list_releases(agent: &Agent, repo: &str, filter: &ReleaseFilter, json: bool) -> Result<()> {
+    let releases = version::scan_releases(agent, repo, filter.limit, |release| {
+        // RFC 3339 timestamps start with the date; drafts have none
+        let published = release.published_at.as_deref().and_then(|at| at.get(..10));
+        match (&filter.since, published) {
+            // The list is newest first, so older releases only follow
+            (Some(since), Some(published)) if published < since.as_str() => version::Scan::Stop,
+            _ if filter
+                .tag
+                .as_ref()
+                .is_some_and(|tag| !tag.matches(&release.tag_name)) =>
+            {
+                version::Scan::Skip
+            }
+            _ => version::Scan::Take,
+        }
+    })?;
+    if json {
+        let reports: Vec<info::ReleaseReport> = releases
+            .iter()
+            .map(|release| info::ReleaseReport::new(repo, release))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+        return Ok(());
+    }
+    let truncated = filter.limit.is_some_and(|limit| releases.len() == limit);
+
+    eprintln!("Available releases for {}:", repo);
+    let rows: Vec<Vec<String>> = releases
+        .iter()
+        .map(|rel| {
+            let status = match (rel.draft, rel.prerelease) {
+                (true, _) => "draft",
+                (false, true) => "prerelease",
+                (false, false) => "",
+            };
+            vec![
+                rel.tag_name.clone(),
+                // The date part of the RFC 3339 timestamp
+                rel.published_at
+                    .as_deref()
+                    .and_then(|at| at.get(..10))
+                    .unwrap_or("-")
+                    .to_string(),
+                status.to_string(),
+                rel.assets.len().to_string(),
+            ]
+        })
+        .collect();
+    print_table(&["TAG", "PUBLISHED", "STATUS", "ASSETS"], &rows);
+    if truncated {
+        eprintln!("Showing the newest releases only; pass --limit N or --all to see more");
+    }
+    Ok(())
+}
+
+/// The release to install from: the requested or latest one, or with `--fallback-previous` the
+/// newest of the recent releases that has an asset for the platform
+pub fn resolve_release(agent: &Agent, repo: &str, opts: &InstallOptions) -> Result<Release> {
+    // --prerelease stands for the channel of every prerelease
+    let channel = if opts.prerelease {
+        Channel::Pre
+    } else {
+        opts.channel.unwrap_or_default()
+    };
+    if let Some(req) = opts.version_req.as_ref().filter(|_| opts.tag.is_none()) {
+        return version::latest_matching(agent, repo, req);
+    }
+    if let Some(pattern) = opts.tag_pattern.as_ref().filter(|_| opts.tag.is_none()) {
+        return version::latest_by_pattern(agent, repo, pattern, channel != Channel::Stable);
+    }
+    if let Some(tag) = &opts.tag {
+        return fetch_release_info(agent, repo, Some(tag), false);
+    }
+    if opts.latest_by_version {
+        return version::latest_by_version(agent, repo, channel);
+    }
+    if opts.fallback_previous == 0 {
+        return channel::latest(agent, repo, channel);
+    }
+
+    let url = format!(
+        "https://api.github.com/repos/{}/releases?per_page=100",
+        repo
+    );
+    let mut response = agent.get(&url).call()?;
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Failed to fetch releases of {}: {}",
+            repo,
+            response.status()
+        ));
+    }
+    let releases: Vec<Release> = response.body_mut().read_json()?;
+    let mut candidates = releases
+        .into_iter()
+        .filter(|r| channel.accepts(r))
+        .take(opts.fallback_previous + 1);
+    let latest = candidates
+        .next()
+        .ok_or_else(|| anyhow!("No releases found for {}", repo))?;
+    if !matching_assets(&latest.assets, opts).is_empty() {
+        return Ok(latest);
+    }
+    for release in candidates {
+        if !matching_assets(&release.assets, opts).is_empty() {
+            eprintln!(
+                "No {}-{} asset in {}, falling back to {}",
+                opts.os, opts.arch, latest.tag_name, release.tag_name
+            );
+            return Ok(release);
+        }
+    }
+    Err(anyhow!(
+        "No {}-{} asset in {} of {} or in the {} release(s) before it",
+        opts.os,
+        opts.arch,
+        latest.tag_name,
+        repo,
+        opts.fallback_previous
+    ))
+}
+
+/// Fetch release information for a given tag or the latest release
+///
+/// GitHub's "latest" never points at a prerelease, so when prereleases are wanted the newest
+/// non-draft entry of the release list is used instead. Releases without assets are rejected.
+pub fn fetch_release_info(
+    agent: &Agent,
+    repo: &str,
+    tag: Option<&str>,
+    prerelease: bool,
+) -> Result<Release> {
+    let release = if tag.is_none() && prerelease {
+        let url = format!("https://api.github.com/repos/{}/releases", repo);
+        let releases: Vec<Release> = agent.get(&url).call()?.body_mut().read_json()?;
+        releases
+            .into_iter()
+            .find(|r| !r.draft)
+            .ok_or_else(|| anyhow!("No releases found for {}", repo))?
+    } else {
+        let url = match tag {
+            Some(t) => format!("https://api.github.com/repos/{}/releases/tags/{}", repo, t),
+            None => format!("https://api.github.com/repos/{}/releases/latest", repo),
+        };
+
+        let mut response = agent
+            .get(&url)
+            .config()
+            .http_status_as_error(false)
+            .build()
+            .call()?;
+        if response.status() == 404 {
+            // GitHub's latest release is never a prerelease, so repositories publishing only
+            // prereleases have none
+            if tag.is_none()
+                && let Ok(release) = fetch_release_info(agent, repo, None, true)
+                && release.prerelease
+            {
+                return Err(anyhow!(
+                    "{} only publishes prereleases (the newest is {}); pass --prerelease to install them",
+                    repo,
+                    release.tag_name
+                ));
+            }
+            return Err(anyhow!(
+                "Failed to fetch release info: {}",
+                token::not_found_hint(repo)
+            ));
+        }
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Failed to fetch release info: {}",
+                response.status()
+            ));
+        }
+        response.body_mut().read_json()?
+    };
+    if release.assets.is_empty() {
+        return Err(no_assets_error(agent, repo, &release));
+    }
+    Ok(release)
+}
+
+/// Explain that a release only has a tag, pointing at its source tarball and at the newest
+/// release that does have assets
+fn no_assets_error(agent: &Agent, repo: &str, release: &Release) -> anyhow::Error {
+    let mut message = format!(
+        "Release {} of {} has no assets, only the source code of the tag",
+        release.tag_name, repo
+    );
+    if let Some(url) = &release.tarball_url {
+        message.push_str(&format!(
+            "\nSource tarball: {} (extract it with --source --tag {})",
+            url, release.tag_name
+        ));
+    }
+    let url = format!("https://api.github.com/repos/{}/releases?per_page=30", repo);
+    let newest_with_assets = agent
+        .get(&url)
+        .call()
+        .ok()
+        .and_then(|mut response| response.body_mut().read_json::<Vec<Release>>().ok())
+        .and_then(|releases| {
+            releases
+                .into_iter()
+                .find(|r| !r.draft && !r.prerelease && !r.assets.is_empty())
+        });
+    if let Some(newest) = newest_with_assets {
+        message.push_str(&format!(
+            "\nThe newest release with assets is {} (use --tag {})",
+            newest.tag_name, newest.tag_name
+        ));
+    }
+    anyhow!(message)
+}
+
+/// The release asset a lockfile pins, failing if the release no longer has it as locked
+pub fn locked_asset(release: &Release, locked: &lockfile::LockedAsset) -> Result<Asset> {
+    let asset = release
+        .assets
+        .iter()
+        .find(|a| a.name == locked.name)
+        .ok_or_else(|| {
+            anyhow!(
+                "Release {} no longer has the locked asset {}",
+                release.tag_name,
+                locked.name
+            )
+        })?;
+    if asset.browser_download_url != locked.url {
+        return Err(anyhow!(
+            "The URL of {} changed since it was locked: {} (locked {})",
+            asset.name,
+            asset.browser_download_url,
+            locked.url
+        ));
+    }
+    Ok(asset.clone())
+}
@@ -0,0 +1,112 @@
+use anyhow::{Result, anyhow};
+use ureq::Agent;
+
+use crate::{
+    config::Config,
+    download::download_asset,
+    gha,
+    hooks::{Change, Event},
+    install::{InstallOptions, file_digests, install_asset, post_install},
+    release::Asset,
+    state::{self, Action, Receipt, Scope, State},
+    update::remove_stale_files,
+    verify::{Expectations, ExpectedDigest},
+    wrapper,
+};
+
+/// Reinstall the previously recorded version of a tool, verifying the recorded digest
+///
+/// Receipts keep a single previous version, so the version rolled back from becomes the new
+/// rollback target.
+pub fn rollback(agent: &Agent, name: &str, scope: Scope) -> Result<()> {
+    let mut state = State::load(scope)?;
+    let mut current = state
+        .tools
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("'{}' is not installed by grd", name))?;
+    let previous = current
+        .previous
+        .take()
+        .ok_or_else(|| anyhow!("No previous version of '{}' is recorded", name))?;
+    eprintln!("Rolling back {}: {} -> {}", name, current.tag, previous.tag);
+
+    // Re-download the exact asset rather than trusting files that may have been overwritten;
+    // versioned installs reuse the version directory if its files are intact
+    let asset = Asset {
+        name: previous.asset.clone(),
+        browser_download_url: previous.url.clone(),
+        size: previous.size,
+        digest: None,
+        content_type: None,
+        download_count: 0,
+    };
+    let config = Config::load()?;
+    let opts = InstallOptions {
+        destination: previous.destination.clone(),
+        extract_all: previous.extract_all,
+        extract_dir: previous.extract_dir.clone(),
+        strip_components: previous.strip_components,
+        member: previous.member.clone(),
+        bin_pattern: previous.bin_pattern.clone(),
+        bins: previous.bins.clone(),
+        layout: previous.layout,
+        ownership: previous.ownership,
+        chmod_dirs: !previous.no_chmod_dirs,
+        normalize_modes: previous.normalize_modes,
+        extractors: config.extractors.clone(),
+        paranoid: config.paranoid,
+        scope,
+        ..InstallOptions::default()
+    };
+    // The hooks of the manifest entry as it is now apply to the rollback as well
+    let hooks = current.hooks.clone();
+    let (from, to) = (current.tag.clone(), previous.tag.clone());
+    let (repo, destination) = (previous.repo.clone(), previous.destination.clone());
+    let change = Change {
+        tool: name,
+        repo: &repo,
+        from: Some(&from),
+        to: &to,
+        destination: &destination,
+    };
+    hooks.run(Event::PreInstall, &change)?;
+    let (files, _) = install_asset(
+        || download_asset(agent, &asset, &opts),
+        &asset,
+        &previous.bin_name,
+        &previous.tag,
+        &opts,
+        &Expectations {
+            digests: vec![ExpectedDigest::new(&previous.sha256, "install receipt")],
+            ..Expectations::default()
+        },
+    )?;
+    let files: Vec<_> = wrapper::wrap(files, &previous.tag, &config.wrappers, scope)?
+        .iter()
+        .map(std::path::absolute)
+        .collect::<std::io::Result<_>>()?;
+
+    let receipt = Receipt {
+        digests: file_digests(&files)?,
+        files,
+        installed_at: state::now(),
+        hooks: hooks.clone(),
+        previous: Some(Box::new(current.clone())),
+        ..*previous
+    };
+    // Remove files that only the newer version had
+    remove_stale_files(&current, &receipt)?;
+    state.log(name, Action::Rollback, &receipt);
+    state.tools.insert(name.to_string(), receipt);
+    state.save()?;
+    post_install(&config, &state.tools[name])?;
+    hooks.run(Event::PostUpdate, &change)?;
+
+    eprintln!("Rolled back '{}' to {}", name, state.tools[name].tag);
+    gha::notice(&format!(
+        "Rolled back {} to {}",
+        name, state.tools[name].tag
+    ));
+    Ok(())
+}
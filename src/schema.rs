@@ -0,0 +1,141 @@
+//! JSON Schemas of the `--json` outputs, printed by `grd schema <output>`
+//!
+//! Each schema's `$id` carries [`VERSION`], which is bumped whenever an output changes in a way
+//! that isn't backward compatible (a field removed, renamed or retyped). New fields can appear
+//! without a bump, so consumers should ignore fields they don't know.
+
+use anyhow::Result;
+use serde_json::{Value, json};
+
+/// Version of the JSON outputs
+pub const VERSION: u32 = 1;
+
+/// Commands with a `--json` output
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum Output {
+    /// `grd install --json`: the installed release and files
+    Install,
+    /// `grd outdated --json`: the installed tools behind their latest release
+    Outdated,
+    /// `grd list owner/repo --json`, `grd info --json` and `grd assets --json`: a release and its
+    /// assets
+    Release,
+    /// `grd releases --json`: the releases of a repository, newest first
+    Releases,
+}
+
+impl Output {
+    fn name(self) -> &'static str {
+        match self {
+            Output::Install => "install",
+            Output::Outdated => "outdated",
+            Output::Release => "release",
+            Output::Releases => "releases",
+        }
+    }
+
+    /// The JSON Schema (draft 2020-12) of the output
+    pub fn schema(self) -> Value {
+        let (description, body) = match self {
+            Output::Install => (
+                "Result of `grd install --json`",
+                json!({
+                    "type": "object",
+                    "required": ["name", "repo", "tag", "asset", "url", "sha256", "destination", "files"],
+                    "properties": {
+                        "name": { "type": "string", "description": "Tool name the install is recorded under" },
+                        "repo": { "type": "string", "description": "GitHub repository (owner/repo)" },
+                        "tag": { "type": "string", "description": "Tag of the installed release" },
+                        "asset": { "type": "string", "description": "Name of the downloaded asset" },
+                        "url": { "type": "string", "format": "uri", "description": "Download URL of the asset" },
+                        "sha256": { "type": "string", "pattern": "^[0-9a-f]{64}$", "description": "SHA-256 of the asset" },
+                        "destination": { "type": "string", "description": "Absolute destination directory" },
+                        "files": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Absolute paths of the files and directories written"
+                        }
+                    }
+                }),
+            ),
+            Output::Outdated => (
+                "Result of `grd outdated --json`: the installed tools behind the latest release of their repository",
+                json!({
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["name", "repo", "installed", "latest"],
+                        "properties": {
+                            "name": { "type": "string", "description": "Tool name" },
+                            "repo": { "type": "string", "description": "GitHub repository (owner/repo)" },
+                            "installed": { "type": "string", "description": "Installed tag" },
+                            "latest": { "type": "string", "description": "Tag of the latest release" }
+                        }
+                    }
+                }),
+            ),
+            Output::Release => (
+                "Result of `grd list owner/repo --json`, `grd info --json` and `grd assets --json`: a release and its assets",
+                release(),
+            ),
+            Output::Releases => (
+                "Result of `grd releases --json`: the releases of a repository, newest first",
+                json!({ "type": "array", "items": release() }),
+            ),
+        };
+        let mut schema = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "$id": format!(
+                "https://github.com/lucidfrontier45/grd/schema/v{}/{}.json",
+                VERSION,
+                self.name()
+            ),
+            "title": format!("grd {}", self.name()),
+            "description": description,
+        });
+        if let (Value::Object(schema), Value::Object(body)) = (&mut schema, body) {
+            schema.extend(body);
+        }
+        schema
+    }
+}
+
+/// Schema of a release object, shared by the release outputs
+fn release() -> Value {
+    json!({
+        "type": "object",
+        "required": ["repo", "tag", "name", "published_at", "author", "prerelease", "draft", "url", "assets"],
+        "properties": {
+            "repo": { "type": "string", "description": "GitHub repository (owner/repo)" },
+            "tag": { "type": "string", "description": "Tag of the release" },
+            "name": { "type": ["string", "null"], "description": "Title of the release" },
+            "published_at": { "type": ["string", "null"], "format": "date-time", "description": "Publication time, null for drafts" },
+            "author": { "type": ["string", "null"], "description": "Login of the account that published the release" },
+            "prerelease": { "type": "boolean", "description": "Whether the release is flagged as a prerelease" },
+            "draft": { "type": "boolean", "description": "Whether the release is an unpublished draft" },
+            "url": { "type": ["string", "null"], "format": "uri", "description": "Web page of the release" },
+            "assets": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["name", "size", "downloads", "url", "digest"],
+                    "properties": {
+                        "name": { "type": "string", "description": "File name of the asset" },
+                        "size": { "type": "integer", "minimum": 0, "description": "Size in bytes" },
+                        "downloads": { "type": "integer", "minimum": 0, "description": "Number of times the asset was downloaded" },
+                        "content_type": { "type": ["string", "null"], "description": "Media type declared by the uploader" },
+                        "url": { "type": "string", "format": "uri", "description": "Download URL of the asset" },
+                        "digest": { "type": ["string", "null"], "description": "Digest computed by GitHub (`sha256:<hex>`), null for older assets" },
+                        "matches": { "type": "boolean", "description": "Whether the asset matches the selected platform; only in `grd info --json` and `grd assets --json`" }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Print the schema of `output`
+pub fn print(output: Output) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(&output.schema())?);
+    Ok(())
+}
@@ -0,0 +1,357 @@
+//! Picking the asset of a release built for the selected platform
+
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    sync::mpsc,
+    thread,
+    time::Instant,
+};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::{install::InstallOptions, release::Asset, units::format_size};
+
+/// Kind of package to pick when a release offers both (typically on Windows)
+#[derive(clap::ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Prefer {
+    /// Archives or bare executables that run in place
+    Portable,
+    /// `.msi`/`.msix` packages and `setup.exe`-style installers
+    Installer,
+}
+
+/// Operating systems and architectures assets can be selected for
+const PLATFORMS: [(&str, &str); 6] = [
+    ("linux", "x86_64"),
+    ("linux", "aarch64"),
+    ("macos", "x86_64"),
+    ("macos", "aarch64"),
+    ("windows", "x86_64"),
+    ("windows", "aarch64"),
+];
+
+pub fn print_platforms() {
+    for (os, arch) in PLATFORMS {
+        println!("{}-{}", os, arch);
+    }
+}
+
+/// Parse and normalize an `os-arch` platform such as `linux-x86_64`
+pub fn parse_platform(platform: &str) -> Result<(String, String)> {
+    let (os, arch) = platform.split_once('-').ok_or_else(|| {
+        anyhow!(
+            "Invalid platform '{}'. Expected OS-ARCH, e.g. linux-x86_64",
+            platform
+        )
+    })?;
+    resolve_platform(Some(os), Some(arch))
+}
+
+/// Normalize explicit `--os`/`--arch` values, defaulting to the host platform
+pub fn resolve_platform(os: Option<&str>, arch: Option<&str>) -> Result<(String, String)> {
+    let os = os
+        .map(normalize_os)
+        .transpose()?
+        .unwrap_or_else(|| env::consts::OS.to_string());
+    let arch = arch
+        .map(normalize_arch)
+        .transpose()?
+        .unwrap_or_else(|| env::consts::ARCH.to_string());
+    Ok((os, arch))
+}
+
+fn normalize_os(input: &str) -> Result<String> {
+    let normalized = input.to_lowercase();
+    match normalized.as_str() {
+        "windows" | "macos" | "linux" => Ok(normalized),
+        _ => Err(anyhow!(
+            "Invalid OS '{}'. Supported: windows, macos, linux",
+            input
+        )),
+    }
+}
+
+fn normalize_arch(input: &str) -> Result<String> {
+    let normalized = input.to_lowercase();
+    match normalized.as_str() {
+        "x86_64" | "amd64" | "x64" => Ok("x86_64".to_string()),
+        "aarch64" | "arm64" => Ok("aarch64".to_string()),
+        _ => Err(anyhow!(
+            "Invalid architecture '{}'. Supported: x86_64 (aliases: amd64, x64), aarch64 (alias: arm64)",
+            input
+        )),
+    }
+}
+
+/// Assets built for the selected platform, narrowed down to the preferred kind of package
+pub fn matching_assets<'a>(assets: &'a [Asset], opts: &InstallOptions) -> Vec<&'a Asset> {
+    let (os, arch) = (opts.os.as_str(), opts.arch.as_str());
+    let blacklist: Vec<String> = opts.exclude.as_deref().map_or_else(Vec::new, |s| {
+        s.split(',').map(|w| w.trim().to_lowercase()).collect()
+    });
+
+    // A configured asset glob replaces the heuristics
+    let pattern = opts.asset_pattern.as_ref().and_then(|asset| {
+        let asset = asset.replace("{os}", os).replace("{arch}", arch);
+        glob::Pattern::new(&asset.to_lowercase()).ok()
+    });
+
+    let mut matches: Vec<&Asset> = assets
+        .iter()
+        .filter(|a| {
+            let name = a.name.to_lowercase();
+            if let Some(pattern) = &pattern {
+                return pattern.matches(&name) && !blacklist.iter().any(|b| name.contains(b));
+            }
+            let os_match = match os {
+                "windows" => {
+                    name.contains("windows")
+                        || name.contains("win64")
+                        || name.contains("pc-windows")
+                }
+                "macos" => {
+                    name.contains("apple-darwin")
+                        || name.contains("macos")
+                        || name.contains("darwin")
+                }
+                "linux" => name.contains("linux") || name.contains("unknown-linux"),
+                _ => false,
+            };
+            let arch_match = match arch {
+                "x86_64" => {
+                    name.contains("x86_64") || name.contains("amd64") || name.contains("x64")
+                }
+                "aarch64" => name.contains("aarch64") || name.contains("arm64"),
+                _ => false,
+            };
+            os_match && arch_match && !blacklist.iter().any(|b| name.contains(b))
+        })
+        .collect();
+
+    // Narrow down to the preferred kind of package, unless none of the matches is of that kind
+    if let Some(prefer) = opts.prefer {
+        let preferred: Vec<&Asset> = matches
+            .iter()
+            .copied()
+            .filter(|a| is_installer(&a.name) == (prefer == Prefer::Installer))
+            .collect();
+        if !preferred.is_empty() {
+            // Break ties between packages of that kind by format, so --prefer never prompts
+            let best = preferred.iter().map(|a| format_rank(&a.name, prefer)).min();
+            matches = preferred
+                .into_iter()
+                .filter(|a| Some(format_rank(&a.name, prefer)) == best)
+                .collect();
+        }
+    }
+
+    // When updating, the counterpart of the installed asset (same name but for the version) wins
+    if let Some(previous) = &opts.previous_asset {
+        let unversioned = |name: &str| name.replace(|c: char| c.is_ascii_digit(), "");
+        if let Some(same) = matches
+            .iter()
+            .copied()
+            .find(|a| unversioned(&a.name) == unversioned(previous))
+        {
+            matches = vec![same];
+        }
+    }
+    matches
+}
+
+pub fn select_asset(assets: &[Asset], opts: &InstallOptions) -> Result<Asset> {
+    let (os, arch, first) = (opts.os.as_str(), opts.arch.as_str(), opts.first);
+    let matches = matching_assets(assets, opts);
+
+    match matches.len() {
+        0 => Err(anyhow!("No matching asset found for {}-{}", os, arch)),
+        1 => Ok(matches[0].clone()),
+        _ => {
+            if first {
+                Ok(matches[0].clone())
+            } else if opts.prefer.is_some() {
+                let names: Vec<&str> = matches.iter().map(|a| a.name.as_str()).collect();
+                Err(anyhow!(
+                    "Several assets of the preferred kind match {}-{}: {}; pass --first or narrow the match with --exclude",
+                    os,
+                    arch,
+                    names.join(", ")
+                ))
+            } else if !io::stdin().is_terminal() {
+                Err(anyhow!(
+                    "Multiple assets match {}-{} and stdin is not a terminal to choose one; pass --first or narrow the match with --exclude",
+                    os,
+                    arch
+                ))
+            } else {
+                eprintln!("Multiple assets found. Select one:");
+                for (i, asset) in matches.iter().enumerate() {
+                    eprintln!(
+                        "{}. {} ({})",
+                        i + 1,
+                        asset.name,
+                        format_size(asset.size, opts.si)
+                    );
+                }
+                let lines = stdin_lines();
+                let deadline = opts.prompt_timeout.map(|t| Instant::now() + t);
+                loop {
+                    eprint!("Enter choice (1-{}): ", matches.len());
+                    io::stderr().flush().unwrap();
+                    let input = match deadline {
+                        Some(deadline) => {
+                            match lines
+                                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                            {
+                                Ok(line) => line,
+                                Err(mpsc::RecvTimeoutError::Timeout) => {
+                                    eprintln!();
+                                    eprintln!(
+                                        "No choice made in time, selecting {}",
+                                        matches[0].name
+                                    );
+                                    return Ok(matches[0].clone());
+                                }
+                                Err(mpsc::RecvTimeoutError::Disconnected) => None,
+                            }
+                        }
+                        None => lines.recv().ok().flatten(),
+                    };
+                    let input = input.ok_or_else(|| anyhow!("Failed to read input"))?;
+                    match input.trim().parse::<usize>() {
+                        Ok(n) if n >= 1 && n <= matches.len() => return Ok(matches[n - 1].clone()),
+                        _ => eprintln!(
+                            "Invalid choice. Enter a number between 1 and {}.",
+                            matches.len()
+                        ),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Read stdin line by line on a background thread, so the prompt can stop waiting for an answer
+///
+/// `None` is sent on end of input or a read error.
+fn stdin_lines() -> mpsc::Receiver<Option<String>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        loop {
+            let mut line = String::new();
+            let line = match io::stdin().read_line(&mut line) {
+                Ok(n) if n > 0 => Some(line),
+                _ => None,
+            };
+            let eof = line.is_none();
+            if tx.send(line).is_err() || eof {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Whether an asset is an installer (`.msi`, `setup.exe`, ...) rather than a portable package
+fn is_installer(filename: &str) -> bool {
+    let name = filename.to_lowercase();
+    [".msi", ".msix", ".msixbundle", ".appx"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+        || (name.ends_with(".exe") && (name.contains("setup") || name.contains("install")))
+}
+
+/// Rank of a package format among those of the preferred kind, lower being better
+fn format_rank(filename: &str, prefer: Prefer) -> usize {
+    const INSTALLERS: &[&[&str]] = &[&[".msi"], &[".msix"], &[".msixbundle"], &[".appx"]];
+    const PORTABLE: &[&[&str]] = &[
+        &[".zip"],
+        &[".tar.gz", ".tgz"],
+        &[".tar.xz", ".txz"],
+        &[".tar.zst", ".tzst"],
+        &[".tar.bz2", ".tbz2", ".tbz"],
+        &[".7z"],
+    ];
+    let name = filename.to_lowercase();
+    let formats = match prefer {
+        Prefer::Installer => INSTALLERS,
+        Prefer::Portable => PORTABLE,
+    };
+    // Anything else (setup.exe, bare executables) comes last
+    formats
+        .iter()
+        .position(|suffixes| suffixes.iter().any(|s| name.ends_with(s)))
+        .unwrap_or(formats.len())
+}
+
+pub fn is_appimage(filename: &str) -> bool {
+    filename.to_lowercase().ends_with(".appimage")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asset(name: &str) -> Asset {
+        Asset {
+            name: name.to_string(),
+            browser_download_url: format!("https://example.com/{}", name),
+            size: 0,
+            digest: None,
+            content_type: None,
+            download_count: 0,
+        }
+    }
+
+    fn windows(prefer: Prefer) -> InstallOptions {
+        InstallOptions {
+            os: "windows".to_string(),
+            arch: "x86_64".to_string(),
+            prefer: Some(prefer),
+            ..InstallOptions::default()
+        }
+    }
+
+    fn names(matches: Vec<&Asset>) -> Vec<&str> {
+        matches.into_iter().map(|a| a.name.as_str()).collect()
+    }
+
+    #[test]
+    fn preference_breaks_ties_by_format() {
+        let assets = [
+            asset("tool-windows-x86_64-setup.exe"),
+            asset("tool-windows-x86_64.msi"),
+            asset("tool-windows-x86_64.tar.gz"),
+            asset("tool-windows-x86_64.zip"),
+        ];
+        assert_eq!(
+            names(matching_assets(&assets, &windows(Prefer::Portable))),
+            ["tool-windows-x86_64.zip"]
+        );
+        assert_eq!(
+            names(matching_assets(&assets, &windows(Prefer::Installer))),
+            ["tool-windows-x86_64.msi"]
+        );
+    }
+
+    #[test]
+    fn remaining_ties_are_errors_instead_of_prompts() {
+        let assets = [
+            asset("tool-windows-x86_64.zip"),
+            asset("tool-windows-x86_64-debug.zip"),
+        ];
+        let err = select_asset(&assets, &windows(Prefer::Portable)).unwrap_err();
+        assert!(err.to_string().contains("tool-windows-x86_64-debug.zip"));
+        let opts = InstallOptions {
+            first: true,
+            ..windows(Prefer::Portable)
+        };
+        assert_eq!(
+            select_asset(&assets, &opts).unwrap().name,
+            "tool-windows-x86_64.zip"
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! Replacing the running grd executable with another release, installed by grd itself
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+use ureq::Agent;
+
+use crate::{
+    install::{InstallOptions, install},
+    resolve::fetch_release_info,
+    state,
+    update::is_newer,
+};
+
+/// Repository grd is released from
+const REPO: &str = "lucidfrontier45/grd";
+
+/// Install the latest release of grd (or `tag`) for the running platform, verified like any other
+/// install, over the current executable
+///
+/// With `check`, only report whether a newer release exists.
+pub fn self_update(agent: &Agent, tag: Option<&str>, check: bool) -> Result<()> {
+    let current = env!("CARGO_PKG_VERSION");
+    let (target, newer) = match tag {
+        Some(tag) => (tag.to_string(), true),
+        None => {
+            // Releases of grd are tagged with semantic versions, so no publication time is needed
+            let latest = fetch_release_info(agent, REPO, None, false)?;
+            let newer = is_newer(&latest, current, None);
+            (latest.tag_name, newer)
+        }
+    };
+    if !newer {
+        eprintln!("grd {} is up to date", current);
+        return Ok(());
+    }
+    if check {
+        println!("grd {} -> {}", current, target);
+        return Ok(());
+    }
+
+    // Replace the file behind a symlink, not the symlink
+    let exe =
+        fs::canonicalize(env::current_exe()?).context("Failed to locate the running executable")?;
+    let dir = exe.parent().unwrap_or(Path::new("."));
+    remove_leftover(&exe);
+    state::ensure_writable(dir)?;
+
+    // Install next to the executable, so that moving it into place is a rename
+    let staging = tempfile::Builder::new()
+        .prefix(".grd-update")
+        .tempdir_in(dir)
+        .with_context(|| format!("Failed to create a staging directory in {:?}", dir))?;
+    let opts = InstallOptions {
+        tag: Some(target.clone()),
+        bin_name: Some("grd".to_string()),
+        destination: staging.path().to_path_buf(),
+        first: true,
+        ..InstallOptions::default()
+    };
+    let installed = install(agent, REPO, &opts)?;
+    let exe_name = exe.file_name().unwrap_or_default();
+    let new = installed
+        .files
+        .iter()
+        .find(|f| f.file_name() == Some(exe_name) && f.is_file())
+        .or_else(|| installed.files.iter().rev().find(|f| f.is_file()))
+        .ok_or_else(|| anyhow!("The {} release installed no executable", installed.tag))?;
+    replace(new, &exe)?;
+
+    println!("grd {} -> {}", current, installed.tag);
+    Ok(())
+}
+
+/// Move `new` over `exe` in one rename
+#[cfg(not(windows))]
+fn replace(new: &Path, exe: &Path) -> Result<()> {
+    fs::rename(new, exe).with_context(|| format!("Failed to replace {:?}", exe))
+}
+
+/// Windows can't overwrite a running executable, but can rename it: move it aside, move the new
+/// one in, and put it back if that fails
+#[cfg(windows)]
+fn replace(new: &Path, exe: &Path) -> Result<()> {
+    let old = leftover_path(exe);
+    let _ = fs::remove_file(&old);
+    fs::rename(exe, &old).with_context(|| format!("Failed to move {:?} aside", exe))?;
+    if let Err(e) = fs::rename(new, exe) {
+        let _ = fs::rename(&old, exe);
+        return Err(e).with_context(|| format!("Failed to replace {:?}", exe));
+    }
+    // Deleting the running executable fails; the next self-update removes it
+    let _ = fs::remove_file(&old);
+    Ok(())
+}
+
+/// Where the replaced executable is moved on Windows
+fn leftover_path(exe: &Path) -> PathBuf {
+    let mut name = exe.file_name().unwrap_or_default().to_os_string();
+    name.push(".old");
+    exe.with_file_name(name)
+}
+
+/// Remove the executable a previous self-update moved aside, now that it no longer runs
+fn remove_leftover(exe: &Path) {
+    let old = leftover_path(exe);
+    if old.is_file() {
+        let _ = fs::remove_file(old);
+    }
+}
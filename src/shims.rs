@@ -0,0 +1,297 @@
+//! Shims: small scripts in one directory that run the version of a tool a project pins
+//!
+//! Each executable of a tool installed with `--layout versioned` gets a shim calling back into
+//! `grd __shim`, which looks for the nearest `.grd-tools` or `.tool-versions` file naming the
+//! tool's repository and runs the matching version, or the selected (`current`) one otherwise.
+//! Putting the shims directory first on `PATH` once is enough for every tool.
+
+use std::{
+    collections::BTreeSet,
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+use semver::VersionReq;
+
+use crate::{
+    Layout, exec, index,
+    manifest::ToolSpec,
+    state::{self, Scope, State},
+    tool_versions, version, versions,
+    wrapper::sh_quote,
+};
+
+/// Directory holding the shims of `scope`
+pub fn dir(scope: Scope) -> Result<PathBuf> {
+    Ok(std::path::absolute(scope.data_dir()?.join("shims"))?)
+}
+
+/// Create the shims directory and write a shim for every executable of the versioned installs
+pub fn create(scope: Scope) -> Result<()> {
+    let dir = dir(scope)?;
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let state = State::load(scope)?;
+    let count = refresh(&state)?;
+    eprintln!("Wrote {} shim(s) to {:?}", count, dir);
+    if !state::on_path(&dir) {
+        eprintln!("Put {:?} first on PATH to use them", dir);
+    }
+    Ok(())
+}
+
+/// Rewrite the shims of `state`'s scope, removing those of tools that are gone; does nothing
+/// until the shims directory was created by `grd shims`
+///
+/// Returns the number of shims.
+pub fn refresh(state: &State) -> Result<usize> {
+    let dir = dir(state.scope)?;
+    if !dir.is_dir() {
+        return Ok(0);
+    }
+    let grd = env::current_exe()
+        .and_then(fs::canonicalize)
+        .context("Failed to locate the grd executable")?;
+    write(&dir, &grd, state)
+}
+
+/// Write into `dir` a shim calling `grd` for every executable of `state`'s versioned installs,
+/// and remove the others
+fn write(dir: &Path, grd: &Path, state: &State) -> Result<usize> {
+    let mut written = BTreeSet::new();
+    for (name, receipt) in &state.tools {
+        if receipt.layout != Layout::Versioned {
+            continue;
+        }
+        for file in receipt
+            .files
+            .iter()
+            .filter(|f| versions::is_version_file(f))
+        {
+            let Some(exe) = file.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let path = shim_path(dir, exe);
+            fs::write(&path, render(grd, state.scope, name, exe))
+                .with_context(|| format!("Failed to write shim {:?}", path))?;
+            #[cfg(unix)]
+            crate::install::set_permissions(&path)?;
+            written.insert(path);
+        }
+    }
+    // The directory belongs to grd, so anything else in it is a stale shim
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !written.contains(&path) {
+            fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+        }
+    }
+    Ok(written.len())
+}
+
+/// Run executable `exe` of the versioned install `tool`, in the version pinned for the current
+/// directory or else the selected one
+pub fn dispatch(tool: &str, exe: &str, scope: Scope, args: &[OsString]) -> Result<()> {
+    let repo = index::load(scope)?
+        .into_iter()
+        .find(|entry| entry.name == tool)
+        .map(|entry| entry.repo)
+        .ok_or_else(|| {
+            anyhow!(
+                "'{}' is not installed by grd; run `grd shims` to refresh",
+                tool
+            )
+        })?;
+    let (present, selected) = versions::versions(scope, tool)?;
+    // `latest` pins nothing
+    let pin = pin(&repo)?.filter(|(_, spec)| spec.tag.is_some() || spec.version.is_some());
+    let version = match pin {
+        Some((file, spec)) => pinned(&present, &spec).ok_or_else(|| {
+            anyhow!(
+                "{} pins {} to {}, which is not installed; install it with `grd install {} --bin-name {} --tag <tag> --layout versioned`",
+                file.display(),
+                repo,
+                spec.tag.or(spec.version).unwrap_or_default(),
+                repo,
+                tool
+            )
+        })?,
+        None => selected.ok_or_else(|| anyhow!("No version of '{}' is selected", tool))?,
+    };
+    exec::exec(
+        &versions::tool_dir(scope, tool)?.join(version).join(exe),
+        args,
+    )
+}
+
+/// The entry for `repo` in the nearest tool-version file that has one, with that file
+fn pin(repo: &str) -> Result<Option<(PathBuf, ToolSpec)>> {
+    let cwd = env::current_dir()?;
+    for dir in cwd.ancestors() {
+        for name in tool_versions::FILE_NAMES {
+            let path = dir.join(name);
+            let Ok(text) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let manifest = tool_versions::parse(&text, &path)?;
+            if let Some(spec) = manifest
+                .tools
+                .into_values()
+                .find(|spec| spec.repo.eq_ignore_ascii_case(repo))
+            {
+                return Ok(Some((path, spec)));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// The version directory among `present` that `spec` asks for: its exact tag, or the highest
+/// version matching its requirement
+fn pinned(present: &[String], spec: &ToolSpec) -> Option<String> {
+    if let Some(tag) = &spec.tag {
+        let dir = tag.replace(['/', '\\'], "_");
+        return present.contains(&dir).then_some(dir);
+    }
+    let req = VersionReq::parse(spec.version.as_deref()?).ok()?;
+    present
+        .iter()
+        .filter_map(|dir| Some((version::parse_tag(dir)?, dir)))
+        .filter(|(version, _)| req.matches(version))
+        .max()
+        .map(|(_, dir)| dir.clone())
+}
+
+/// Where the shim of `exe` goes: a `.cmd` script on Windows
+fn shim_path(dir: &Path, exe: &str) -> PathBuf {
+    if cfg!(windows) {
+        dir.join(format!("{}.cmd", exe.strip_suffix(".exe").unwrap_or(exe)))
+    } else {
+        dir.join(exe)
+    }
+}
+
+fn render(grd: &Path, scope: Scope, tool: &str, exe: &str) -> String {
+    let scope = match scope {
+        Scope::User => "user",
+        Scope::System => "system",
+    };
+    if cfg!(windows) {
+        format!(
+            "@echo off\r\nrem Generated by grd\r\n\"{}\" __shim --scope {} \"{}\" \"{}\" -- %*\r\n",
+            grd.display(),
+            scope,
+            tool,
+            exe
+        )
+    } else {
+        format!(
+            "#!/bin/sh\n# Generated by grd\nexec {} __shim --scope {} {} {} -- \"$@\"\n",
+            sh_quote(&grd.to_string_lossy()),
+            scope,
+            sh_quote(tool),
+            sh_quote(exe)
+        )
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn spec(tag: Option<&str>, version: Option<&str>) -> ToolSpec {
+        ToolSpec {
+            tag: tag.map(str::to_string),
+            version: version.map(str::to_string),
+            ..ToolSpec::default()
+        }
+    }
+
+    #[test]
+    fn pins_select_a_present_version() {
+        let present = ["13.0.0", "14.0.0", "14.1.1", "v15.0.0", "nightly_2024"].map(String::from);
+        for (spec, expected) in [
+            (spec(Some("14.0.0"), None), Some("14.0.0")),
+            (spec(Some("nightly/2024"), None), Some("nightly_2024")),
+            (spec(Some("12.0.0"), None), None),
+            (spec(None, Some("^14")), Some("14.1.1")),
+            (spec(None, Some(">=13")), Some("v15.0.0")),
+            (spec(None, Some("^16")), None),
+            (spec(None, Some("not a requirement")), None),
+            (spec(None, None), None),
+        ] {
+            assert_eq!(pinned(&present, &spec).as_deref(), expected, "{:?}", spec);
+        }
+    }
+
+    #[test]
+    fn shims_call_back_into_grd() {
+        assert_eq!(
+            render(Path::new("/opt/grd"), Scope::User, "rg", "it's"),
+            "#!/bin/sh\n# Generated by grd\nexec '/opt/grd' __shim --scope user 'rg' 'it'\\''s' -- \"$@\"\n"
+        );
+    }
+
+    #[test]
+    fn shims_follow_the_versioned_installs() {
+        let root = tempfile::tempdir().unwrap();
+        let shims = root.path().join("shims");
+        let version = root.path().join("tools/rg/14.1.0");
+        fs::create_dir_all(&shims).unwrap();
+        fs::create_dir_all(&version).unwrap();
+        // The record marks the directory as a version directory
+        fs::write(version.join(".grd-version.json"), "{}").unwrap();
+        fs::write(shims.join("stale"), "").unwrap();
+
+        let mut state = State::default();
+        let receipt = |layout: &str, files: Vec<PathBuf>| {
+            serde_json::from_value(serde_json::json!({
+                "repo": "BurntSushi/ripgrep",
+                "tag": "14.1.0",
+                "asset": "rg.tar.gz",
+                "url": "https://example.com/rg.tar.gz",
+                "size": 1,
+                "sha256": "00",
+                "bin_name": "rg",
+                "destination": "/bin",
+                "extract_all": false,
+                "strip_components": 0,
+                "layout": layout,
+                "first": false,
+                "no_chmod_dirs": false,
+                "normalize_modes": false,
+                "files": files,
+                "hold": false,
+                "installed_at": 0
+            }))
+            .unwrap()
+        };
+        state.tools.insert(
+            "rg".to_string(),
+            // The link in the destination gets no shim
+            receipt(
+                "versioned",
+                vec![version.join("rg"), root.path().join("bin/rg")],
+            ),
+        );
+        state.tools.insert(
+            "fd".to_string(),
+            receipt("flat", vec![root.path().join("bin/fd")]),
+        );
+
+        assert_eq!(write(&shims, Path::new("/opt/grd"), &state).unwrap(), 1);
+        let names: Vec<_> = fs::read_dir(&shims)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["rg"]);
+        let script = fs::read_to_string(shims.join("rg")).unwrap();
+        assert!(
+            script.contains("__shim --scope user 'rg' 'rg'"),
+            "{}",
+            script
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! Source archives of tags, for repositories that publish no prebuilt assets
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use tempfile::NamedTempFile;
+use ureq::Agent;
+
+use crate::{
+    download::{DownloadSource, download_with_progress},
+    http,
+    install::{extract_and_save, file_sha256},
+    plan::{ExtractPlan, Selection},
+    progress::ProgressBar,
+    release::Release,
+    units::format_size,
+};
+
+#[derive(Deserialize)]
+struct Tag {
+    name: String,
+}
+
+/// Download the source tarball of `tag` (or of the latest release) and extract it into `dir`
+/// under `destination`, by default `<repo name>-<tag>`, without the archive's top directory
+///
+/// Returns the directory. Any tag works, whether or not a release was published for it.
+pub fn fetch(
+    agent: &Agent,
+    repo: &str,
+    tag: Option<&str>,
+    destination: &Path,
+    dir: Option<&Path>,
+    si: bool,
+    extractors: &BTreeMap<String, String>,
+) -> Result<PathBuf> {
+    let tag = match tag {
+        Some(tag) => tag.to_string(),
+        None => latest_tag(agent, repo)?,
+    };
+    let name = repo.split('/').next_back().unwrap_or(repo);
+    let dir = match dir {
+        Some(dir) => destination.join(dir),
+        None => destination.join(format!("{}-{}", name, tag.replace(['/', '\\'], "_"))),
+    };
+    if dir
+        .read_dir()
+        .is_ok_and(|mut entries| entries.next().is_some())
+    {
+        return Err(anyhow!(
+            "{:?} already exists and is not empty; remove it or pass --extract-dir",
+            dir
+        ));
+    }
+
+    eprintln!("Downloading the source code of {} {}...", repo, tag);
+    let url = format!("https://api.github.com/repos/{}/tarball/{}", repo, tag);
+    // GitHub builds the archive on the fly, so its size isn't known up front
+    let pb = ProgressBar::hidden();
+    let mut response = http::get_with_retry(agent, &url, &pb)?;
+    let mut temp_file = NamedTempFile::new()?;
+    let received =
+        download_with_progress(&mut response.body_mut().as_reader(), &pb, |buf: &[u8]| {
+            temp_file.write_all(buf)
+        })?;
+    eprintln!(
+        "Downloaded {} (SHA-256 {})",
+        format_size(received, si),
+        file_sha256(temp_file.path())?
+    );
+
+    // The archive holds a single `<owner>-<repo>-<commit>/` directory
+    fs::create_dir_all(&dir)?;
+    let plan = ExtractPlan::new(dir.clone(), name, Selection::All).strip_components(1);
+    let archive = format!("{}-{}.tar.gz", name, tag);
+    extract_and_save(
+        DownloadSource::Disk(temp_file),
+        &archive,
+        &plan,
+        None,
+        false,
+        extractors,
+    )?;
+    eprintln!("Extracted the source code of {} {} to {:?}", repo, tag, dir);
+    Ok(dir)
+}
+
+/// Tag of the latest release, or the newest tag of a repository without releases
+fn latest_tag(agent: &Agent, repo: &str) -> Result<String> {
+    let url = format!("https://api.github.com/repos/{}/releases/latest", repo);
+    let mut response = agent
+        .get(&url)
+        .config()
+        .http_status_as_error(false)
+        .build()
+        .call()?;
+    if response.status().is_success() {
+        let release: Release = response.body_mut().read_json()?;
+        return Ok(release.tag_name);
+    }
+    let url = format!("https://api.github.com/repos/{}/tags?per_page=1", repo);
+    let tags: Vec<Tag> = agent.get(&url).call()?.body_mut().read_json()?;
+    tags.into_iter()
+        .next()
+        .map(|tag| tag.name)
+        .ok_or_else(|| anyhow!("{} has neither releases nor tags", repo))
+}
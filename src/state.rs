@@ -0,0 +1,431 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::{Layout, channel::Channel, hooks::ToolHooks, index, select::Prefer, shims};
+
+/// Schema version written to the state file
+const STATE_VERSION: u32 = 1;
+
+/// Everything grd has installed in one scope, keyed by tool name
+#[derive(Serialize, Deserialize, Debug)]
+pub struct State {
+    pub version: u32,
+    /// Scope the state was loaded from, and is saved back to
+    #[serde(skip)]
+    pub scope: Scope,
+    #[serde(default)]
+    pub tools: BTreeMap<String, Receipt>,
+    /// Every install-related event, oldest first
+    #[serde(default)]
+    pub history: Vec<Event>,
+}
+
+/// One entry of the install history
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Event {
+    pub tool: String,
+    pub action: Action,
+    pub tag: String,
+    pub asset: String,
+    /// Unix timestamp of the event
+    pub at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Install,
+    Update,
+    Reinstall,
+    Rollback,
+    Switch,
+    Uninstall,
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Action::Install => "install",
+            Action::Update => "update",
+            Action::Reinstall => "reinstall",
+            Action::Rollback => "rollback",
+            Action::Switch => "switch",
+            Action::Uninstall => "uninstall",
+        };
+        f.pad(s)
+    }
+}
+
+/// What one install placed where, and how to reproduce it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Receipt {
+    pub repo: String,
+    pub tag: String,
+    /// Publication time of the release, which orders tags that aren't semantic versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_at: Option<String>,
+    pub asset: String,
+    pub url: String,
+    pub size: u64,
+    /// SHA-256 of the downloaded asset
+    pub sha256: String,
+    pub bin_name: String,
+    pub destination: PathBuf,
+    #[serde(default)]
+    pub extract_all: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extract_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub strip_components: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub member: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bin_pattern: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bins: Vec<String>,
+    #[serde(default)]
+    pub layout: Layout,
+    /// Release channel the tool tracks, which `update` resolves the latest release from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<Channel>,
+    /// Platform the asset was selected for; missing in older receipts, meaning the host's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset_pattern: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefer: Option<Prefer>,
+    #[serde(default)]
+    pub first: bool,
+    /// Glob naming the checksum asset; digests pinned with --checksum only hold for one release
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum_asset: Option<String>,
+    /// UID and GID the installed files were given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ownership: Option<(u32, u32)>,
+    #[serde(default)]
+    pub no_chmod_dirs: bool,
+    #[serde(default)]
+    pub normalize_modes: bool,
+    /// Files and directories written by the install
+    pub files: Vec<PathBuf>,
+    /// SHA-256 of each regular file written, to detect later modifications
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub digests: BTreeMap<PathBuf, String>,
+    /// Hooks of the manifest entry that installed the tool
+    #[serde(default, skip_serializing_if = "ToolHooks::is_empty")]
+    pub hooks: ToolHooks,
+    /// Held by its manifest entry: `update --all` skips it
+    #[serde(default)]
+    pub hold: bool,
+    /// Unix timestamp of the install
+    pub installed_at: u64,
+    /// The install this one replaced, kept for rollback
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<Box<Receipt>>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            scope: Scope::User,
+            tools: BTreeMap::new(),
+            history: Vec::new(),
+        }
+    }
+}
+
+impl State {
+    pub fn path(scope: Scope) -> Result<PathBuf> {
+        Ok(scope.data_dir()?.join("state.json"))
+    }
+
+    /// Load the state file of `scope`, or an empty state if nothing was installed yet
+    pub fn load(scope: Scope) -> Result<Self> {
+        let path = Self::path(scope)?;
+        if !path.exists() {
+            return Ok(Self {
+                scope,
+                ..Self::default()
+            });
+        }
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read state file {:?}", path))?;
+        let mut state: State = serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse state file {:?}", path))?;
+        if state.version > STATE_VERSION {
+            return Err(anyhow!(
+                "State file {:?} was written by a newer grd (schema version {})",
+                path,
+                state.version
+            ));
+        }
+        state.scope = scope;
+        Ok(state)
+    }
+
+    /// Write the state file atomically, in the current schema version
+    pub fn save(&mut self) -> Result<()> {
+        self.version = STATE_VERSION;
+        let path = Self::path(self.scope)?;
+        let dir = path.parent().ok_or_else(|| anyhow!("Invalid state path"))?;
+        ensure_writable(dir)?;
+        fs::create_dir_all(dir)?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, &path)?;
+        index::write(self)?;
+        shims::refresh(self)?;
+        Ok(())
+    }
+
+    /// Record an install of `name`, keeping the replaced version for rollback
+    pub fn record(&mut self, name: &str, mut receipt: Receipt) {
+        let action = match self.tools.remove(name) {
+            // Reinstalling the same version keeps the older rollback target
+            Some(old) if old.tag == receipt.tag => {
+                receipt.previous = old.previous;
+                Action::Reinstall
+            }
+            Some(mut old) => {
+                old.previous = None;
+                receipt.previous = Some(Box::new(old));
+                Action::Update
+            }
+            None => Action::Install,
+        };
+        self.log(name, action, &receipt);
+        self.tools.insert(name.to_string(), receipt);
+    }
+
+    /// Append an event about `receipt` to the history
+    pub fn log(&mut self, name: &str, action: Action, receipt: &Receipt) {
+        self.history.push(Event {
+            tool: name.to_string(),
+            action,
+            tag: receipt.tag.clone(),
+            asset: receipt.asset.clone(),
+            at: now(),
+        });
+    }
+}
+
+/// Whether tools are installed for the current user or for every user of the machine
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Scope {
+    /// `~/.local/bin`, tracked in the per-user data directory
+    #[default]
+    User,
+    /// `/usr/local/bin`, tracked in the machine-wide data directory (needs elevation)
+    System,
+}
+
+impl Scope {
+    /// Default destination for executables installed in this scope
+    pub fn bin_dir(self) -> Result<PathBuf> {
+        match self {
+            Scope::User if cfg!(windows) => Ok(local_app_data()?.join("grd").join("bin")),
+            Scope::User => Ok(home()?.join(".local/bin")),
+            Scope::System if cfg!(windows) => {
+                let programs = env::var_os("ProgramFiles")
+                    .ok_or_else(|| anyhow!("ProgramFiles is not set"))?;
+                Ok(PathBuf::from(programs).join("grd").join("bin"))
+            }
+            Scope::System => Ok(PathBuf::from("/usr/local/bin")),
+        }
+    }
+
+    /// Directory holding the state file of this scope
+    pub fn data_dir(self) -> Result<PathBuf> {
+        match self {
+            Scope::User => data_dir(),
+            Scope::System if cfg!(windows) => {
+                let program_data =
+                    env::var_os("ProgramData").ok_or_else(|| anyhow!("ProgramData is not set"))?;
+                Ok(PathBuf::from(program_data).join("grd"))
+            }
+            Scope::System => Ok(PathBuf::from("/var/lib/grd")),
+        }
+    }
+}
+
+/// A writable directory on `PATH` to install into without `-d`: the user bin directory,
+/// `~/bin` or `~/.cargo/bin`, in that order
+///
+/// Falls back to the user bin directory with a warning when none of them qualifies.
+pub fn path_bin_dir() -> Result<PathBuf> {
+    let bin_dir = Scope::User.bin_dir()?;
+    let mut candidates = vec![bin_dir.clone()];
+    if let Ok(home) = home() {
+        candidates.extend([home.join("bin"), home.join(".cargo").join("bin")]);
+    }
+    let found = candidates
+        .iter()
+        .find(|dir| dir.is_dir() && on_path(dir) && tempfile::tempfile_in(dir).is_ok());
+    match found {
+        Some(dir) => Ok(dir.clone()),
+        None => {
+            eprintln!(
+                "Warning: none of {} is a writable directory on PATH; installing to {:?}, add it to PATH to run the tools",
+                candidates
+                    .iter()
+                    .map(|dir| dir.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                bin_dir
+            );
+            Ok(bin_dir)
+        }
+    }
+}
+
+/// Whether `dir` is one of the directories of `PATH`
+pub fn on_path(dir: &Path) -> bool {
+    let canonical = fs::canonicalize(dir).ok();
+    env::split_paths(&env::var_os("PATH").unwrap_or_default()).any(|entry| {
+        entry == dir || (canonical.is_some() && fs::canonicalize(&entry).ok() == canonical)
+    })
+}
+
+/// Fail early with a hint to elevate when `dir` (or the ancestor it would be created in) is not writable
+pub fn ensure_writable(dir: &Path) -> Result<()> {
+    let existing = dir
+        .ancestors()
+        .find(|d| d.is_dir())
+        .ok_or_else(|| anyhow!("No existing parent of {:?}", dir))?;
+    match tempfile::tempfile_in(existing) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Err(anyhow!(
+            "{:?} is not writable; system-wide installs need elevation (re-run with sudo, or from an administrator prompt on Windows)",
+            existing
+        )),
+        Err(e) => Err(e).with_context(|| format!("{:?} is not writable", existing)),
+    }
+}
+
+/// Per-user data directory (`$GRD_DATA_DIR`, or the platform's local data directory)
+pub fn data_dir() -> Result<PathBuf> {
+    if let Some(dir) = env::var_os("GRD_DATA_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    if cfg!(windows) {
+        return Ok(local_app_data()?.join("grd"));
+    }
+    if let Some(xdg) = env::var_os("XDG_DATA_HOME").filter(|v| !v.is_empty()) {
+        return Ok(PathBuf::from(xdg).join("grd"));
+    }
+    Ok(home()?.join(".local/share/grd"))
+}
+
+/// Per-user cache directory (`$GRD_CACHE_DIR`, or the platform's cache directory), whose
+/// contents can be deleted at any time
+pub fn cache_dir() -> Result<PathBuf> {
+    if let Some(dir) = env::var_os("GRD_CACHE_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    if cfg!(windows) {
+        return Ok(local_app_data()?.join("grd").join("cache"));
+    }
+    if let Some(xdg) = env::var_os("XDG_CACHE_HOME").filter(|v| !v.is_empty()) {
+        return Ok(PathBuf::from(xdg).join("grd"));
+    }
+    Ok(home()?.join(".cache/grd"))
+}
+
+fn local_app_data() -> Result<PathBuf> {
+    env::var_os("LOCALAPPDATA")
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow!("LOCALAPPDATA is not set"))
+}
+
+pub fn home() -> Result<PathBuf> {
+    env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow!("HOME is not set"))
+}
+
+/// Current Unix timestamp in seconds
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receipt(tag: &str) -> serde_json::Value {
+        serde_json::json!({
+            "repo": "BurntSushi/ripgrep",
+            "tag": tag,
+            "asset": "rg.tar.gz",
+            "url": "https://example.com/rg.tar.gz",
+            "size": 1,
+            "sha256": "00",
+            "bin_name": "rg",
+            "destination": "/bin",
+            "extract_all": false,
+            "strip_components": 0,
+            "layout": "flat",
+            "first": false,
+            "no_chmod_dirs": false,
+            "normalize_modes": false,
+            "files": ["/bin/rg"],
+            "hold": false,
+            "installed_at": 0
+        })
+    }
+
+    #[test]
+    fn receipts_round_trip() {
+        let mut value = receipt("14.1.0");
+        let extra = serde_json::json!({
+            "published_at": "2024-01-06T00:00:00Z",
+            "channel": "rc",
+            "os": "linux",
+            "arch": "x86_64",
+            "exclude": "musl",
+            "prefer": "portable",
+            "checksum_asset": "{asset}.sha256",
+            "ownership": [0, 0],
+            "digests": { "/bin/rg": "11" },
+            "previous": receipt("14.0.0")
+        });
+        for (key, field) in extra.as_object().unwrap() {
+            value[key] = field.clone();
+        }
+        let parsed: Receipt = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), value);
+    }
+
+    #[test]
+    fn one_version_is_kept_for_rollback() {
+        let mut state = State::default();
+        for tag in ["13.0.0", "14.0.0", "14.1.0"] {
+            state.record("rg", serde_json::from_value(receipt(tag)).unwrap());
+        }
+        let previous = state.tools["rg"].previous.as_ref().unwrap();
+        assert_eq!(previous.tag, "14.0.0");
+        assert!(previous.previous.is_none());
+
+        // Reinstalling the current version keeps the rollback target
+        state.record("rg", serde_json::from_value(receipt("14.1.0")).unwrap());
+        let previous = state.tools["rg"].previous.as_ref().unwrap();
+        assert_eq!(previous.tag, "14.0.0");
+        assert_eq!(state.history.len(), 4);
+        assert_eq!(state.history[3].action, Action::Reinstall);
+    }
+}
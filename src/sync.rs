@@ -0,0 +1,255 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use ureq::Agent;
+
+use crate::{
+    config::Config,
+    gha,
+    hooks::{Change, Event},
+    install::{InstallOptions, install, post_install, receipt_for},
+    lockfile::{self, Lockfile},
+    manifest::{Manifest, ToolSpec},
+    resolve::resolve_release,
+    state::{self, Receipt, Scope, State},
+    token,
+    update::remove_stale_files,
+    version, wrapper,
+};
+
+/// Install or update every tool of a manifest in `scope`, skipping those already at the
+/// version the manifest resolves to
+///
+/// With `locked`, the releases and assets pinned in the manifest's lockfile are installed.
+pub fn sync(agent: &Agent, manifest_path: &Path, scope: Scope, locked: bool) -> Result<()> {
+    let manifest = Manifest::load(manifest_path)?;
+    let config = Config::load()?;
+    token::preflight(agent, &manifest.repos())?;
+    let lockfile = locked
+        .then(|| Lockfile::load(&lockfile::path_for(manifest_path)))
+        .transpose()?;
+    let base_dir = manifest_path.parent().unwrap_or(Path::new(""));
+    let hooks_dir = std::path::absolute(base_dir.join("."))?;
+    if scope == Scope::System {
+        state::ensure_writable(&scope.data_dir()?)?;
+    }
+
+    let mut state = State::load(scope)?;
+    let mut changed = 0;
+    for (name, spec) in &manifest.tools {
+        let destination = match &spec.destination {
+            Some(dir) => base_dir.join(dir),
+            None => scope.bin_dir()?,
+        };
+        let mut opts = InstallOptions {
+            destination: std::path::absolute(destination)?,
+            hooks: spec.hooks(&hooks_dir),
+            scope,
+            ..spec
+                .install_options(name, &config)
+                .with_context(|| format!("Invalid manifest entry '{}'", name))?
+        };
+        let current = state
+            .tools
+            .get(name)
+            .filter(|r| r.repo.eq_ignore_ascii_case(&spec.repo))
+            .cloned();
+        if record_hold(&mut state, name, current.as_ref(), spec.hold) {
+            state.save()?;
+        }
+        if spec.hold
+            && let Some(current) = &current
+        {
+            eprintln!("==> {} held at {}", name, current.tag);
+            continue;
+        }
+        if let Some(lockfile) = &lockfile {
+            pin(lockfile, name, spec, &mut opts)?;
+        }
+
+        let release = resolve_release(agent, &spec.repo, &opts)?;
+        if let Some(current) = current
+            .as_ref()
+            .filter(|c| c.tag == release.tag_name && c.destination == opts.destination)
+        {
+            eprintln!("==> {} is up to date ({})", name, current.tag);
+            continue;
+        }
+
+        eprintln!("==> {} ({}) {}", name, spec.repo, release.tag_name);
+        let change = Change {
+            tool: name,
+            repo: &spec.repo,
+            from: current.as_ref().map(|c| c.tag.as_str()),
+            to: &release.tag_name,
+            destination: &opts.destination,
+        };
+        opts.hooks.run(Event::PreInstall, &change)?;
+        let event = match current {
+            Some(_) => Event::PostUpdate,
+            None => Event::PostInstall,
+        };
+        opts.tag = Some(release.tag_name.clone());
+        opts.previous_asset = current.as_ref().map(|c| c.asset.clone());
+        let mut installed = install(agent, &spec.repo, &opts)?;
+        installed.files = wrapper::wrap(installed.files, &installed.tag, &config.wrappers, scope)?;
+        let receipt = receipt_for(&spec.repo, &installed, &opts)?;
+        if let Some(current) = &current {
+            remove_stale_files(current, &receipt)?;
+        }
+        state.record(name, receipt);
+        state.save()?;
+        post_install(&config, &state.tools[name])?;
+        opts.hooks.run(event, &change)?;
+        changed += 1;
+    }
+
+    eprintln!(
+        "Synced {} tool(s) from {:?}: {} installed or updated",
+        manifest.tools.len(),
+        manifest_path,
+        changed
+    );
+    if changed > 0 {
+        gha::notice(&format!("Synced {} tool(s)", changed));
+    }
+    Ok(())
+}
+
+/// Record the manifest's `hold` on the installed tool `current`, returning whether it changed
+///
+/// The manifest decides the hold `update --all` honours, also for tools left as they are.
+fn record_hold(state: &mut State, name: &str, current: Option<&Receipt>, hold: bool) -> bool {
+    if current.is_none_or(|c| c.hold == hold) {
+        return false;
+    }
+    let Some(receipt) = state.tools.get_mut(name) else {
+        return false;
+    };
+    receipt.hold = hold;
+    true
+}
+
+/// Install the release and asset `lockfile` pins for the manifest entry `name`, which has to
+/// still allow them
+fn pin(lockfile: &Lockfile, name: &str, spec: &ToolSpec, opts: &mut InstallOptions) -> Result<()> {
+    let (tag, asset) = lockfile.pin(&spec.repo, Some(name), &opts.os, &opts.arch)?;
+    let drifted = spec.tag.as_ref().is_some_and(|t| *t != tag)
+        || opts
+            .version_req
+            .as_ref()
+            .is_some_and(|req| version::parse_tag(&tag).is_none_or(|v| !req.matches(&v)));
+    if drifted {
+        return Err(anyhow!(
+            "{} is locked at {}, which the manifest no longer allows; run `grd lock`",
+            name,
+            tag
+        ));
+    }
+    opts.tag = Some(tag);
+    opts.locked = Some(asset);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use semver::VersionReq;
+
+    fn receipt(hold: bool) -> Receipt {
+        serde_json::from_value(serde_json::json!({
+            "repo": "BurntSushi/ripgrep",
+            "tag": "14.0.0",
+            "asset": "rg.tar.gz",
+            "url": "https://example.com/rg.tar.gz",
+            "size": 1,
+            "sha256": "00",
+            "bin_name": "rg",
+            "destination": "/bin",
+            "hold": hold,
+            "files": [],
+            "installed_at": 0
+        }))
+        .unwrap()
+    }
+
+    fn lockfile() -> Lockfile {
+        toml::from_str(
+            r#"
+            version = 1
+
+            [tools.rg]
+            repo = "BurntSushi/ripgrep"
+            tag = "14.1.0"
+
+            [tools.rg.assets.linux-x86_64]
+            name = "ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz"
+            url = "https://example.com/rg.tar.gz"
+            sha256 = "00"
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn linux() -> InstallOptions {
+        InstallOptions {
+            os: "linux".to_string(),
+            arch: "x86_64".to_string(),
+            ..InstallOptions::default()
+        }
+    }
+
+    fn spec(tag: Option<&str>) -> ToolSpec {
+        ToolSpec {
+            repo: "BurntSushi/ripgrep".to_string(),
+            tag: tag.map(str::to_string),
+            ..ToolSpec::default()
+        }
+    }
+
+    #[test]
+    fn the_manifest_decides_the_hold() {
+        let mut state = State::default();
+        state.tools.insert("rg".to_string(), receipt(false));
+        let current = state.tools["rg"].clone();
+        assert!(record_hold(&mut state, "rg", Some(&current), true));
+        assert!(state.tools["rg"].hold);
+
+        let current = state.tools["rg"].clone();
+        assert!(!record_hold(&mut state, "rg", Some(&current), true));
+        assert!(record_hold(&mut state, "rg", Some(&current), false));
+        assert!(!state.tools["rg"].hold);
+
+        // Tools that aren't installed (or come from another repository) are left alone
+        assert!(!record_hold(&mut state, "fd", None, true));
+    }
+
+    #[test]
+    fn pins_the_locked_release_and_asset() {
+        let mut opts = linux();
+        pin(&lockfile(), "rg", &spec(None), &mut opts).unwrap();
+        assert_eq!(opts.tag.as_deref(), Some("14.1.0"));
+        assert_eq!(
+            opts.locked.map(|asset| asset.name).as_deref(),
+            Some("ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz")
+        );
+
+        let mut opts = InstallOptions {
+            version_req: Some(VersionReq::parse("~14").unwrap()),
+            ..linux()
+        };
+        pin(&lockfile(), "rg", &spec(Some("14.1.0")), &mut opts).unwrap();
+    }
+
+    #[test]
+    fn locks_the_manifest_no_longer_allows_are_errors() {
+        let err = pin(&lockfile(), "rg", &spec(Some("14.0.0")), &mut linux()).unwrap_err();
+        assert!(err.to_string().contains("run `grd lock`"));
+
+        let mut opts = InstallOptions {
+            version_req: Some(VersionReq::parse("^15").unwrap()),
+            ..linux()
+        };
+        assert!(pin(&lockfile(), "rg", &spec(None), &mut opts).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+//! Phase breakdown of a run, printed with `--timings`
+
+use std::{
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::units::format_size;
+
+/// Phases recorded so far, in order of first occurrence, or `None` when timings are disabled
+///
+/// A thread that panicked while holding the lock can't leave the list half-updated in a way that
+/// matters for a report, so a poisoned lock is used as is.
+static PHASES: Mutex<Option<Vec<Phase>>> = Mutex::new(None);
+
+struct Phase {
+    name: &'static str,
+    elapsed: Duration,
+    /// Bytes processed, to report throughput
+    bytes: u64,
+}
+
+/// Record phase durations from now on
+pub fn enable() {
+    *PHASES.lock().unwrap_or_else(PoisonError::into_inner) = Some(Vec::new());
+}
+
+/// Add the time since `started` to `phase`, along with the bytes it processed
+pub fn record(phase: &'static str, started: Instant, bytes: u64) {
+    let elapsed = started.elapsed();
+    let mut phases = PHASES.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some(phases) = phases.as_mut() else {
+        return;
+    };
+    match phases.iter_mut().find(|p| p.name == phase) {
+        Some(p) => {
+            p.elapsed += elapsed;
+            p.bytes += bytes;
+        }
+        None => phases.push(Phase {
+            name: phase,
+            elapsed,
+            bytes,
+        }),
+    }
+}
+
+/// Print the recorded phases with their durations and throughput
+pub fn report() {
+    let phases = PHASES.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some(phases) = phases.as_ref().filter(|p| !p.is_empty()) else {
+        return;
+    };
+    eprintln!("Timings:");
+    for phase in phases {
+        let throughput = if phase.bytes > 0 && !phase.elapsed.is_zero() {
+            let per_second = (phase.bytes as f64 / phase.elapsed.as_secs_f64()) as u64;
+            format!("{}/s", format_size(per_second, false))
+        } else {
+            String::new()
+        };
+        let line = format!(
+            "  {:<10} {:>8.3}s  {:>14}",
+            phase.name,
+            phase.elapsed.as_secs_f64(),
+            throughput
+        );
+        eprintln!("{}", line.trim_end());
+    }
+    let total: Duration = phases.iter().map(|p| p.elapsed).sum();
+    eprintln!("  {:<10} {:>8.3}s", "total", total.as_secs_f64());
+}
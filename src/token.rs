@@ -0,0 +1,144 @@
+//! GitHub API token taken from the environment, and checks of what it grants
+
+use std::env;
+
+use anyhow::{Result, anyhow};
+use ureq::{
+    Agent, Body, SendBody,
+    http::{Request, Response, StatusCode, header::HeaderValue},
+    middleware::MiddlewareNext,
+};
+
+/// Environment variables a token is read from, in order
+const VARIABLES: [&str; 2] = ["GITHUB_TOKEN", "GH_TOKEN"];
+
+/// The first non-empty token variable, with its name
+pub fn from_env() -> Option<(&'static str, String)> {
+    VARIABLES.iter().find_map(|name| {
+        env::var(name)
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .map(|value| (*name, value))
+    })
+}
+
+/// Middleware sending the token with API requests only, never to the hosts serving downloads
+pub fn authorize(
+    token: &str,
+) -> Option<
+    impl Fn(Request<SendBody>, MiddlewareNext) -> Result<Response<Body>, ureq::Error>
+    + Send
+    + Sync
+    + 'static,
+> {
+    let value = HeaderValue::from_str(&format!("Bearer {}", token)).ok()?;
+    Some(
+        move |mut request: Request<SendBody>, next: MiddlewareNext| {
+            if request.uri().host() == Some("api.github.com") {
+                request.headers_mut().insert("authorization", value.clone());
+            }
+            next.handle(request)
+        },
+    )
+}
+
+/// Before a session of many requests, check that the token is valid and can read every
+/// repository, explaining what is missing instead of failing later with a bare 404
+///
+/// Does nothing without a token, or when reading from a mirror.
+pub fn preflight(agent: &Agent, repos: &[&str]) -> Result<()> {
+    let Some((variable, _)) = from_env().filter(|_| crate::mirror::base().is_none()) else {
+        return Ok(());
+    };
+    let response = get(agent, "https://api.github.com/rate_limit")?;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        return Err(anyhow!(
+            "{} is invalid or has expired; create a new token, or unset it to use anonymous access",
+            variable
+        ));
+    }
+    // Only classic tokens report their scopes; fine-grained and app tokens grant per repository
+    let scopes: Option<Vec<String>> = response
+        .headers()
+        .get("x-oauth-scopes")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(',').map(|s| s.trim().to_string()).collect());
+    if let Some(remaining) = header_u64(&response, "x-ratelimit-remaining")
+        && remaining < repos.len() as u64 * 2
+    {
+        eprintln!(
+            "Warning: {} allows only {} more API request(s) this hour",
+            variable, remaining
+        );
+    }
+
+    let mut problems = Vec::new();
+    for repo in repos {
+        let response = get(agent, &format!("https://api.github.com/repos/{}", repo))?;
+        let status = response.status();
+        if status.is_success() {
+            continue;
+        }
+        let problem = if status == StatusCode::FORBIDDEN
+            && let Some(sso) = response.headers().get("x-github-sso")
+        {
+            format!(
+                "{} must be authorized for the single sign-on of {}'s organization ({})",
+                variable,
+                repo,
+                sso.to_str().unwrap_or_default()
+            )
+        } else if status != StatusCode::NOT_FOUND {
+            format!("{}: {}", repo, status)
+        } else {
+            match &scopes {
+                Some(scopes) if !scopes.iter().any(|s| s == "repo") => format!(
+                    "{} lacks the repo scope needed for private repository {} (or it doesn't exist)",
+                    variable, repo
+                ),
+                Some(_) => format!("{} doesn't exist or {} has no access to it", repo, variable),
+                None => format!(
+                    "{} doesn't exist or isn't among the repositories {} may read (fine-grained tokens need read access to its contents)",
+                    repo, variable
+                ),
+            }
+        };
+        problems.push(problem);
+    }
+    if problems.is_empty() {
+        eprintln!("{} can read all {} repositories", variable, repos.len());
+        return Ok(());
+    }
+    Err(anyhow!(
+        "Token preflight failed:\n  - {}",
+        problems.join("\n  - ")
+    ))
+}
+
+/// Hint for a release lookup that GitHub answered with 404
+pub fn not_found_hint(repo: &str) -> String {
+    match from_env() {
+        Some((variable, _)) => format!(
+            "{} has no such release, doesn't exist, or {} can't read it",
+            repo, variable
+        ),
+        None => format!(
+            "{} has no such release or doesn't exist; set GITHUB_TOKEN if it is private",
+            repo
+        ),
+    }
+}
+
+fn get(agent: &Agent, url: &str) -> Result<Response<Body>> {
+    Ok(agent
+        .get(url)
+        .config()
+        .http_status_as_error(false)
+        .build()
+        .call()?)
+}
+
+fn header_u64(response: &Response<Body>, name: &str) -> Option<u64> {
+    response.headers().get(name)?.to_str().ok()?.parse().ok()
+}
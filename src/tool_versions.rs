@@ -0,0 +1,158 @@
+//! Project-local tool-version files, in the `.tool-versions` format of asdf and mise
+//!
+//! Each line names a repository and the version the project uses:
+//!
+//! ```text
+//! # .grd-tools
+//! BurntSushi/ripgrep 14.1.0
+//! github:sharkdp/fd  10
+//! sharkdp/bat        latest
+//! ```
+//!
+//! `github:` and `ubi:` prefixes are accepted as written for mise. A `.tool-versions` file is
+//! shared with asdf plugins, so its entries that don't name a repository are left to them.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Result, anyhow};
+use semver::{Version, VersionReq};
+
+use crate::manifest::{Manifest, ToolSpec};
+
+/// File names recognized as tool-version files, in order of preference
+pub const FILE_NAMES: [&str; 2] = [".grd-tools", ".tool-versions"];
+
+/// Whether `path` is named like a tool-version file
+pub fn is_tool_versions(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| FILE_NAMES.contains(&name))
+}
+
+/// Parse the tool-version file `text` read from `path` as a manifest, each tool named after its
+/// repository
+pub fn parse(text: &str, path: &Path) -> Result<Manifest> {
+    let shared = path
+        .file_name()
+        .is_some_and(|name| name == ".tool-versions");
+    let mut tools = BTreeMap::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let mut fields = line.split_whitespace();
+        let (Some(tool), Some(version)) = (fields.next(), fields.next()) else {
+            if line.is_empty() {
+                continue;
+            }
+            return Err(anyhow!(
+                "{}:{}: expected `<owner/repo> <version>`",
+                path.display(),
+                number + 1
+            ));
+        };
+        let repo = ["github:", "ubi:"]
+            .iter()
+            .find_map(|prefix| tool.strip_prefix(prefix))
+            .unwrap_or(tool);
+        let Some((_, name)) = repo
+            .split_once('/')
+            .filter(|(owner, name)| !owner.is_empty() && !name.is_empty() && !name.contains('/'))
+        else {
+            if shared {
+                continue;
+            }
+            return Err(anyhow!(
+                "{}:{}: '{}' is not a GitHub repository (owner/repo)",
+                path.display(),
+                number + 1,
+                tool
+            ));
+        };
+
+        let mut spec = ToolSpec {
+            repo: repo.to_string(),
+            ..ToolSpec::default()
+        };
+        // Later versions on the line are fallbacks for asdf; grd installs the first one
+        if version != "latest" {
+            let bare = version.strip_prefix('v').unwrap_or(version);
+            if Version::parse(bare).is_ok() {
+                spec.version = Some(format!("={}", bare));
+            } else if bare.chars().all(|c| c.is_ascii_digit() || c == '.')
+                && VersionReq::parse(&format!("~{}", bare)).is_ok()
+            {
+                // A partial version is a prefix: `14` is any 14.x.y, `1.2` any 1.2.z
+                spec.version = Some(format!("~{}", bare));
+            } else {
+                spec.tag = Some(version.to_string());
+            }
+        }
+        if tools.insert(name.to_string(), spec).is_some() {
+            return Err(anyhow!(
+                "{}:{}: tool '{}' is listed twice",
+                path.display(),
+                number + 1,
+                name
+            ));
+        }
+    }
+    Ok(Manifest { tools })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_as(name: &str, text: &str) -> Result<Manifest> {
+        parse(text, Path::new(name))
+    }
+
+    #[test]
+    fn reads_versions_and_skips_comments() {
+        let manifest = parse_as(
+            ".grd-tools",
+            "# tools of the project\n\
+             BurntSushi/ripgrep 14.1.0   # pinned\n\
+             \n\
+             github:sharkdp/fd  v10\n\
+             ubi:sharkdp/bat    latest\n\
+             cli/cli            nightly-2024\n",
+        )
+        .unwrap();
+        let tools = &manifest.tools;
+        assert_eq!(tools.len(), 4);
+        assert_eq!(tools["ripgrep"].version.as_deref(), Some("=14.1.0"));
+        assert_eq!(tools["fd"].repo, "sharkdp/fd");
+        assert_eq!(tools["fd"].version.as_deref(), Some("~10"));
+        assert_eq!(
+            (tools["bat"].version.clone(), tools["bat"].tag.clone()),
+            (None, None)
+        );
+        assert_eq!(tools["cli"].tag.as_deref(), Some("nightly-2024"));
+    }
+
+    #[test]
+    fn later_versions_on_a_line_are_fallbacks() {
+        let manifest = parse_as(".grd-tools", "BurntSushi/ripgrep 14.1.0 13.0.0 latest\n").unwrap();
+        assert_eq!(
+            manifest.tools["ripgrep"].version.as_deref(),
+            Some("=14.1.0")
+        );
+    }
+
+    #[test]
+    fn other_tools_are_left_to_asdf_in_tool_versions_only() {
+        let text = "nodejs 20.11.0\nBurntSushi/ripgrep 14.1.0\n";
+        let manifest = parse_as(".tool-versions", text).unwrap();
+        assert_eq!(manifest.tools.keys().collect::<Vec<_>>(), ["ripgrep"]);
+        let err = parse_as(".grd-tools", text).unwrap_err();
+        assert!(err.to_string().contains(".grd-tools:1"));
+    }
+
+    #[test]
+    fn malformed_lines_are_errors() {
+        assert!(parse_as(".grd-tools", "BurntSushi/ripgrep\n").is_err());
+        assert!(parse_as(".grd-tools", "a/b/c 1.0\n").is_err());
+        let err = parse_as(".grd-tools", "owner/rg 1.0\nother/rg 2.0\n").unwrap_err();
+        assert!(err.to_string().contains("listed twice"));
+    }
+}
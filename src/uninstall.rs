@@ -0,0 +1,59 @@
+use std::{cmp::Reverse, fs};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::{
+    Layout, gha,
+    state::{Action, Scope, State},
+    versions,
+};
+
+/// Remove the files an install of `name` wrote and forget it; with `dry_run`, only list them
+pub fn uninstall(name: &str, scope: Scope, dry_run: bool) -> Result<()> {
+    let mut state = State::load(scope)?;
+    let receipt = state
+        .tools
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("'{}' is not installed by grd", name))?;
+
+    // Deepest paths first, so that directories are empty by the time they are removed
+    let mut paths = receipt.files.clone();
+    paths.sort_by_key(|path| Reverse(path.components().count()));
+    for path in &paths {
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            eprintln!("Already gone: {}", path.display());
+            continue;
+        };
+        if dry_run {
+            println!("{}", path.display());
+            continue;
+        }
+        if metadata.is_dir() {
+            // Directories the user has since put files in are left alone
+            if fs::remove_dir(path).is_err() {
+                eprintln!("Kept non-empty directory {}", path.display());
+                continue;
+            }
+        } else {
+            fs::remove_file(path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+        eprintln!("Removed {}", path.display());
+    }
+    // Every version kept by a versioned install goes with it
+    if receipt.layout == Layout::Versioned {
+        versions::remove_all(scope, name, dry_run)?;
+    }
+    if dry_run {
+        eprintln!("Would uninstall {} {} (dry run)", name, receipt.tag);
+        return Ok(());
+    }
+
+    state.log(name, Action::Uninstall, &receipt);
+    state.tools.remove(name);
+    state.save()?;
+    eprintln!("Uninstalled {} {}", name, receipt.tag);
+    gha::notice(&format!("Uninstalled {} {}", name, receipt.tag));
+    Ok(())
+}
@@ -0,0 +1,84 @@
+//! Human-readable sizes
+
+use anyhow::{Result, anyhow};
+
+/// Human-readable size in binary units (KiB, MiB, ...), or decimal units (kB, MB, ...) with `si`
+pub fn format_size(bytes: u64, si: bool) -> String {
+    let (base, units) = if si {
+        (1000.0, ["kB", "MB", "GB", "TB"])
+    } else {
+        (1024.0, ["KiB", "MiB", "GiB", "TiB"])
+    };
+    if (bytes as f64) < base {
+        return if bytes == 1 {
+            "1 byte".to_string()
+        } else {
+            format!("{} bytes", bytes)
+        };
+    }
+    let mut size = bytes as f64 / base;
+    let mut unit = units[0];
+    for next in &units[1..] {
+        if size < base {
+            break;
+        }
+        size /= base;
+        unit = next;
+    }
+    format!("{:.1} {}", size, unit)
+}
+
+/// Parse a size in bytes, with an optional unit: `k`, `M`, `G`, `T` or `KiB`... for powers of
+/// 1024, `kB`, `MB`... for powers of 1000 (e.g. `500M`, `2GiB`, `1.5GB`)
+pub fn parse_size(input: &str) -> Result<u64> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("Invalid size '{}'", input))?;
+    let unit = unit.trim().to_ascii_lowercase();
+    let multiplier: u64 = match unit.trim_end_matches('b') {
+        "" => 1,
+        "k" | "ki" if unit != "kb" => 1 << 10,
+        "m" | "mi" if unit != "mb" => 1 << 20,
+        "g" | "gi" if unit != "gb" => 1 << 30,
+        "t" | "ti" if unit != "tb" => 1 << 40,
+        "k" => 1_000,
+        "m" => 1_000_000,
+        "g" => 1_000_000_000,
+        "t" => 1_000_000_000_000,
+        _ => return Err(anyhow!("Unknown size unit in '{}'", input)),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("1k").unwrap(), 1024);
+        assert_eq!(parse_size("2KiB").unwrap(), 2048);
+        assert_eq!(parse_size("500M").unwrap(), 500 << 20);
+        assert_eq!(parse_size("1kB").unwrap(), 1000);
+        assert_eq!(parse_size("1.5GB").unwrap(), 1_500_000_000);
+        assert_eq!(parse_size(" 10 MB ").unwrap(), 10_000_000);
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("10 parsecs").is_err());
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_size(1, false), "1 byte");
+        assert_eq!(format_size(1000, false), "1000 bytes");
+        assert_eq!(format_size(1000, true), "1.0 kB");
+        assert_eq!(format_size(1536, false), "1.5 KiB");
+        assert_eq!(format_size(5 << 30, false), "5.0 GiB");
+        assert_eq!(format_size(3 << 50, false), "3072.0 TiB");
+    }
+}
@@ -0,0 +1,281 @@
+use std::{collections::BTreeSet, fs};
+
+use anyhow::{Result, anyhow};
+use ureq::Agent;
+
+use crate::{
+    channel::{self, Channel},
+    config::{Config, RepoConfig},
+    gha,
+    hooks::{Change, Event},
+    install::{InstallOptions, install, post_install, receipt_for},
+    notes,
+    release::Release,
+    state::{Receipt, Scope, State},
+    token, version, versions, wrapper,
+};
+
+/// Reinstall tools recorded in the state file whose repository has a newer release
+///
+/// Without `name`, every installed tool is checked, except those listed in `except` and those
+/// held by their manifest entry. With `changelog`, the notes of the releases skipped over are
+/// printed before each update.
+pub fn update(
+    agent: &Agent,
+    name: Option<&str>,
+    except: &[String],
+    scope: Scope,
+    changelog: bool,
+) -> Result<()> {
+    let mut state = State::load(scope)?;
+    if state.tools.is_empty() {
+        eprintln!("No tools installed by grd");
+        return Ok(());
+    }
+    if let Some(unknown) = except.iter().find(|n| !state.tools.contains_key(*n)) {
+        return Err(anyhow!("'{}' is not installed by grd", unknown));
+    }
+    let names: Vec<String> = match name {
+        Some(name) if !state.tools.contains_key(name) => {
+            return Err(anyhow!("'{}' is not installed by grd", name));
+        }
+        Some(name) => vec![name.to_string()],
+        None => state
+            .tools
+            .iter()
+            .filter(|(name, receipt)| {
+                if except.contains(name) {
+                    eprintln!("==> {} skipped", name);
+                } else if receipt.hold {
+                    eprintln!("==> {} held at {}", name, receipt.tag);
+                } else {
+                    return true;
+                }
+                false
+            })
+            .map(|(name, _)| name.clone())
+            .collect(),
+    };
+
+    let config = Config::load()?;
+    if name.is_none() {
+        let repos: BTreeSet<&str> = names.iter().map(|n| state.tools[n].repo.as_str()).collect();
+        token::preflight(agent, &Vec::from_iter(repos))?;
+    }
+    let mut updated = 0;
+    for name in &names {
+        let current = state.tools[name].clone();
+        let repo_config = config.repo(&current.repo);
+        let latest = latest(agent, &current.repo, &repo_config, current.channel)?;
+        if !is_newer(&latest, &current.tag, current.published_at.as_deref()) {
+            eprintln!("{} is up to date ({})", name, current.tag);
+            continue;
+        }
+
+        eprintln!(
+            "==> Updating {}: {} -> {}",
+            name, current.tag, latest.tag_name
+        );
+        if changelog {
+            notes::changelog(agent, &current.repo, &current.tag, &latest.tag_name)?;
+        }
+        let mut opts = update_options(&current, &latest.tag_name, &config, scope);
+        config.repo(&current.repo).apply(&mut opts)?;
+        let change = Change {
+            tool: name,
+            repo: &current.repo,
+            from: Some(&current.tag),
+            to: &latest.tag_name,
+            destination: &current.destination,
+        };
+        current.hooks.run(Event::PreInstall, &change)?;
+        let mut installed = install(agent, &current.repo, &opts)?;
+        installed.files = wrapper::wrap(installed.files, &installed.tag, &config.wrappers, scope)?;
+        let receipt = receipt_for(&current.repo, &installed, &opts)?;
+
+        remove_stale_files(&current, &receipt)?;
+        state.record(name, receipt);
+        state.save()?;
+        post_install(&config, &state.tools[name])?;
+        state.tools[name].hooks.run(Event::PostUpdate, &change)?;
+        println!("{} {} -> {}", name, current.tag, latest.tag_name);
+        updated += 1;
+    }
+
+    if updated > 0 {
+        gha::notice(&format!("Updated {} tool(s)", updated));
+    }
+    Ok(())
+}
+
+/// Remove the files that only the replaced install wrote
+///
+/// Versions of a versioned install are kept, only links to them are removed.
+pub fn remove_stale_files(old: &Receipt, new: &Receipt) -> Result<()> {
+    for stale in old.files.iter().filter(|f| !new.files.contains(f)) {
+        if versions::is_version_file(stale) {
+            continue;
+        }
+        if fs::symlink_metadata(stale).is_ok_and(|m| !m.is_dir()) {
+            fs::remove_file(stale)?;
+        }
+    }
+    Ok(())
+}
+
+/// The release `repo` is updated to: the latest of the channel it was installed from (`tracked`)
+/// or else its configured one, or the highest version with `latest_by_version`
+pub fn latest(
+    agent: &Agent,
+    repo: &str,
+    config: &RepoConfig,
+    tracked: Option<Channel>,
+) -> Result<Release> {
+    let channel = tracked.or(config.channel).unwrap_or_default();
+    if config.latest_by_version == Some(true) {
+        version::latest_by_version(agent, repo, channel)
+    } else {
+        channel::latest(agent, repo, channel)
+    }
+}
+
+/// Whether `latest` is a newer release than the one tagged `installed`, published at
+/// `installed_at`
+///
+/// Tags that aren't semantic versions are ordered by publication time, and only compared for
+/// equality when it isn't known for both.
+pub fn is_newer(latest: &Release, installed: &str, installed_at: Option<&str>) -> bool {
+    match (
+        version::parse_tag(&latest.tag_name),
+        version::parse_tag(installed),
+    ) {
+        (Some(latest), Some(installed)) => latest > installed,
+        _ => match (latest.published_at.as_deref(), installed_at) {
+            (Some(latest), Some(installed)) => latest > installed,
+            _ => latest.tag_name != installed,
+        },
+    }
+}
+
+/// Install options reproducing `receipt`, for release `tag`
+fn update_options(receipt: &Receipt, tag: &str, config: &Config, scope: Scope) -> InstallOptions {
+    let defaults = InstallOptions::default();
+    InstallOptions {
+        tag: Some(tag.to_string()),
+        bin_name: receipt.bins.is_empty().then(|| receipt.bin_name.clone()),
+        destination: receipt.destination.clone(),
+        extract_all: receipt.extract_all,
+        extract_dir: receipt.extract_dir.clone(),
+        strip_components: receipt.strip_components,
+        member: receipt.member.clone(),
+        bin_pattern: receipt.bin_pattern.clone(),
+        bins: receipt.bins.clone(),
+        layout: receipt.layout,
+        channel: receipt.channel,
+        os: receipt.os.clone().unwrap_or(defaults.os),
+        arch: receipt.arch.clone().unwrap_or(defaults.arch),
+        exclude: receipt.exclude.clone(),
+        asset_pattern: receipt.asset_pattern.clone(),
+        prefer: receipt.prefer,
+        first: receipt.first,
+        checksum_asset: receipt.checksum_asset.clone(),
+        ownership: receipt.ownership,
+        chmod_dirs: !receipt.no_chmod_dirs,
+        normalize_modes: receipt.normalize_modes,
+        extractors: config.extractors.clone(),
+        previous_asset: Some(receipt.asset.clone()),
+        hooks: receipt.hooks.clone(),
+        minisign_key: config.minisign_keys.get(&receipt.repo).cloned(),
+        gpg_key: config.gpg_keys.get(&receipt.repo).cloned(),
+        paranoid: config.paranoid,
+        hold: receipt.hold,
+        scope,
+        ..defaults
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(tag: &str, published_at: Option<&str>) -> Release {
+        Release {
+            tag_name: tag.to_string(),
+            assets: Vec::new(),
+            tarball_url: None,
+            name: None,
+            published_at: published_at.map(str::to_string),
+            html_url: None,
+            body: None,
+            author: None,
+            prerelease: false,
+            draft: false,
+        }
+    }
+
+    #[test]
+    fn compares_semantic_versions() {
+        // Publication times don't matter, e.g. for a patch release of an older series
+        let at = Some("2024-06-01T00:00:00Z");
+        assert!(is_newer(&release("v1.10.0", None), "v1.9.0", at));
+        assert!(is_newer(&release("1.0.0", None), "v1.0.0-rc.1", None));
+        assert!(!is_newer(&release("v1.0.0-rc.1", None), "v1.0.0", None));
+        assert!(!is_newer(&release("v1.0.0", None), "1.0.0", None));
+    }
+
+    #[test]
+    fn other_tags_are_ordered_by_publication() {
+        let june_2 = Some("2024-06-02T00:00:00Z");
+        let june_1 = Some("2024-06-01T00:00:00Z");
+        assert!(is_newer(&release("nightly-b", june_2), "nightly-a", june_1));
+        assert!(!is_newer(
+            &release("nightly-a", june_1),
+            "nightly-b",
+            june_2
+        ));
+        assert!(!is_newer(&release("nightly", june_1), "nightly", june_1));
+    }
+
+    #[test]
+    fn other_tags_without_publication_times_only_differ() {
+        let june_1 = Some("2024-06-01T00:00:00Z");
+        assert!(is_newer(&release("nightly-b", june_1), "nightly-a", None));
+        assert!(is_newer(&release("nightly-a", None), "nightly-b", june_1));
+        assert!(!is_newer(&release("nightly", june_1), "nightly", None));
+    }
+
+    #[test]
+    fn updates_reuse_recorded_options() {
+        let receipt: Receipt = serde_json::from_value(serde_json::json!({
+            "repo": "owner/tool",
+            "tag": "v1.0.0",
+            "asset": "tool-windows-x86_64.zip",
+            "url": "https://example.com/tool-windows-x86_64.zip",
+            "size": 1,
+            "sha256": "0",
+            "bin_name": "tool",
+            "destination": "/opt/bin",
+            "os": "windows",
+            "arch": "aarch64",
+            "exclude": "debug",
+            "prefer": "portable",
+            "first": true,
+            "checksum_asset": "{asset}.sha256",
+            "ownership": [1000, 1000],
+            "normalize_modes": true,
+            "files": [],
+            "installed_at": 0
+        }))
+        .unwrap();
+        let opts = update_options(&receipt, "v2.0.0", &Config::default(), Scope::User);
+        assert_eq!(
+            (opts.os.as_str(), opts.arch.as_str()),
+            ("windows", "aarch64")
+        );
+        assert_eq!(opts.exclude.as_deref(), Some("debug"));
+        assert_eq!(opts.prefer, Some(crate::select::Prefer::Portable));
+        assert!(opts.first && opts.normalize_modes && opts.chmod_dirs);
+        assert_eq!(opts.checksum_asset.as_deref(), Some("{asset}.sha256"));
+        assert_eq!(opts.ownership, Some((1000, 1000)));
+    }
+}
@@ -0,0 +1,12 @@
+use anyhow::Result;
+use ureq::Agent;
+
+use crate::{install::InstallOptions, resolve::resolve_release, select::select_asset};
+
+/// Print the URL of the asset grd would install, so another downloader can fetch it
+pub fn url(agent: &Agent, repo: &str, opts: &InstallOptions) -> Result<()> {
+    let release = resolve_release(agent, repo, opts)?;
+    let asset = select_asset(&release.assets, opts)?;
+    println!("{}", asset.browser_download_url);
+    Ok(())
+}
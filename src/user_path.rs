@@ -0,0 +1,54 @@
+//! Adding an install destination to the user's `PATH` on Windows
+
+use std::{io::Write, path::Path, process::Command};
+
+use anyhow::{Context, Result, anyhow};
+
+/// Append the directory in `GRD_PATH_DIR` to the `Path` value of `HKCU\Environment`, keeping
+/// the value's unexpanded `%VARIABLES%`, then tell running programs (Explorer, so new shells)
+/// that the environment changed; exits with 3 if it was already there
+const SCRIPT: &str = r#"
+$dir = $env:GRD_PATH_DIR
+$key = [Microsoft.Win32.Registry]::CurrentUser.CreateSubKey('Environment')
+$path = [string]$key.GetValue('Path', '', 'DoNotExpandEnvironmentNames')
+$entries = @($path -split ';' | Where-Object { $_ })
+foreach ($entry in $entries) {
+    if ([Environment]::ExpandEnvironmentVariables($entry).TrimEnd('\') -ieq $dir.TrimEnd('\')) { exit 3 }
+}
+$key.SetValue('Path', (($entries + $dir) -join ';'), 'ExpandString')
+Add-Type -Namespace Grd -Name Native -MemberDefinition '[DllImport("user32.dll", CharSet = CharSet.Unicode)] public static extern IntPtr SendMessageTimeout(IntPtr hWnd, uint Msg, UIntPtr wParam, string lParam, uint fuFlags, uint uTimeout, out UIntPtr lpdwResult);'
+$result = [UIntPtr]::Zero
+[void][Grd.Native]::SendMessageTimeout([IntPtr]0xffff, 0x1A, [UIntPtr]::Zero, 'Environment', 2, 5000, [ref]$result)
+"#;
+
+/// Add `dir` to the per-user `PATH` for new shells, unless it is already there
+pub fn add(dir: &Path) -> Result<()> {
+    let dir = std::path::absolute(dir)?;
+    let mut script = tempfile::Builder::new().suffix(".ps1").tempfile()?;
+    script.write_all(SCRIPT.as_bytes())?;
+    script.flush()?;
+    let status = Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-ExecutionPolicy",
+            "Bypass",
+            "-File",
+        ])
+        .arg(script.path())
+        .env("GRD_PATH_DIR", &dir)
+        .status()
+        .context("Failed to run powershell")?;
+    match status.code() {
+        Some(0) => eprintln!(
+            "Added {:?} to your PATH; open a new terminal to run the installed tools",
+            dir
+        ),
+        Some(3) => eprintln!(
+            "{:?} is already in your PATH; open a new terminal to pick it up",
+            dir
+        ),
+        _ => return Err(anyhow!("Failed to add {:?} to PATH: {}", dir, status)),
+    }
+    Ok(())
+}
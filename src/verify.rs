@@ -0,0 +1,337 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, anyhow};
+use ureq::Agent;
+
+use crate::{
+    download::DownloadSource, gha, minisign::MinisignCheck, pgp::PgpCheck, release::Asset,
+};
+
+/// Everything the downloaded asset is checked against before it is extracted
+#[derive(Default)]
+pub struct Expectations {
+    pub digests: Vec<ExpectedDigest>,
+    pub minisign: Option<MinisignCheck>,
+    pub gpg: Option<PgpCheck>,
+}
+
+impl Expectations {
+    /// Fail unless the downloaded asset matches every expected digest and signature
+    pub fn check(&self, asset_name: &str, source: &DownloadSource, sha256: &str) -> Result<()> {
+        verify(asset_name, sha256, &self.digests)?;
+        if let Some(minisign) = &self.minisign {
+            minisign.check(asset_name, source)?;
+        }
+        if let Some(gpg) = &self.gpg {
+            gpg.check(asset_name, source)?;
+        }
+        Ok(())
+    }
+}
+
+/// Download a small release asset such as a checksum file or signature
+pub fn fetch(agent: &Agent, asset: &Asset) -> Result<Vec<u8>> {
+    let mut response = agent.get(&asset.browser_download_url).call()?;
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Failed to download {}: {}",
+            asset.name,
+            response.status()
+        ));
+    }
+    Ok(response.body_mut().read_to_vec()?)
+}
+
+/// A SHA-256 digest the downloaded asset has to match, and where it comes from
+#[derive(Debug, Clone)]
+pub struct ExpectedDigest {
+    pub sha256: String,
+    pub origin: String,
+}
+
+impl ExpectedDigest {
+    pub fn new(sha256: &str, origin: impl Into<String>) -> Self {
+        Self {
+            sha256: sha256.to_lowercase(),
+            origin: origin.into(),
+        }
+    }
+}
+
+/// Parse a pinned digest given as `sha256:<hex>`
+pub fn parse_checksum(value: &str) -> Result<ExpectedDigest> {
+    let (algorithm, hex) = value
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Invalid checksum '{}': expected sha256:<hex>", value))?;
+    if !algorithm.eq_ignore_ascii_case("sha256") {
+        return Err(anyhow!(
+            "Unsupported checksum algorithm '{}': only sha256 is supported",
+            algorithm
+        ));
+    }
+    if !is_sha256_hex(hex) {
+        return Err(anyhow!(
+            "Invalid checksum '{}': expected 64 hexadecimal digits",
+            value
+        ));
+    }
+    Ok(ExpectedDigest::new(hex, "--checksum"))
+}
+
+/// Look up the asset's digest in a local checksum file (`sha256sum` or BSD format, or a bare digest)
+pub fn digest_from_file(path: &Path, asset_name: &str) -> Result<ExpectedDigest> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read checksum file {:?}", path))?;
+    let sha256 = find_digest(&text, asset_name, true)
+        .ok_or_else(|| anyhow!("No SHA-256 digest for {} in {:?}", asset_name, path))?;
+    Ok(ExpectedDigest::new(&sha256, path.display().to_string()))
+}
+
+/// Look up the asset's digest in a checksum file published next to it in the release
+///
+/// Per-asset files (`<asset>.sha256`) are preferred over aggregate ones (`SHA256SUMS`,
+/// `checksums.txt`, `<project>_<version>_checksums.txt`); aggregate files not listing the asset
+/// are skipped. A checksum file that fails to download is an error, unless it is an aggregate one
+/// and another file lists the asset.
+pub fn published_digest(
+    agent: &Agent,
+    assets: &[Asset],
+    asset: &Asset,
+) -> Result<Option<ExpectedDigest>> {
+    let mut candidates: Vec<&Asset> = assets
+        .iter()
+        .filter(|a| is_own_checksum_file(&a.name, &asset.name))
+        .collect();
+    candidates.extend(assets.iter().filter(|a| is_checksum_file(&a.name)));
+
+    let mut failures = Vec::new();
+    for candidate in &candidates {
+        let own = is_own_checksum_file(&candidate.name, &asset.name);
+        let text = match fetch(agent, candidate) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(e) if own => {
+                return Err(e.context(format!(
+                    "Failed to download the checksum file {}",
+                    candidate.name
+                )));
+            }
+            Err(e) => {
+                gha::warning(&format!("Skipping checksum file {}: {}", candidate.name, e));
+                failures.push(candidate.name.as_str());
+                continue;
+            }
+        };
+        if let Some(sha256) = find_digest(&text, &asset.name, own) {
+            return Ok(Some(ExpectedDigest::new(&sha256, candidate.name.clone())));
+        }
+    }
+    if !failures.is_empty() {
+        return Err(anyhow!(
+            "No other checksum file lists {}, and these could not be downloaded: {}",
+            asset.name,
+            failures.join(", ")
+        ));
+    }
+    Ok(None)
+}
+
+/// Look up the asset's digest in the release asset a definition names as its checksum file
+pub fn digest_from_release(
+    agent: &Agent,
+    assets: &[Asset],
+    asset: &Asset,
+    pattern: &str,
+) -> Result<ExpectedDigest> {
+    let pattern = pattern.replace("{asset}", &glob::Pattern::escape(&asset.name));
+    let glob = glob::Pattern::new(&pattern)
+        .map_err(|e| anyhow!("Invalid checksums glob '{}': {}", pattern, e))?;
+    let candidate = assets
+        .iter()
+        .find(|a| glob.matches(&a.name))
+        .ok_or_else(|| anyhow!("The release has no checksum file matching '{}'", pattern))?;
+    let text = String::from_utf8_lossy(&fetch(agent, candidate)?).into_owned();
+    let own = candidate.name != asset.name && candidate.name.starts_with(&asset.name);
+    let sha256 = find_digest(&text, &asset.name, own).ok_or_else(|| {
+        anyhow!(
+            "{} doesn't list a SHA-256 digest of {}",
+            candidate.name,
+            asset.name
+        )
+    })?;
+    Ok(ExpectedDigest::new(&sha256, candidate.name.clone()))
+}
+
+/// The digest GitHub computed for the asset, as reported by the releases API
+///
+/// Only SHA-256 digests are used; assets uploaded before GitHub started computing them have none.
+pub fn api_digest(asset: &Asset) -> Option<ExpectedDigest> {
+    let (algorithm, hex) = asset.digest.as_deref()?.split_once(':')?;
+    (algorithm.eq_ignore_ascii_case("sha256") && is_sha256_hex(hex))
+        .then(|| ExpectedDigest::new(hex, "the GitHub API"))
+}
+
+/// Fail unless the downloaded asset matches every expected digest
+fn verify(asset_name: &str, sha256: &str, expected: &[ExpectedDigest]) -> Result<()> {
+    for digest in expected {
+        if !digest.sha256.eq_ignore_ascii_case(sha256) {
+            return Err(anyhow!(
+                "Checksum mismatch for {}: expected {} (from {}), got {}",
+                asset_name,
+                digest.sha256,
+                digest.origin,
+                sha256
+            ));
+        }
+        eprintln!("Verified SHA-256 against {}", digest.origin);
+    }
+    Ok(())
+}
+
+/// `<asset>.sha256` and similar files holding the digest of a single asset
+fn is_own_checksum_file(name: &str, asset_name: &str) -> bool {
+    name.strip_prefix(asset_name).is_some_and(|suffix| {
+        matches!(
+            suffix.to_lowercase().as_str(),
+            ".sha256" | ".sha256sum" | ".sha256.txt"
+        )
+    })
+}
+
+/// Release-wide checksum files listing several assets
+fn is_checksum_file(name: &str) -> bool {
+    let name = name.to_lowercase();
+    matches!(
+        name.as_str(),
+        "sha256sums" | "sha256sums.txt" | "sha256sum.txt" | "checksums.sha256"
+    ) || name.ends_with("checksums.txt")
+}
+
+/// Find the digest listed for `asset_name`, in `sha256sum` (`<hex>  [*]<name>`) or BSD
+/// (`SHA256 (<name>) = <hex>`) format; per-asset files may hold just the digest
+fn find_digest(text: &str, asset_name: &str, own: bool) -> Option<String> {
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("SHA256 (") {
+            if let Some((name, hex)) = rest.split_once(") = ")
+                && file_name(name) == asset_name
+                && is_sha256_hex(hex.trim())
+            {
+                return Some(hex.trim().to_string());
+            }
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let Some(hex) = parts.next().filter(|hex| is_sha256_hex(hex)) else {
+            continue;
+        };
+        match parts.next() {
+            Some(name) if file_name(name.trim_start_matches('*')) == asset_name => {
+                return Some(hex.to_string());
+            }
+            None if own => return Some(hex.to_string()),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+fn is_sha256_hex(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEX: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn finds_sha256sum_lines() {
+        let text = format!(
+            "{}  other.tar.gz\n{}  tool-linux.tar.gz\n",
+            "0".repeat(64),
+            HEX
+        );
+        assert_eq!(
+            find_digest(&text, "tool-linux.tar.gz", false).as_deref(),
+            Some(HEX)
+        );
+        let binary = format!("{} *dist/tool-linux.tar.gz\n", HEX);
+        assert_eq!(
+            find_digest(&binary, "tool-linux.tar.gz", false).as_deref(),
+            Some(HEX)
+        );
+        assert_eq!(find_digest(&text, "tool-macos.tar.gz", false), None);
+    }
+
+    #[test]
+    fn finds_bsd_lines() {
+        let text = format!("SHA256 (tool-linux.tar.gz) = {}\n", HEX);
+        assert_eq!(
+            find_digest(&text, "tool-linux.tar.gz", false).as_deref(),
+            Some(HEX)
+        );
+        assert_eq!(find_digest(&text, "tool.tar.gz", false), None);
+    }
+
+    #[test]
+    fn bare_digest_only_in_own_file() {
+        let text = format!("{}\n", HEX);
+        assert_eq!(
+            find_digest(&text, "tool.tar.gz", true).as_deref(),
+            Some(HEX)
+        );
+        assert_eq!(find_digest(&text, "tool.tar.gz", false), None);
+        assert_eq!(find_digest("not a digest\n", "tool.tar.gz", true), None);
+    }
+
+    #[test]
+    fn own_checksum_files() {
+        assert!(is_own_checksum_file("tool.tar.gz.sha256", "tool.tar.gz"));
+        assert!(is_own_checksum_file("tool.tar.gz.SHA256SUM", "tool.tar.gz"));
+        assert!(!is_own_checksum_file("tool.tar.gz.sig", "tool.tar.gz"));
+        assert!(is_checksum_file("tool_1.0_checksums.txt"));
+        assert!(is_checksum_file("SHA256SUMS"));
+    }
+
+    fn asset(name: &str) -> Asset {
+        Asset {
+            name: name.to_string(),
+            // Nothing listens on port 1, so every download fails at once
+            browser_download_url: format!("http://127.0.0.1:1/{}", name),
+            size: 0,
+            digest: None,
+            content_type: None,
+            download_count: 0,
+        }
+    }
+
+    #[test]
+    fn checksum_files_that_fail_to_download_are_errors() {
+        let agent = Agent::new_with_defaults();
+        let tool = asset("tool.tar.gz");
+        let own = [tool.clone(), asset("tool.tar.gz.sha256")];
+        let err = published_digest(&agent, &own, &tool).unwrap_err();
+        assert!(format!("{:#}", err).contains("tool.tar.gz.sha256"));
+        let aggregate = [tool.clone(), asset("SHA256SUMS")];
+        assert!(published_digest(&agent, &aggregate, &tool).is_err());
+        assert!(
+            published_digest(&agent, std::slice::from_ref(&tool), &tool)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn parses_pinned_checksums() {
+        let digest = parse_checksum(&format!("SHA256:{}", HEX.to_uppercase())).unwrap();
+        assert_eq!(digest.sha256, HEX);
+        assert!(parse_checksum(HEX).is_err());
+        assert!(parse_checksum(&format!("sha512:{}", HEX)).is_err());
+        assert!(parse_checksum("sha256:abc").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::{Result, anyhow};
+use regex_lite::Regex;
+use semver::{Version, VersionReq};
+use ureq::Agent;
+
+use crate::{channel::Channel, release::Release};
+
+/// Semantic version of a tag, ignoring a leading `v` (e.g. `v1.2.3`, `1.2.3`)
+pub fn parse_tag(tag: &str) -> Option<Version> {
+    let tag = tag.strip_prefix('v').unwrap_or(tag);
+    Version::parse(tag).ok()
+}
+
+/// The version requirement a `--tag` value stands for, if it is a range like `^1.4` or
+/// `>=0.10, <0.12` rather than a tag name
+///
+/// Bare versions such as `1.4` are tag names: they parse as requirements too, but releases are
+/// tagged like that.
+pub fn parse_range(tag: &str) -> Result<Option<VersionReq>> {
+    if !tag.starts_with(['^', '~', '<', '>', '=', '*']) && !tag.contains(',') {
+        return Ok(None);
+    }
+    VersionReq::parse(tag)
+        .map(Some)
+        .map_err(|e| anyhow!("Invalid version range '{}': {}", tag, e))
+}
+
+/// Compile a `--tag-pattern` regular expression, which has to match whole tags
+pub fn parse_tag_pattern(pattern: &str) -> Result<Regex> {
+    Regex::new(&format!("^(?:{})$", pattern))
+        .map_err(|e| anyhow!("Invalid --tag-pattern '{}': {}", pattern, e))
+}
+
+/// The most recently published release whose tag matches `pattern`, for tags that aren't
+/// semantic versions (dates, CalVer, `release-` prefixes); drafts, releases without assets and,
+/// unless `prerelease`, prereleases are ignored
+pub fn latest_by_pattern(
+    agent: &Agent,
+    repo: &str,
+    pattern: &Regex,
+    prerelease: bool,
+) -> Result<Release> {
+    all_releases(agent, repo)?
+        .into_iter()
+        .filter(|r| !r.draft && !r.assets.is_empty() && (prerelease || !r.prerelease))
+        .filter(|r| pattern.is_match(&r.tag_name))
+        // RFC 3339 timestamps in UTC sort chronologically as strings
+        .max_by(|a, b| a.published_at.cmp(&b.published_at))
+        .ok_or_else(|| anyhow!("No release of {} has a tag matching --tag-pattern", repo))
+}
+
+/// Every release of `repo`, newest first, following the API's pages
+pub fn all_releases(agent: &Agent, repo: &str) -> Result<Vec<Release>> {
+    scan_releases(agent, repo, None, |_| Scan::Take)
+}
+
+/// What to do with a release while paging through a repository's releases
+pub enum Scan {
+    Take,
+    Skip,
+    /// Skip it and every older release
+    Stop,
+}
+
+/// Up to `limit` releases of `repo` that `scan` takes, newest first, fetching only the pages
+/// needed
+pub fn scan_releases(
+    agent: &Agent,
+    repo: &str,
+    limit: Option<usize>,
+    mut scan: impl FnMut(&Release) -> Scan,
+) -> Result<Vec<Release>> {
+    const PER_PAGE: usize = 100;
+    let mut releases = Vec::new();
+    for page in 1.. {
+        let url = format!(
+            "https://api.github.com/repos/{}/releases?per_page={}&page={}",
+            repo, PER_PAGE, page
+        );
+        let mut response = agent.get(&url).call()?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Failed to fetch releases of {}: {}",
+                repo,
+                response.status()
+            ));
+        }
+        let batch: Vec<Release> = response.body_mut().read_json()?;
+        let last = batch.len() < PER_PAGE;
+        for release in batch {
+            if limit.is_some_and(|limit| releases.len() >= limit) {
+                return Ok(releases);
+            }
+            match scan(&release) {
+                Scan::Take => releases.push(release),
+                Scan::Skip => {}
+                Scan::Stop => return Ok(releases),
+            }
+        }
+        if last {
+            break;
+        }
+    }
+    Ok(releases)
+}
+
+/// The release with the highest version satisfying `req`; drafts, releases without assets and
+/// tags that are not semantic versions are ignored
+pub fn latest_matching(agent: &Agent, repo: &str, req: &VersionReq) -> Result<Release> {
+    all_releases(agent, repo)?
+        .into_iter()
+        .filter(|r| !r.draft && !r.assets.is_empty())
+        .filter_map(|r| parse_tag(&r.tag_name).map(|v| (v, r)))
+        .filter(|(v, _)| req.matches(v))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, r)| r)
+        .ok_or_else(|| anyhow!("No release of {} matches '{}'", repo, req))
+}
+
+/// The release with the highest version in `channel`, rather than the one GitHub calls latest,
+/// which is merely the most recent and can be a patch of an older series; drafts, releases
+/// without assets and tags that are not semantic versions are ignored
+pub fn latest_by_version(agent: &Agent, repo: &str, channel: Channel) -> Result<Release> {
+    all_releases(agent, repo)?
+        .into_iter()
+        .filter(|r| channel.accepts(r) && !r.assets.is_empty())
+        .filter_map(|r| parse_tag(&r.tag_name).map(|v| (v, r)))
+        // Semantic version prereleases like `2.0.0-dev.1` are never stable
+        .filter(|(v, _)| channel != Channel::Stable || v.pre.is_empty())
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, r)| r)
+        .ok_or_else(|| {
+            anyhow!(
+                "No release of {} in the {} channel is tagged with a semantic version",
+                repo,
+                channel.name()
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tags() {
+        assert_eq!(parse_tag("v1.2.3"), Some(Version::new(1, 2, 3)));
+        assert_eq!(parse_tag("1.2.3"), Some(Version::new(1, 2, 3)));
+        assert!(parse_tag("v1.0.0-rc.1").is_some_and(|v| !v.pre.is_empty()));
+        assert_eq!(parse_tag("release-1.2.3"), None);
+        assert_eq!(parse_tag("v1.2"), None);
+    }
+
+    #[test]
+    fn ranges_are_told_from_tags() {
+        assert!(parse_range("1.4").unwrap().is_none());
+        assert!(parse_range("v1.4.0").unwrap().is_none());
+        let req = parse_range("^1.4").unwrap().unwrap();
+        assert!(req.matches(&Version::new(1, 9, 0)));
+        assert!(!req.matches(&Version::new(2, 0, 0)));
+        let req = parse_range(">=0.10, <0.12").unwrap().unwrap();
+        assert!(req.matches(&Version::new(0, 11, 5)));
+        assert!(!req.matches(&Version::new(0, 12, 0)));
+        assert!(parse_range("^one").is_err());
+    }
+
+    #[test]
+    fn tag_patterns_match_whole_tags() {
+        let pattern = parse_tag_pattern(r"\d{4}-\d{2}-\d{2}").unwrap();
+        assert!(pattern.is_match("2024-06-01"));
+        assert!(!pattern.is_match("v2024-06-01"));
+        assert!(!pattern.is_match("2024-06-01-hotfix"));
+        assert!(parse_tag_pattern("(").is_err());
+    }
+}
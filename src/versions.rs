@@ -0,0 +1,407 @@
+//! Versioned installs: each version of a tool in its own directory, selected by a symlink
+//!
+//! ```text
+//! <data dir>/tools/rg/14.1.0/rg
+//! <data dir>/tools/rg/14.1.1/rg
+//! <data dir>/tools/rg/current -> 14.1.1
+//! ~/.local/bin/rg -> <data dir>/tools/rg/current/rg
+//! ```
+//!
+//! The destination only holds links through `current`, so switching to a version that is already
+//! present flips that symlink instead of downloading it again.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Layout, gha,
+    hooks::{Change, Event},
+    install::{file_digests, file_sha256},
+    state::{self, Action, Receipt, Scope, State},
+    table::print_table,
+    verify::Expectations,
+};
+
+/// File in each version directory recording the asset it was installed from
+const MARKER: &str = ".grd-version.json";
+
+/// Symlink to the selected version, next to the version directories
+const CURRENT: &str = "current";
+
+/// What a version directory was installed from, and the digests of its files
+#[derive(Serialize, Deserialize, Debug)]
+struct Stored {
+    asset: String,
+    url: String,
+    size: u64,
+    /// SHA-256 of the downloaded asset
+    sha256: String,
+    /// SHA-256 of each file, keyed by file name
+    digests: BTreeMap<String, String>,
+}
+
+/// Directory holding every version of `name`
+pub fn tool_dir(scope: Scope, name: &str) -> Result<PathBuf> {
+    Ok(std::path::absolute(
+        scope.data_dir()?.join("tools").join(name),
+    )?)
+}
+
+/// Directory of version `tag` of `name`
+pub fn version_dir(scope: Scope, name: &str, tag: &str) -> Result<PathBuf> {
+    // Tags like `cli/v1.2.0` would otherwise nest directories
+    Ok(tool_dir(scope, name)?.join(tag.replace(['/', '\\'], "_")))
+}
+
+/// Whether `path` is a file of a version directory, which outlives the install that wrote it
+pub fn is_version_file(path: &Path) -> bool {
+    path.parent().is_some_and(|dir| dir.join(MARKER).is_file())
+}
+
+/// The files of version directory `dir` and the asset's SHA-256, if it holds `asset` untouched
+/// and the asset satisfies every expected digest
+///
+/// Otherwise the directory's record is dropped, so that an interrupted reinstall isn't trusted.
+pub fn reusable(
+    dir: &Path,
+    asset: &str,
+    expected: &Expectations,
+) -> Option<(Vec<PathBuf>, String)> {
+    let marker = dir.join(MARKER);
+    let stored = fs::read_to_string(&marker)
+        .ok()
+        .and_then(|text| serde_json::from_str::<Stored>(&text).ok())
+        .filter(|stored| stored.asset == asset)
+        .filter(|stored| {
+            expected
+                .digests
+                .iter()
+                .all(|d| d.sha256.eq_ignore_ascii_case(&stored.sha256))
+        });
+    let files: Option<Vec<PathBuf>> = stored.as_ref().and_then(|stored| {
+        stored
+            .digests
+            .iter()
+            .map(|(name, sha256)| {
+                let path = dir.join(name);
+                file_sha256(&path)
+                    .ok()
+                    .filter(|actual| actual == sha256)
+                    .map(|_| path)
+            })
+            .collect()
+    });
+    match (stored, files) {
+        (Some(stored), Some(files)) => Some((files, stored.sha256)),
+        _ => {
+            let _ = fs::remove_file(marker);
+            None
+        }
+    }
+}
+
+/// Record that version directory `dir` holds `files` extracted from `asset`
+pub fn record(
+    dir: &Path,
+    asset: &crate::release::Asset,
+    sha256: &str,
+    files: &[PathBuf],
+) -> Result<()> {
+    let digests = file_digests(files)?
+        .into_iter()
+        .filter_map(|(path, sha256)| {
+            let name = path.file_name()?.to_string_lossy().into_owned();
+            Some((name, sha256))
+        })
+        .collect();
+    let stored = Stored {
+        asset: asset.name.clone(),
+        url: asset.browser_download_url.clone(),
+        size: asset.size,
+        sha256: sha256.to_string(),
+        digests,
+    };
+    let marker = dir.join(MARKER);
+    fs::write(&marker, serde_json::to_string_pretty(&stored)?)
+        .with_context(|| format!("Failed to write {:?}", marker))
+}
+
+/// Point `current` at version directory `dir` and link each of its `files` from `destination`,
+/// returning the links
+pub fn activate(dir: &Path, destination: &Path, files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let tool_dir = dir
+        .parent()
+        .ok_or_else(|| anyhow!("{:?} is not a version directory", dir))?;
+    let version = dir
+        .file_name()
+        .ok_or_else(|| anyhow!("{:?} is not a version directory", dir))?;
+    replace_with_link(Path::new(version), &tool_dir.join(CURRENT), true)?;
+
+    fs::create_dir_all(destination)?;
+    let mut links = Vec::with_capacity(files.len());
+    for file in files {
+        let name = file
+            .file_name()
+            .ok_or_else(|| anyhow!("{:?} has no file name", file))?;
+        let link = std::path::absolute(destination.join(name))?;
+        replace_with_link(&tool_dir.join(CURRENT).join(name), &link, false)?;
+        links.push(link);
+    }
+    Ok(links)
+}
+
+/// Versions of `name` present in `scope`, and the selected one
+pub fn versions(scope: Scope, name: &str) -> Result<(Vec<String>, Option<String>)> {
+    list(&tool_dir(scope, name)?)
+}
+
+/// Version directories in the tool directory `dir`, and the one `current` points at
+fn list(dir: &Path) -> Result<(Vec<String>, Option<String>)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok((Vec::new(), None));
+    };
+    let mut versions = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() && entry.path().join(MARKER).is_file() {
+            versions.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    versions.sort();
+    let current = fs::read_link(dir.join(CURRENT))
+        .ok()
+        .map(|target| target.to_string_lossy().into_owned());
+    Ok((versions, current))
+}
+
+/// Select version `tag` of the versioned install `name` by flipping its `current` symlink, or
+/// list the versions present without `tag`
+pub fn switch(name: &str, tag: Option<&str>, scope: Scope) -> Result<()> {
+    let mut state = State::load(scope)?;
+    let current = state
+        .tools
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("'{}' is not installed by grd", name))?;
+    if current.layout != Layout::Versioned {
+        return Err(anyhow!(
+            "'{}' was not installed with --layout versioned; reinstall it that way to keep several versions",
+            name
+        ));
+    }
+    let Some(tag) = tag else {
+        let (versions, selected) = versions(scope, name)?;
+        let rows: Vec<Vec<String>> = versions
+            .into_iter()
+            .map(|version| {
+                let mark = if selected.as_ref() == Some(&version) {
+                    "*"
+                } else {
+                    ""
+                };
+                vec![mark.to_string(), version]
+            })
+            .collect();
+        print_table(&["", "VERSION"], &rows);
+        return Ok(());
+    };
+    if current.tag == tag {
+        eprintln!("{} {} is already selected", name, tag);
+        return Ok(());
+    }
+
+    let dir = version_dir(scope, name, tag)?;
+    let stored: Stored = fs::read_to_string(dir.join(MARKER))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .ok_or_else(|| {
+            anyhow!(
+                "{} {} is not present in {:?}; install it with `grd install {} --tag {} --layout versioned`",
+                name,
+                tag,
+                tool_dir(scope, name).unwrap_or_default(),
+                current.repo,
+                tag
+            )
+        })?;
+    let files: Vec<PathBuf> = stored.digests.keys().map(|file| dir.join(file)).collect();
+
+    let change = Change {
+        tool: name,
+        repo: &current.repo,
+        from: Some(&current.tag),
+        to: tag,
+        destination: &current.destination,
+    };
+    current.hooks.run(Event::PreInstall, &change)?;
+    let links = activate(&dir, &current.destination, &files)?;
+    let files = [files, links].concat();
+    // Links to executables the selected version lacks would dangle
+    for stale in current.files.iter().filter(|f| !files.contains(f)) {
+        if !is_version_file(stale) && fs::symlink_metadata(stale).is_ok_and(|m| m.is_symlink()) {
+            fs::remove_file(stale)?;
+        }
+    }
+
+    let receipt = Receipt {
+        tag: tag.to_string(),
+        // Not recorded with the version; updates compare the tags alone
+        published_at: None,
+        asset: stored.asset,
+        url: stored.url,
+        size: stored.size,
+        sha256: stored.sha256,
+        digests: file_digests(&files)?,
+        files,
+        installed_at: state::now(),
+        previous: Some(Box::new(Receipt {
+            previous: None,
+            ..current.clone()
+        })),
+        ..current.clone()
+    };
+    state.log(name, Action::Switch, &receipt);
+    state.tools.insert(name.to_string(), receipt);
+    state.save()?;
+    current.hooks.run(Event::PostUpdate, &change)?;
+
+    eprintln!("Switched '{}' to {}", name, tag);
+    gha::notice(&format!("Switched {} to {}", name, tag));
+    Ok(())
+}
+
+/// Remove every version of `name`; with `dry_run`, only print the directory
+pub fn remove_all(scope: Scope, name: &str, dry_run: bool) -> Result<()> {
+    let dir = tool_dir(scope, name)?;
+    if !dir.exists() {
+        return Ok(());
+    }
+    if dry_run {
+        println!("{}", dir.display());
+        return Ok(());
+    }
+    fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove {:?}", dir))?;
+    eprintln!("Removed {}", dir.display());
+    Ok(())
+}
+
+/// Atomically make `link` a symlink to `target`, replacing whatever is there
+fn replace_with_link(target: &Path, link: &Path, directory: bool) -> Result<()> {
+    let name = link.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = link.with_file_name(format!(".{}.grd-tmp", name));
+    let _ = fs::remove_file(&temporary);
+    #[cfg(unix)]
+    let created = {
+        let _ = directory;
+        std::os::unix::fs::symlink(target, &temporary)
+    };
+    #[cfg(windows)]
+    let created = if directory {
+        std::os::windows::fs::symlink_dir(target, &temporary)
+    } else {
+        std::os::windows::fs::symlink_file(target, &temporary)
+    };
+    created.with_context(|| {
+        format!(
+            "Failed to create symlink {:?} (on Windows, enable Developer Mode or run elevated)",
+            temporary
+        )
+    })?;
+    // Windows can't rename over a directory symlink
+    if cfg!(windows) && directory {
+        let _ = fs::remove_dir(link);
+    }
+    fs::rename(&temporary, link).with_context(|| format!("Failed to replace {:?}", link))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::release::Asset;
+
+    fn asset(name: &str) -> Asset {
+        Asset {
+            name: name.to_string(),
+            browser_download_url: format!("https://example.com/{}", name),
+            size: 1,
+            digest: None,
+            content_type: None,
+            download_count: 0,
+        }
+    }
+
+    /// Version directory `tag` of `tool_dir` holding an `rg` that prints the tag
+    fn install(tool_dir: &Path, tag: &str) -> (PathBuf, Vec<PathBuf>) {
+        let dir = tool_dir.join(tag);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("rg");
+        fs::write(&file, tag).unwrap();
+        record(
+            &dir,
+            &asset(&format!("rg-{}.tar.gz", tag)),
+            "00",
+            std::slice::from_ref(&file),
+        )
+        .unwrap();
+        (dir, vec![file])
+    }
+
+    #[test]
+    fn current_selects_the_linked_version() {
+        let root = tempfile::tempdir().unwrap();
+        let (tool_dir, bin) = (root.path().join("tools/rg"), root.path().join("bin"));
+        let (old, old_files) = install(&tool_dir, "14.0.0");
+        let (new, new_files) = install(&tool_dir, "14.1.0");
+
+        let links = activate(&old, &bin, &old_files).unwrap();
+        assert_eq!(links, [std::path::absolute(bin.join("rg")).unwrap()]);
+        assert_eq!(fs::read_to_string(bin.join("rg")).unwrap(), "14.0.0");
+        assert!(is_version_file(&old_files[0]));
+        assert!(!is_version_file(&links[0]));
+
+        // Switching flips `current`; the link in the destination stays as it is
+        activate(&new, &bin, &new_files).unwrap();
+        assert_eq!(fs::read_to_string(bin.join("rg")).unwrap(), "14.1.0");
+        assert_eq!(
+            fs::read_link(bin.join("rg")).unwrap(),
+            tool_dir.join("current/rg")
+        );
+        assert_eq!(
+            list(&tool_dir).unwrap(),
+            (
+                vec!["14.0.0".to_string(), "14.1.0".to_string()],
+                Some("14.1.0".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn lists_nothing_before_the_first_install() {
+        let root = tempfile::tempdir().unwrap();
+        assert_eq!(list(&root.path().join("rg")).unwrap(), (Vec::new(), None));
+    }
+
+    #[test]
+    fn only_untouched_versions_are_reused() {
+        let root = tempfile::tempdir().unwrap();
+        let (dir, files) = install(root.path(), "14.1.0");
+        let expected = Expectations::default();
+        assert_eq!(
+            reusable(&dir, "rg-14.1.0.tar.gz", &expected),
+            Some((files.clone(), "00".to_string()))
+        );
+        assert_eq!(reusable(&dir, "rg-other.tar.gz", &expected), None);
+
+        // A modified file drops the record, so the version is installed again
+        let (dir, files) = install(root.path(), "14.2.0");
+        fs::write(&files[0], "tampered").unwrap();
+        assert_eq!(reusable(&dir, "rg-14.2.0.tar.gz", &expected), None);
+        assert!(!dir.join(MARKER).exists());
+    }
+}
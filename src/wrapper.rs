@@ -0,0 +1,129 @@
+//! Wrapper scripts setting environment variables and arguments around installed executables
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::{install::create_dest_dir, state::Scope};
+
+/// Environment and leading arguments an executable is always run with
+///
+/// ```toml
+/// [wrappers.mytool]
+/// env = { JAVA_HOME = "/opt/jdk-21" }
+/// args = ["--config", "/etc/mytool.toml"]
+/// ```
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Wrapper {
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Move each installed executable that has a wrapper configured into the store, and write a
+/// wrapper script in its place
+///
+/// Returns the installed files with the wrappers and the stored executables.
+pub fn wrap(
+    files: Vec<PathBuf>,
+    tag: &str,
+    wrappers: &BTreeMap<String, Wrapper>,
+    scope: Scope,
+) -> Result<Vec<PathBuf>> {
+    if wrappers.is_empty() {
+        return Ok(files);
+    }
+    let mut result = Vec::with_capacity(files.len());
+    for file in files {
+        let Some(wrapper) = exe_stem(&file).and_then(|stem| wrappers.get(stem)) else {
+            result.push(file);
+            continue;
+        };
+        let name = exe_stem(&file).unwrap_or_default().to_string();
+        // The store itself is shared; only the tool's own directories belong to the install
+        let store = std::path::absolute(scope.data_dir()?.join("store"))?;
+        fs::create_dir_all(&store)?;
+        let store_dir = store.join(&name).join(tag);
+        result.extend(create_dest_dir(&store_dir)?);
+        let stored = store_dir.join(file.file_name().unwrap_or_default());
+        move_file(&file, &stored)?;
+
+        let script = script_path(&file);
+        fs::write(&script, render(wrapper, &stored))
+            .with_context(|| format!("Failed to write wrapper {:?}", script))?;
+        #[cfg(unix)]
+        crate::install::set_permissions(&script)?;
+        eprintln!("Wrapped {} in {:?}", name, script);
+        result.push(stored);
+        result.push(script);
+    }
+    Ok(result)
+}
+
+/// Executable name without `.exe`, or `None` for non-executable files
+fn exe_stem(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    if cfg!(windows) {
+        name.strip_suffix(".exe")
+    } else {
+        Some(name)
+    }
+}
+
+/// Where the wrapper of `exe` goes: in its place, or next to it as a `.cmd` script on Windows
+fn script_path(exe: &Path) -> PathBuf {
+    if cfg!(windows) {
+        exe.with_extension("cmd")
+    } else {
+        exe.to_path_buf()
+    }
+}
+
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    // The store may be on another filesystem than the destination
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to).with_context(|| format!("Failed to copy {:?} to {:?}", from, to))?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+fn render(wrapper: &Wrapper, target: &Path) -> String {
+    if cfg!(windows) {
+        let mut script = String::from("@echo off\r\nrem Generated by grd\r\n");
+        for (key, value) in &wrapper.env {
+            script.push_str(&format!("set \"{}={}\"\r\n", key, value));
+        }
+        let args: Vec<String> = wrapper.args.iter().map(|a| format!("\"{}\"", a)).collect();
+        script.push_str(&format!(
+            "\"{}\" {} %*\r\n",
+            target.display(),
+            args.join(" ")
+        ));
+        script
+    } else {
+        let mut script = String::from("#!/bin/sh\n# Generated by grd\n");
+        for (key, value) in &wrapper.env {
+            script.push_str(&format!("export {}={}\n", key, sh_quote(value)));
+        }
+        let args: Vec<String> = wrapper.args.iter().map(|a| sh_quote(a)).collect();
+        script.push_str(&format!(
+            "exec {} {}\"$@\"\n",
+            sh_quote(&target.to_string_lossy()),
+            args.iter().map(|a| format!("{} ", a)).collect::<String>()
+        ));
+        script
+    }
+}
+
+/// Quote a string for POSIX shells, taking it literally
+pub fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}